@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+
+use crate::pep517::Builder;
+
+/// Which distributions `ezpy build` creates.
+#[derive(Debug, Clone, Copy)]
+pub struct Targets {
+    pub sdist: bool,
+    pub wheel: bool,
+}
+
+/// Builds the sdist and/or wheel of the project at `project` into `output` (its `dist`
+/// directory by default), calling its PEP 517 backend in an isolated environment created
+/// from the managed interpreter selected by `python`.
+pub async fn build(
+    project: &Path,
+    output: Option<PathBuf>,
+    targets: Targets,
+    python: Option<VersionString>,
+) -> eyre::Result<()> {
+    // The project may be elsewhere than the current directory, and pins its own version.
+    let project_dir = std::path::absolute(project)?;
+    let resolved = crate::pin::resolve_in(&project_dir, python, false).await?;
+    crate::pin::check_requires_python_in(&project_dir, &resolved).await?;
+
+    let builder = Builder::new(project, &resolved.version).await?;
+
+    let output = output.unwrap_or_else(|| builder.project.join("dist"));
+    tokio::fs::create_dir_all(&output).await?;
+    let output = output.canonicalize()?;
+
+    let mut kinds = Vec::new();
+    if targets.sdist {
+        kinds.push("sdist");
+    }
+    if targets.wheel {
+        kinds.push("wheel");
+    }
+
+    println!(
+        "Building {} of {} with Python {}",
+        kinds.join(" and "),
+        builder.project.display(),
+        resolved.version
+    );
+
+    for kind in kinds {
+        builder.install_requires_for(kind).await?;
+
+        let built = builder.build(kind, &output).await?.ok_or_else(|| {
+            eyre::eyre!("The build backend of the project can't build a {}", kind)
+        })?;
+
+        println!("Built {}", built.display());
+    }
+
+    Ok(())
+}
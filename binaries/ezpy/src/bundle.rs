@@ -0,0 +1,346 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pyvenv::PyvenvConfig;
+
+const MANIFEST: &str = "bundle.toml";
+const PYTHON_DIR: &str = "python";
+const ENV_DIR: &str = "env";
+
+/// Describes the content of a bundle, stored as `bundle.toml` at the root of the archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub name: String,
+    pub python: String,
+
+    /// The base interpreter, relative to the `python` directory of the bundle.
+    pub executable: String,
+
+    pub os: String,
+    pub arch: String,
+    pub ezpy: String,
+}
+
+/// Packs the global environment `name` and its base interpreter into a zstd compressed tarball.
+///
+/// Console scripts are given a launcher that finds the environment's interpreter next to
+/// itself, so the bundle keeps working wherever it's unpacked. Windows `.exe` launchers
+/// embed an absolute path and are copied as is.
+pub async fn create_bundle(name: String, output: PathBuf) -> eyre::Result<()> {
+    let env = crate::venv::get_global_env(name.clone())?;
+    let config = PyvenvConfig::read(&env).await?;
+
+    let python = tokio::fs::canonicalize(indygreg::python_path(config.version.clone())?)
+        .await
+        .map_err(|_| {
+            eyre::eyre!(
+                "Python {} is not installed by ezpy, only environments based on an ezpy-managed interpreter can be bundled",
+                config.version
+            )
+        })?;
+
+    let executable = tokio::fs::canonicalize(&config.executable)
+        .await
+        .ok()
+        .and_then(|executable| executable.strip_prefix(&python).ok().map(Path::to_path_buf))
+        .unwrap_or_else(crate::python_bin_path);
+
+    let manifest = BundleManifest {
+        name: name.clone(),
+        python: config.version.clone(),
+        executable: executable
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        ezpy: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    println!(
+        "Bundling {} with Python {} into {}...",
+        name,
+        config.version,
+        output.display()
+    );
+
+    let destination = output.clone();
+    let written =
+        tokio::task::spawn_blocking(move || write_bundle(&destination, &manifest, &python, &env))
+            .await?;
+
+    if let Err(e) = written {
+        tokio::fs::remove_file(&output).await.ok();
+        return Err(e);
+    }
+
+    let size = tokio::fs::metadata(&output).await?.len();
+    println!(
+        "Created bundle {} ({})",
+        output.display(),
+        crate::inspect::format_size(size)
+    );
+
+    Ok(())
+}
+
+fn write_bundle(
+    output: &Path,
+    manifest: &BundleManifest,
+    python: &Path,
+    env: &Path,
+) -> eyre::Result<()> {
+    let file = std::fs::File::create(output)
+        .map_err(|e| eyre::eyre!("Failed to create {}: {}", output.display(), e))?;
+    let encoder = zstd::Encoder::new(file, 0)?.auto_finish();
+
+    let mut builder = tar::Builder::new(encoder);
+    builder.follow_symlinks(false);
+
+    append_file(
+        &mut builder,
+        MANIFEST,
+        toml::to_string(manifest)?.as_bytes(),
+    )?;
+    builder.append_dir_all(PYTHON_DIR, python)?;
+    append_env(&mut builder, env, env, python)?;
+
+    builder.into_inner()?;
+
+    Ok(())
+}
+
+fn append_env<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    env: &Path,
+    dir: &Path,
+    python: &Path,
+) -> eyre::Result<()> {
+    let scripts = env.join(crate::env_scripts_path());
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let kind = entry.file_type()?;
+        let name = Path::new(ENV_DIR).join(path.strip_prefix(env)?);
+
+        if entry.file_name() == "__pycache__" {
+            continue;
+        }
+
+        let bundled = kind
+            .is_symlink()
+            .then(|| bundled_target(&path, &name, python))
+            .flatten();
+
+        if let Some(target) = bundled {
+            // Point the environment at the interpreter shipped next to it.
+            append_symlink(builder, &name, &target)?;
+        } else if kind.is_dir() {
+            builder.append_dir(&name, &path)?;
+            append_env(builder, env, &path, python)?;
+        } else if kind.is_file() && dir == scripts {
+            match relocatable_script(&path, &scripts)? {
+                Some(contents) => append_file(builder, &name, contents.as_bytes())?,
+                None => builder.append_path_with_name(&path, &name)?,
+            }
+        } else {
+            builder.append_path_with_name(&path, &name)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The target inside the bundle of the symlink at `path`, whose entry is `name`, when it's an
+/// absolute link into the interpreter install at `python` (`python`, `python3`,
+/// `python3.X`, ...).
+fn bundled_target(path: &Path, name: &Path, python: &Path) -> Option<PathBuf> {
+    if !std::fs::read_link(path).ok()?.is_absolute() {
+        return None;
+    }
+
+    let target = std::fs::canonicalize(path).ok()?;
+    let relative = target.strip_prefix(python).ok()?;
+
+    let mut bundled = PathBuf::new();
+    for _ in 1..name.components().count() {
+        bundled.push("..");
+    }
+
+    Some(bundled.join(PYTHON_DIR).join(relative))
+}
+
+/// Replaces the absolute shebang of a console script with a `/bin/sh` trampoline running the
+/// interpreter found next to the script.
+fn relocatable_script(path: &Path, scripts: &Path) -> eyre::Result<Option<String>> {
+    let mut contents = String::new();
+    if std::fs::File::open(path)?
+        .read_to_string(&mut contents)
+        .is_err()
+    {
+        return Ok(None);
+    }
+
+    let Some((shebang, rest)) = contents.split_once('\n') else {
+        return Ok(None);
+    };
+
+    let Some(interpreter) = shebang.strip_prefix("#!") else {
+        return Ok(None);
+    };
+
+    let interpreter = Path::new(interpreter.trim());
+    if interpreter.parent() != Some(scripts) {
+        return Ok(None);
+    }
+
+    let program = interpreter.file_name().unwrap().to_string_lossy();
+
+    Ok(Some(format!(
+        "#!/bin/sh\n'''exec' \"$(dirname -- \"$(realpath -- \"$0\")\")\"/'{}' \"$0\" \"$@\"\n' '''\n{}",
+        program, rest
+    )))
+}
+
+fn append_file<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    name: impl AsRef<Path>,
+    contents: &[u8],
+) -> eyre::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o755);
+    header.set_mtime(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+    );
+
+    builder.append_data(&mut header, name, contents)?;
+
+    Ok(())
+}
+
+fn append_symlink<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    name: &Path,
+    target: &Path,
+) -> eyre::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    header.set_mode(0o777);
+
+    builder.append_link(&mut header, name, target)?;
+
+    Ok(())
+}
+
+/// Unpacks a bundle into `dir`, which defaults to a directory named after the bundled
+/// environment, and points the environment at the unpacked interpreter.
+pub async fn install_bundle(archive: PathBuf, dir: Option<PathBuf>) -> eyre::Result<()> {
+    let manifest = read_manifest(&archive).await?;
+
+    if manifest.os != std::env::consts::OS || manifest.arch != std::env::consts::ARCH {
+        eyre::bail!(
+            "Bundle {} was created for {}-{}, it can't be installed on {}-{}",
+            archive.display(),
+            manifest.os,
+            manifest.arch,
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        );
+    }
+
+    let dir = match dir {
+        Some(dir) => dir,
+        None => std::env::current_dir()?.join(&manifest.name),
+    };
+
+    if dir.exists() && std::fs::read_dir(&dir)?.next().is_some() {
+        eyre::bail!(
+            "Directory {} already exists and is not empty",
+            dir.display()
+        );
+    }
+
+    tokio::fs::create_dir_all(&dir).await?;
+    let dir = tokio::fs::canonicalize(&dir).await?;
+
+    let (source, destination) = (archive.clone(), dir.clone());
+    let unpacked = tokio::task::spawn_blocking(move || -> eyre::Result<()> {
+        let file = std::fs::File::open(&source)?;
+        let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+        archive.set_preserve_permissions(true);
+        archive.unpack(&destination)?;
+
+        Ok(())
+    })
+    .await?;
+
+    if let Err(e) = unpacked {
+        tokio::fs::remove_dir_all(&dir).await.ok();
+        return Err(e);
+    }
+
+    let env = dir.join(ENV_DIR);
+    let executable = dir.join(PYTHON_DIR).join(&manifest.executable);
+
+    let config = PyvenvConfig {
+        home: executable.parent().unwrap().to_path_buf(),
+        include_system_site_packages: false,
+        version: manifest.python.clone(),
+        executable,
+        prompt: Some(manifest.name.clone()),
+    };
+
+    config.write(&env).await?;
+    crate::pyvenv::write_activation_scripts(&env, &manifest.name).await?;
+
+    println!(
+        "Installed bundle {} with Python {} at {}",
+        manifest.name,
+        manifest.python,
+        dir.display()
+    );
+    println!(
+        "Run its interpreter with {}",
+        env.join(crate::env_bin_path()).display()
+    );
+
+    Ok(())
+}
+
+async fn read_manifest(archive: &Path) -> eyre::Result<BundleManifest> {
+    let path = archive.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::open(&path)
+            .map_err(|e| eyre::eyre!("Failed to open {}: {}", path.display(), e))?;
+        let mut archive = tar::Archive::new(zstd::Decoder::new(file)?);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()? != Path::new(MANIFEST) {
+                continue;
+            }
+
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+
+            return toml::from_str(&contents)
+                .map_err(|e| eyre::eyre!("Invalid bundle manifest in {}: {}", path.display(), e));
+        }
+
+        Err(eyre::eyre!(
+            "{} is not an ezpy bundle, {} is missing",
+            path.display(),
+            MANIFEST
+        ))
+    })
+    .await?
+}
@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// Name of the per-project configuration file.
+pub const PROJECT_CONFIG_FILE: &str = ".ezpy.toml";
+
+/// Settings read from a project's `.ezpy.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ProjectConfig {
+    /// A global environment to use for this project instead of a local `.venv`.
+    pub env: Option<String>,
+
+    /// Directory names recognized as local environments, `.venv` by default.
+    pub env_names: Option<Vec<String>>,
+}
+
+impl ProjectConfig {
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+
+        toml::from_str(&contents)
+            .map_err(|e| eyre::eyre!("Failed to parse {}: {}", path.display(), e))
+    }
+}
+
+/// Settings read from the user's `config.toml`, see [`user_config_path`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct UserConfig {
+    /// Package indexes to publish to, by name.
+    #[serde(default)]
+    pub repositories: BTreeMap<String, RepositoryConfig>,
+}
+
+/// A `[repositories.<name>]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RepositoryConfig {
+    /// The legacy upload URL of the index.
+    pub url: Option<String>,
+    pub username: Option<String>,
+
+    /// The password, or an API token when no username is set.
+    pub password: Option<String>,
+}
+
+impl UserConfig {
+    /// Reads the user configuration, which is empty when the file doesn't exist.
+    pub fn load() -> eyre::Result<Self> {
+        let path = user_config_path()?;
+        if !path.exists() {
+            return Ok(UserConfig::default());
+        }
+
+        let contents = std::fs::read_to_string(&path)?;
+        toml::from_str(&contents)
+            .map_err(|e| eyre::eyre!("Failed to parse {}: {}", path.display(), e))
+    }
+}
+
+/// The user configuration file: `EZPY_CONFIG` when set, otherwise `config.toml` in
+/// `$XDG_CONFIG_HOME/ezpy` (`~/.config/ezpy` by default, `~/ezpy` on Windows).
+pub fn user_config_path() -> eyre::Result<PathBuf> {
+    if let Some(path) = std::env::var_os("EZPY_CONFIG") {
+        return Ok(PathBuf::from(path));
+    }
+
+    let home = simple_home_dir::home_dir().ok_or_else(|| {
+        eyre::eyre!("Failed to get home directory, your home directory is not set")
+    })?;
+
+    let dir = if cfg!(windows) {
+        home.join("ezpy")
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| home.join(".config"))
+            .join("ezpy")
+    };
+
+    Ok(dir.join("config.toml"))
+}
@@ -0,0 +1,198 @@
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+
+use crate::install_home_ezpy;
+use crate::pyvenv::PyvenvConfig;
+use crate::venv::EnvOptions;
+
+/// Checks every environment for a missing or replaced base interpreter.
+pub async fn doctor() -> eyre::Result<()> {
+    let mut envs: Vec<(String, PathBuf)> = Vec::new();
+
+    let global_dir = install_home_ezpy()?.join("env");
+    if let Some(local) = crate::venv::find_nearest_env(&std::env::current_dir()?).unwrap_or(None) {
+        if !local.starts_with(&global_dir) {
+            envs.push((local.display().to_string(), local));
+        }
+    }
+
+    if global_dir.exists() {
+        let mut entries = tokio::fs::read_dir(&global_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                envs.push((
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path(),
+                ));
+            }
+        }
+    }
+
+    envs.sort();
+
+    let mut broken = 0;
+    for (name, path) in &envs {
+        let issues = diagnose(path).await;
+
+        if issues.is_empty() {
+            println!("{}: ok", name);
+            continue;
+        }
+
+        broken += 1;
+        println!("{}: broken", name);
+        for issue in issues {
+            println!("  - {}", issue);
+        }
+
+        if path.starts_with(&global_dir) {
+            println!(
+                "  Fix it with `ezpy env rebase {} --python {}`",
+                name,
+                suggested_version(path).await
+            );
+        } else {
+            println!(
+                "  Fix it by deleting {} and running `ezpy env`",
+                path.display()
+            );
+        }
+    }
+
+    if envs.is_empty() {
+        println!("No virtual environments found");
+    }
+
+    if broken > 0 {
+        eyre::bail!("{} virtual environment(s) need attention", broken);
+    }
+
+    Ok(())
+}
+
+/// Lists what's wrong with the environment at `env`, if anything.
+pub async fn diagnose(env: &Path) -> Vec<String> {
+    let config = match PyvenvConfig::read(env).await {
+        Ok(config) => config,
+        Err(_) => return vec!["missing pyvenv.cfg, this isn't a virtual environment".to_string()],
+    };
+
+    let mut issues = Vec::new();
+
+    if !config.executable.exists() {
+        issues.push(format!(
+            "base interpreter {} no longer exists",
+            config.executable.display()
+        ));
+    } else if !config.home.exists() {
+        issues.push(format!("home {} no longer exists", config.home.display()));
+    }
+
+    let python = env.join(crate::env_bin_path());
+    if !python.exists() {
+        issues.push(format!("{} is dangling or missing", python.display()));
+    }
+
+    if !config.version.is_empty()
+        && indygreg::python_path(config.version.clone()).is_ok_and(|path| !path.exists())
+        && config
+            .executable
+            .starts_with(indygreg::install::install_home_indygreg().unwrap_or_default())
+    {
+        issues.push(format!("Python {} is no longer installed", config.version));
+    }
+
+    issues
+}
+
+/// The newest installed version sharing the environment's minor version, or the newest overall.
+async fn suggested_version(env: &Path) -> VersionString {
+    let minor = PyvenvConfig::read(env)
+        .await
+        .map(|config| crate::pyvenv::short_version(&config.version))
+        .unwrap_or_default();
+
+    crate::pin::resolve_installed(&minor)
+        .or_else(|_| {
+            crate::pin::best_installed(&crate::pep440::SpecifierSet::default())?
+                .ok_or_else(|| eyre::eyre!("No Python installed"))
+        })
+        .unwrap_or("<VERSION>".to_string())
+}
+
+/// Recreates the global environment `name` on another interpreter, reinstalling its packages.
+pub async fn rebase_env(
+    name: String,
+    version: Option<VersionString>,
+    options: EnvOptions,
+) -> eyre::Result<()> {
+    let env = crate::venv::get_global_env(name.clone())?;
+    let version = crate::pin::resolve(version, options.install_missing)
+        .await?
+        .version;
+
+    // Packages installed from a URL or in editable mode are reinstalled from where they came
+    // from, as `ezpy freeze` lists them.
+    let mut packages = Vec::new();
+    if let Ok(config) = PyvenvConfig::read(&env).await {
+        let site_packages = config.site_packages(&env);
+        let installed = crate::inspect::installed_distributions(&site_packages)
+            .await
+            .unwrap_or_default();
+
+        for (name, version) in installed {
+            if !crate::venv::SEEDED_PACKAGES.contains(&name.to_lowercase().as_str()) {
+                packages.push(
+                    crate::install::frozen_requirement(&site_packages, &name, &version).await,
+                );
+            }
+        }
+    }
+    let args = packages
+        .iter()
+        .flat_map(|package| match package.strip_prefix("-e ") {
+            Some(path) => vec!["-e".to_string(), path.to_string()],
+            None => vec![package.clone()],
+        })
+        .collect::<Vec<_>>();
+
+    // The backup is kept out of the env directory, so it's never listed as an environment.
+    let backup = crate::cache_home_ezpy()?.join("rebase-backup").join(&name);
+    tokio::fs::remove_dir_all(&backup).await.ok();
+    tokio::fs::create_dir_all(backup.parent().unwrap()).await?;
+    tokio::fs::rename(&env, &backup).await?;
+
+    let result = async {
+        crate::venv::create_env(&version, &env, &name, options).await?;
+
+        if !packages.is_empty() {
+            println!("Reinstalling {} package(s)...", packages.len());
+            crate::install::install_packages(args, Some(name.clone())).await?;
+        }
+
+        Ok::<(), eyre::Report>(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tokio::fs::remove_dir_all(&env).await.ok();
+        tokio::fs::rename(&backup, &env).await?;
+
+        return Err(e.wrap_err(format!(
+            "Failed to rebase {}, the original environment was restored",
+            name
+        )));
+    }
+
+    tokio::fs::remove_dir_all(&backup).await?;
+
+    println!(
+        "Rebased global virtual environment {} on Python {} with {} package(s)",
+        name,
+        version,
+        packages.len()
+    );
+
+    Ok(())
+}
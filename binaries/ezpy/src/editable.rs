@@ -0,0 +1,287 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::install::Sources;
+use crate::pep440::Version;
+use crate::pep508::{InterpreterInfo, Requirement};
+use crate::pep517::Builder;
+use crate::venv::resolve_env;
+use crate::wheel::{Target, Wheel};
+
+/// Installs the local projects at `paths` in editable mode, as described by PEP 660: the
+/// project's backend builds an editable wheel in an isolated build environment, which is
+/// installed along with the project's dependencies. Dependencies come from `sources` when
+/// given, otherwise they're installed with pip.
+pub async fn install_editable(
+    paths: Vec<PathBuf>,
+    sources: Option<Sources>,
+    global: Option<String>,
+) -> eyre::Result<()> {
+    let venv = resolve_env(global.clone()).await?;
+    let target = Target::new(&venv).await?;
+    let markers = InterpreterInfo::query(&target.python).await?.markers();
+
+    for path in paths {
+        let builder = Builder::new(&path, &target.version).await?;
+        let wheel = build_editable(&builder).await?;
+
+        let (metadata, wheel) = tokio::task::spawn_blocking(move || -> eyre::Result<_> {
+            let metadata = Wheel::open(&wheel)?.metadata()?;
+            Ok((metadata, wheel))
+        })
+        .await??;
+
+        let installed = target.installed()?;
+        let mut dependencies = Vec::new();
+        for dependency in &metadata.requires_dist {
+            let mut requirement: Requirement = dependency.parse()?;
+            if !requirement.evaluate(&markers, &[]) {
+                continue;
+            }
+
+            let satisfied = installed
+                .get(&requirement.normalized_name())
+                .and_then(|version| version.parse::<Version>().ok())
+                .is_some_and(|version| {
+                    requirement.url.is_none() && requirement.specifiers.contains(&version)
+                });
+            if !satisfied {
+                // The marker was evaluated above, and pip can't be given it on the command line.
+                requirement.marker = None;
+                dependencies.push(requirement.to_string());
+            }
+        }
+
+        if !dependencies.is_empty() {
+            match &sources {
+                Some(sources) => {
+                    crate::install::install_wheels(dependencies, sources.clone(), global.clone())
+                        .await?
+                }
+                None => crate::install::install_packages(dependencies, global.clone()).await?,
+            }
+        }
+
+        let direct_url = serde_json::json!({
+            "url": file_url(&builder.project),
+            "dir_info": { "editable": true },
+        })
+        .to_string();
+
+        let install_target = target.clone();
+        tokio::task::spawn_blocking(move || {
+            Wheel::open(&wheel)?.install_from(&install_target, Some(&direct_url))
+        })
+        .await??;
+
+        println!(
+            "Installed {}-{} in editable mode from {}",
+            metadata.name,
+            metadata.version,
+            builder.project.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Builds the editable wheel of the project, making one pointing at its sources with a
+/// `.pth` file when the backend doesn't support PEP 660.
+async fn build_editable(builder: &Builder) -> eyre::Result<PathBuf> {
+    let wheels = builder.scratch("wheels").await?;
+
+    builder.install_requires_for("editable").await?;
+    if let Some(wheel) = builder.build("editable", &wheels).await? {
+        return Ok(wheel);
+    }
+
+    println!(
+        "The build backend of {} doesn't support editable installs, adding its sources to the path instead",
+        builder.project.display()
+    );
+
+    builder.install_requires_for("wheel").await?;
+
+    let metadata = builder.scratch("metadata").await?;
+    let dist_info = match builder.prepare_metadata("wheel", &metadata).await? {
+        Some(dist_info) => dist_info,
+        None => {
+            let wheel = builder
+                .build("wheel", &builder.scratch("full").await?)
+                .await?
+                .ok_or_else(|| eyre::eyre!("The build backend has no build_wheel hook"))?;
+            let metadata = metadata.clone();
+            tokio::task::spawn_blocking(move || extract_dist_info(&wheel, &metadata)).await??
+        }
+    };
+
+    let project = builder.project.clone();
+    tokio::task::spawn_blocking(move || pth_wheel(&project, &dist_info, &wheels)).await?
+}
+
+/// Extracts the `.dist-info` directory of `wheel` into `directory`.
+fn extract_dist_info(wheel: &Path, directory: &Path) -> eyre::Result<PathBuf> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(wheel)?)?;
+    let mut dist_info = None;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let Some(relative) = file.enclosed_name() else {
+            continue;
+        };
+
+        let Some(top) = relative.components().next() else {
+            continue;
+        };
+        if !top.as_os_str().to_string_lossy().ends_with(".dist-info") || file.is_dir() {
+            continue;
+        }
+
+        let destination = directory.join(&relative);
+        std::fs::create_dir_all(destination.parent().unwrap())?;
+        std::io::copy(&mut file, &mut std::fs::File::create(&destination)?)?;
+        dist_info = Some(directory.join(top));
+    }
+
+    dist_info.ok_or_else(|| eyre::eyre!("{} has no .dist-info directory", wheel.display()))
+}
+
+/// Writes a wheel made of the metadata in `dist_info` and a `.pth` file adding the sources
+/// of `project` (its `src` directory when it has one) to `sys.path`.
+fn pth_wheel(project: &Path, dist_info: &Path, directory: &Path) -> eyre::Result<PathBuf> {
+    let dist_info_name = dist_info
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (name, version) = dist_info_name
+        .strip_suffix(".dist-info")
+        .and_then(|stem| stem.split_once('-'))
+        .ok_or_else(|| eyre::eyre!("Invalid metadata directory {}", dist_info.display()))?;
+
+    let sources = match project.join("src") {
+        src if src.is_dir() => src,
+        _ => project.to_path_buf(),
+    };
+
+    let mut files = vec![(
+        format!("__editable__.{}-{}.pth", name, version),
+        format!("{}\n", sources.display()).into_bytes(),
+    )];
+
+    for entry in std::fs::read_dir(dist_info)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_file() && !matches!(file_name.as_str(), "RECORD" | "WHEEL") {
+            files.push((
+                format!("{}/{}", dist_info_name, file_name),
+                std::fs::read(entry.path())?,
+            ));
+        }
+    }
+
+    files.push((
+        format!("{}/WHEEL", dist_info_name),
+        b"Wheel-Version: 1.0\nGenerator: ezpy\nRoot-Is-Purelib: true\nTag: py3-none-any\n".to_vec(),
+    ));
+
+    let mut record = files
+        .iter()
+        .map(|(path, contents)| {
+            format!(
+                "{},{},{}\n",
+                path,
+                crate::wheel::digest(contents),
+                contents.len()
+            )
+        })
+        .collect::<String>();
+    record.push_str(&format!("{}/RECORD,,\n", dist_info_name));
+    files.push((format!("{}/RECORD", dist_info_name), record.into_bytes()));
+
+    let path = directory.join(format!("{}-{}-py3-none-any.whl", name, version));
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&path)?);
+    for (name, contents) in files {
+        writer.start_file(name, zip::write::SimpleFileOptions::default())?;
+        writer.write_all(&contents)?;
+    }
+    writer.finish()?;
+
+    Ok(path)
+}
+
+fn file_url(path: &Path) -> String {
+    reqwest::Url::from_file_path(path)
+        .map(|url| url.to_string())
+        .unwrap_or_else(|_| format!("file://{}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wheel::tests::{temp_dir, test_target};
+
+    #[test]
+    fn writes_pth_wheels() {
+        let dir = temp_dir("pth-wheel");
+        let project = dir.join("project");
+        std::fs::create_dir_all(project.join("src")).unwrap();
+        let dist_info = dir.join("metadata").join("demo-1.0.dist-info");
+        std::fs::create_dir_all(&dist_info).unwrap();
+        std::fs::write(
+            dist_info.join("METADATA"),
+            "Metadata-Version: 2.1\nName: demo\nVersion: 1.0\n",
+        )
+        .unwrap();
+        std::fs::write(dist_info.join("RECORD"), "stale,,\n").unwrap();
+
+        let wheel = pth_wheel(&project, &dist_info, &dir).unwrap();
+        assert_eq!(wheel, dir.join("demo-1.0-py3-none-any.whl"));
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&wheel).unwrap()).unwrap();
+        let mut record = String::new();
+        std::io::Read::read_to_string(
+            &mut archive.by_name("demo-1.0.dist-info/RECORD").unwrap(),
+            &mut record,
+        )
+        .unwrap();
+        let mut paths = record
+            .lines()
+            .map(|line| line.split(',').next().unwrap())
+            .collect::<Vec<_>>();
+        paths.sort();
+        assert_eq!(
+            paths,
+            [
+                "__editable__.demo-1.0.pth",
+                "demo-1.0.dist-info/METADATA",
+                "demo-1.0.dist-info/RECORD",
+                "demo-1.0.dist-info/WHEEL",
+            ]
+        );
+        assert!(record.contains("demo-1.0.dist-info/RECORD,,\n"));
+
+        let target = test_target(&dir.join("env"));
+        let direct_url = r#"{"url":"file:///project","dir_info":{"editable":true}}"#;
+        Wheel::open(&wheel)
+            .unwrap()
+            .install_from(&target, Some(direct_url))
+            .unwrap();
+
+        // The sources are found through the `src` layout of the project.
+        assert_eq!(
+            std::fs::read_to_string(target.site_packages.join("__editable__.demo-1.0.pth"))
+                .unwrap(),
+            format!("{}\n", project.join("src").display())
+        );
+        let installed = target.site_packages.join("demo-1.0.dist-info");
+        assert_eq!(
+            std::fs::read_to_string(installed.join("direct_url.json")).unwrap(),
+            direct_url
+        );
+        assert!(std::fs::read_to_string(installed.join("RECORD"))
+            .unwrap()
+            .contains("direct_url.json"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
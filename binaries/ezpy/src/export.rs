@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pyvenv::PyvenvConfig;
+use crate::venv::EnvOptions;
+
+/// A reproducible description of a global environment, as written by `ezpy env export`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvExport {
+    pub name: String,
+    pub python: String,
+
+    /// Frozen requirements (`name==version`) of every installed package.
+    pub packages: Vec<String>,
+}
+
+pub async fn export_env(name: String) -> eyre::Result<()> {
+    let env = crate::venv::get_global_env(name.clone())?;
+    let config = PyvenvConfig::read(&env).await?;
+
+    let packages = crate::inspect::installed_distributions(&config.site_packages(&env))
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(name, _)| !name.eq_ignore_ascii_case("pip"))
+        .map(|(name, version)| format!("{}=={}", name, version))
+        .collect();
+
+    let export = EnvExport {
+        name,
+        python: config.version,
+        packages,
+    };
+
+    print!("{}", toml::to_string(&export)?);
+
+    Ok(())
+}
+
+pub async fn import_env(
+    file: &Path,
+    name: Option<String>,
+    options: EnvOptions,
+) -> eyre::Result<()> {
+    let contents = tokio::fs::read_to_string(file)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to read {}: {}", file.display(), e))?;
+    let export: EnvExport = toml::from_str(&contents)
+        .map_err(|e| eyre::eyre!("Failed to parse {}: {}", file.display(), e))?;
+
+    let name = name.unwrap_or(export.name);
+
+    crate::venv::create_global_env(Some(export.python), name.clone(), options).await?;
+
+    if !export.packages.is_empty() {
+        println!("Installing {} package(s)...", export.packages.len());
+        crate::install::install_packages(export.packages, Some(name)).await?;
+    }
+
+    Ok(())
+}
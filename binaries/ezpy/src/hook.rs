@@ -0,0 +1,331 @@
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::shell::Shell;
+
+/// Environment variable holding the environment activated by the hook.
+pub const HOOK_ENV_VAR: &str = "EZPY_HOOK_ENV";
+
+/// Prints the hook to add to the user's shell configuration.
+pub fn print_hook(shell: Shell) -> eyre::Result<()> {
+    let exe = std::env::current_exe()?;
+    let exe = shell.quote(&exe.display().to_string());
+
+    // The hook only calls back into ezpy when the working directory changed, and ezpy
+    // answers from its cache unless something that decided the environment changed, so
+    // prompts stay fast even deep inside large repositories.
+    let hook = match shell {
+        Shell::Bash => format!(
+            r#"_ezpy_hook() {{
+    local previous_exit_status=$?
+    if [ "${{_EZPY_HOOK_PWD:-}}" != "$PWD" ]; then
+        _EZPY_HOOK_PWD="$PWD"
+        eval "$({exe} hook-env bash)"
+    fi
+    return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_ezpy_hook;"* ]]; then
+    PROMPT_COMMAND="_ezpy_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi
+"#
+        ),
+        Shell::Zsh => format!(
+            r#"_ezpy_hook() {{
+    if [[ "${{_EZPY_HOOK_PWD:-}}" != "$PWD" ]]; then
+        _EZPY_HOOK_PWD="$PWD"
+        eval "$({exe} hook-env zsh)"
+    fi
+}}
+typeset -ag precmd_functions
+if (( ! ${{precmd_functions[(I)_ezpy_hook]}} )); then
+    precmd_functions=(_ezpy_hook $precmd_functions)
+fi
+"#
+        ),
+        Shell::Fish => format!(
+            r#"function _ezpy_hook --on-variable PWD --description 'Activate ezpy environments on directory change'
+    if test "$_EZPY_HOOK_PWD" != "$PWD"
+        set -g _EZPY_HOOK_PWD $PWD
+        {exe} hook-env fish | source
+    end
+end
+_ezpy_hook
+"#
+        ),
+        _ => eyre::bail!("Shell hooks are only available for bash, zsh and fish"),
+    };
+
+    print!("{}", hook);
+
+    Ok(())
+}
+
+/// Prints the code the hook evaluates to switch environments after a directory change.
+pub fn print_hook_env(shell: Shell) -> eyre::Result<()> {
+    let current = std::env::var_os(HOOK_ENV_VAR)
+        .filter(|env| !env.is_empty())
+        .map(PathBuf::from);
+    let active = std::env::var_os("VIRTUAL_ENV").map(PathBuf::from);
+    let target = cached_nearest_env(&std::env::current_dir()?).unwrap_or(None);
+
+    print!("{}", hook_env_code(shell, current, active, target));
+
+    Ok(())
+}
+
+/// The code switching from `current`, the environment the hook activated, to `target`.
+fn hook_env_code(
+    shell: Shell,
+    current: Option<PathBuf>,
+    active: Option<PathBuf>,
+    target: Option<PathBuf>,
+) -> String {
+    if current == target {
+        return String::new();
+    }
+
+    // Leave environments activated by hand alone.
+    if active.is_some() && active != current {
+        return String::new();
+    }
+
+    let mut code = String::new();
+
+    if current.is_some() {
+        code.push_str(&crate::shell::deactivate_code(shell));
+        code.push_str(&unset_var(shell, HOOK_ENV_VAR));
+    }
+
+    if let Some(target) = target {
+        code.push_str(&crate::shell::activate_code(shell, &target));
+        code.push_str(&set_var(shell, HOOK_ENV_VAR, &target.display().to_string()));
+    }
+
+    code
+}
+
+/// The environment found for a directory, with the modification times of the paths that
+/// decided it.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct CacheEntry {
+    key: String,
+    stamp: Vec<(PathBuf, Option<u128>)>,
+    env: Option<PathBuf>,
+}
+
+/// Same as [`crate::venv::find_nearest_env`], answered from a cache entry for `start`
+/// while no ancestor directory, `.ezpy.toml` or the environment found was modified.
+fn cached_nearest_env(start: &Path) -> eyre::Result<Option<PathBuf>> {
+    cached_nearest_env_in(&crate::cache_home_ezpy()?.join("hook"), start)
+}
+
+fn cached_nearest_env_in(cache: &Path, start: &Path) -> eyre::Result<Option<PathBuf>> {
+    let key = cache_key(start);
+    let path = cache.join(format!("{:.16x}", Sha256::digest(key.as_bytes())));
+
+    let cached = std::fs::read(&path)
+        .ok()
+        .and_then(|contents| serde_json::from_slice::<CacheEntry>(&contents).ok())
+        .filter(|entry| entry.key == key && entry.stamp == stamp(start, entry.env.as_deref()));
+    if let Some(entry) = cached {
+        return Ok(entry.env);
+    }
+
+    let env = crate::venv::find_nearest_env(start)?;
+
+    let entry = CacheEntry {
+        key,
+        stamp: stamp(start, env.as_deref()),
+        env: env.clone(),
+    };
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).ok();
+    }
+    std::fs::write(&path, serde_json::to_vec(&entry)?).ok();
+
+    Ok(env)
+}
+
+/// Identifies the cache entry of `start`, which also depends on the environment names searched.
+fn cache_key(start: &Path) -> String {
+    format!(
+        "{}\n{}",
+        start.display(),
+        std::env::var(crate::venv::ENV_NAMES_VAR).unwrap_or_default()
+    )
+}
+
+/// The modification times of the ancestors of `start`, which change when an environment,
+/// `.ezpy.toml` or repository marker is created or removed in them, of the `.ezpy.toml` files
+/// in them, and of the `pyvenv.cfg` of `env`.
+fn stamp(start: &Path, env: Option<&Path>) -> Vec<(PathBuf, Option<u128>)> {
+    let mut paths = Vec::new();
+    for dir in start.ancestors() {
+        paths.push(dir.to_path_buf());
+
+        let config = dir.join(crate::config::PROJECT_CONFIG_FILE);
+        if config.exists() {
+            paths.push(config);
+        }
+    }
+    paths.extend(env.map(|env| env.join("pyvenv.cfg")));
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|modified| modified.as_nanos());
+            (path, modified)
+        })
+        .collect()
+}
+
+fn set_var(shell: Shell, name: &str, value: &str) -> String {
+    let value = shell.quote(value);
+
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("export {}={}\n", name, value),
+        Shell::Fish => format!("set -gx {} {}\n", name, value),
+        Shell::Nushell => format!("$env.{} = {}\n", name, value),
+        Shell::Powershell => format!("$env:{} = {}\n", name, value),
+    }
+}
+
+fn unset_var(shell: Shell, name: &str) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => format!("unset {}\n", name),
+        Shell::Fish => format!("set -e {}\n", name),
+        Shell::Nushell => format!("hide-env -i {}\n", name),
+        Shell::Powershell => format!(
+            "Remove-Item -Path Env:{} -ErrorAction SilentlyContinue\n",
+            name
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wheel::tests::temp_dir;
+
+    /// Caches `env` for `start` as if it had been found there.
+    fn seed(cache: &Path, start: &Path, env: &Path) {
+        let key = cache_key(start);
+        let entry = CacheEntry {
+            stamp: stamp(start, Some(env)),
+            key: key.clone(),
+            env: Some(env.to_path_buf()),
+        };
+
+        std::fs::create_dir_all(cache).unwrap();
+        std::fs::write(
+            cache.join(format!("{:.16x}", Sha256::digest(key.as_bytes()))),
+            serde_json::to_vec(&entry).unwrap(),
+        )
+        .unwrap();
+    }
+
+    /// Seeds the cache and looks `start` up, retrying when other tests touching the shared
+    /// temporary directory invalidated the entry in between.
+    fn seeded_lookup(cache: &Path, start: &Path, env: &Path) -> Option<PathBuf> {
+        let mut found = None;
+        for _ in 0..10 {
+            seed(cache, start, env);
+            found = cached_nearest_env_in(cache, start).unwrap();
+            if found.as_deref() == Some(env) {
+                break;
+            }
+        }
+
+        found
+    }
+
+    #[test]
+    fn test_cached_nearest_env_hit() {
+        let dir = temp_dir("hook-hit");
+        let cache = dir.join("cache");
+        let start = dir.join("project").join("src");
+        std::fs::create_dir_all(&start).unwrap();
+
+        // The cached environment doesn't exist, so only a cache hit can return it.
+        let fake = dir.join("fake-env");
+        assert_eq!(seeded_lookup(&cache, &start, &fake), Some(fake));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cached_nearest_env_invalidated_by_config() {
+        let dir = temp_dir("hook-config");
+        let cache = dir.join("cache");
+        let project = dir.join("project");
+        let start = project.join("src");
+        std::fs::create_dir_all(&start).unwrap();
+
+        let fake = dir.join("fake-env");
+        assert_eq!(seeded_lookup(&cache, &start, &fake), Some(fake));
+
+        std::fs::write(project.join(crate::config::PROJECT_CONFIG_FILE), "").unwrap();
+        assert_eq!(cached_nearest_env_in(&cache, &start).unwrap(), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cached_nearest_env_invalidated_by_env() {
+        let dir = temp_dir("hook-env");
+        let cache = dir.join("cache");
+        let project = dir.join("project");
+        let start = project.join("src");
+        std::fs::create_dir_all(&start).unwrap();
+
+        let fake = dir.join("fake-env");
+        assert_eq!(seeded_lookup(&cache, &start, &fake), Some(fake));
+
+        let env = project.join(".venv");
+        std::fs::create_dir_all(&env).unwrap();
+        std::fs::write(env.join("pyvenv.cfg"), "home = /usr/bin\n").unwrap();
+        assert_eq!(cached_nearest_env_in(&cache, &start).unwrap(), Some(env));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_hook_env_code() {
+        let env = PathBuf::from("/project/.venv");
+        let other = PathBuf::from("/other/.venv");
+
+        let code = hook_env_code(Shell::Bash, None, None, Some(env.clone()));
+        assert!(code.contains("export VIRTUAL_ENV='/project/.venv'\n"));
+        assert!(code.ends_with("export EZPY_HOOK_ENV='/project/.venv'\n"));
+
+        let code = hook_env_code(Shell::Bash, Some(env.clone()), Some(env.clone()), None);
+        assert!(code.contains("unset EZPY_HOOK_ENV\n"));
+        assert!(!code.contains("export VIRTUAL_ENV="));
+
+        // Nothing changes when the environment is already active.
+        assert_eq!(
+            hook_env_code(
+                Shell::Bash,
+                Some(env.clone()),
+                Some(env.clone()),
+                Some(env.clone())
+            ),
+            ""
+        );
+
+        // An environment activated by hand is left alone.
+        assert_eq!(
+            hook_env_code(Shell::Bash, None, Some(other.clone()), Some(env.clone())),
+            ""
+        );
+        assert_eq!(
+            hook_env_code(Shell::Bash, Some(env.clone()), Some(other), None),
+            ""
+        );
+    }
+}
@@ -0,0 +1,790 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use reqwest::Url;
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::pep440::{SpecifierSet, Version};
+use crate::pep508::{normalize_name, Requirement};
+use crate::tags::Tags;
+use crate::wheel::WheelFilename;
+
+pub const DEFAULT_INDEX_URL: &str = "https://pypi.org/simple/";
+
+const ACCEPT: &str = "application/vnd.pypi.simple.v1+json, application/vnd.pypi.simple.v1+html;q=0.2, text/html;q=0.01";
+
+/// A file listed on a project page of a simple repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistFile {
+    pub filename: String,
+    pub url: Url,
+
+    /// Digests keyed by algorithm, e.g. `sha256`.
+    pub hashes: HashMap<String, String>,
+    pub requires_python: Option<String>,
+    pub yanked: bool,
+
+    /// Set when the index serves the core metadata of the file next to it, at the file URL
+    /// with `.metadata` appended (PEP 658), with the digests of that metadata file.
+    pub core_metadata: Option<HashMap<String, String>>,
+}
+
+/// A client for the Simple Repository API (PEP 503 HTML and PEP 691 JSON), over HTTP or
+/// `file://` URLs.
+#[derive(Debug, Clone)]
+pub struct Index {
+    urls: Vec<Url>,
+    client: reqwest::Client,
+}
+
+impl Index {
+    /// Creates a client querying `index_url` (PyPI by default) followed by `extra_index_urls`.
+    pub fn new(index_url: Option<&str>, extra_index_urls: &[String]) -> eyre::Result<Self> {
+        let urls = std::iter::once(index_url.unwrap_or(DEFAULT_INDEX_URL))
+            .chain(extra_index_urls.iter().map(String::as_str))
+            .map(parse_index_url)
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(Index {
+            urls,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    /// Lists the files of `project` across every index.
+    pub async fn files(&self, project: &str) -> eyre::Result<Vec<DistFile>> {
+        let mut files = Vec::new();
+
+        for url in &self.urls {
+            let page = url.join(&format!("{}/", normalize_name(project)))?;
+            files.extend(self.fetch_page(&page).await?);
+        }
+
+        Ok(files)
+    }
+
+    async fn fetch_page(&self, page: &Url) -> eyre::Result<Vec<DistFile>> {
+        if crate::is_verbose() {
+            eprintln!("ezpy: fetching {}", page);
+        }
+
+        if page.scheme() == "file" {
+            return read_local_page(page).await;
+        }
+
+        let response = self
+            .client
+            .get(page.clone())
+            .header(reqwest::header::ACCEPT, ACCEPT)
+            .send()
+            .await
+            .map_err(|e| eyre::eyre!("Failed to fetch {}: {}", page, e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+
+        if !response.status().is_success() {
+            eyre::bail!("Failed to fetch {}: {}", page, response.status());
+        }
+
+        let page = response.url().clone();
+        let json = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("json"));
+        let body = response.text().await?;
+
+        if json {
+            parse_json(&page, &body)
+        } else {
+            Ok(parse_html(&page, &body))
+        }
+    }
+
+    /// Finds the best wheel satisfying `requirement`: the newest version, then the most
+    /// specific tags. Yanked files and files rejecting `python` are ignored.
+    pub async fn find_wheel(
+        &self,
+        requirement: &Requirement,
+        tags: &Tags,
+        python: &Version,
+    ) -> eyre::Result<Option<(WheelFilename, DistFile)>> {
+        let files = self.files(&requirement.name).await?;
+
+        Ok(best_wheel(files, requirement, tags, python))
+    }
+}
+
+/// Downloads `file` into `dir`, reusing a previous download with a matching digest.
+pub async fn download(file: &DistFile, dir: &Path) -> eyre::Result<PathBuf> {
+    tokio::fs::create_dir_all(dir).await?;
+    let destination = dir.join(&file.filename);
+
+    if destination.exists() && verify(file, &tokio::fs::read(&destination).await?).is_ok() {
+        return Ok(destination);
+    }
+
+    let contents = if file.url.scheme() == "file" {
+        let path = file
+            .url
+            .to_file_path()
+            .map_err(|_| eyre::eyre!("Invalid file URL {}", file.url))?;
+        tokio::fs::read(&path)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?
+    } else {
+        let response = reqwest::get(file.url.clone()).await?;
+        if !response.status().is_success() {
+            eyre::bail!("Failed to download {}: {}", file.url, response.status());
+        }
+        response.bytes().await?.to_vec()
+    };
+
+    verify(file, &contents)?;
+
+    // Write under a temporary name so an interrupted download is never reused.
+    let partial = dir.join(format!("{}.part", file.filename));
+    tokio::fs::write(&partial, &contents).await?;
+    tokio::fs::rename(&partial, &destination).await?;
+
+    Ok(destination)
+}
+
+/// Fetches the core metadata the index serves for `file` (PEP 658), or `None` when it doesn't
+/// advertise any.
+pub async fn core_metadata(file: &DistFile) -> eyre::Result<Option<String>> {
+    let Some(hashes) = &file.core_metadata else {
+        return Ok(None);
+    };
+
+    let mut url = file.url.clone();
+    url.set_path(&format!("{}.metadata", file.url.path()));
+
+    let contents = if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|_| eyre::eyre!("Invalid file URL {}", url))?;
+        tokio::fs::read(&path)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?
+    } else {
+        let response = reqwest::get(url.clone()).await?;
+        if !response.status().is_success() {
+            eyre::bail!("Failed to download {}: {}", url, response.status());
+        }
+        response.bytes().await?.to_vec()
+    };
+
+    let metadata = DistFile {
+        filename: format!("{}.metadata", file.filename),
+        url,
+        hashes: hashes.clone(),
+        requires_python: None,
+        yanked: false,
+        core_metadata: None,
+    };
+    verify(&metadata, &contents)?;
+
+    Ok(Some(String::from_utf8_lossy(&contents).to_string()))
+}
+
+/// Picks the best wheel among `files`: the newest version satisfying `requirement`, then the
+/// most specific tags. Yanked files and files rejecting `python` are ignored. Pre-releases are
+/// only picked when the requirement asks for one or when nothing else satisfies it.
+pub fn best_wheel(
+    files: impl IntoIterator<Item = DistFile>,
+    requirement: &Requirement,
+    tags: &Tags,
+    python: &Version,
+) -> Option<(WheelFilename, DistFile)> {
+    let name = requirement.normalized_name();
+
+    let candidates = files
+        .into_iter()
+        .filter_map(|file| {
+            let wheel = file.filename.parse::<WheelFilename>().ok()?;
+            let priority = tags.priority(&wheel)?;
+
+            let matches = normalize_name(&wheel.name) == name
+                && requirement.specifiers.contains(&wheel.version)
+                && file.is_installable(python);
+
+            matches.then_some((wheel, priority, file))
+        })
+        .collect::<Vec<_>>();
+
+    let prereleases = requirement.specifiers.allows_prereleases()
+        || candidates
+            .iter()
+            .all(|(wheel, _, _)| wheel.version.is_prerelease());
+
+    candidates
+        .into_iter()
+        .filter(|(wheel, _, _)| prereleases || !wheel.version.is_prerelease())
+        .max_by(|(a, a_priority, _), (b, b_priority, _)| {
+            a.version
+                .cmp(&b.version)
+                .then_with(|| b_priority.cmp(a_priority))
+        })
+        .map(|(wheel, _, file)| (wheel, file))
+}
+
+impl DistFile {
+    /// Whether the file may be installed for `python`: it isn't yanked and its
+    /// `requires-python` allows the interpreter.
+    pub fn is_installable(&self, python: &Version) -> bool {
+        !self.yanked
+            && self.requires_python.as_ref().is_none_or(|requires| {
+                requires
+                    .parse::<SpecifierSet>()
+                    .map_or(true, |requires| requires.contains(python))
+            })
+    }
+
+    /// Describes a local file, such as a wheel found in a `--find-links` directory.
+    pub fn local(path: &Path) -> eyre::Result<Self> {
+        Ok(DistFile {
+            filename: path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string(),
+            url: Url::from_file_path(std::path::absolute(path)?)
+                .map_err(|_| eyre::eyre!("Invalid path {}", path.display()))?,
+            hashes: HashMap::new(),
+            requires_python: None,
+            yanked: false,
+            core_metadata: None,
+        })
+    }
+}
+
+fn parse_index_url(url: &str) -> eyre::Result<Url> {
+    // Bare paths are accepted as local indexes.
+    let mut url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => {
+            let path = std::path::absolute(url)?;
+            Url::from_directory_path(&path)
+                .map_err(|_| eyre::eyre!("Invalid index URL '{}'", url))?
+        }
+    };
+
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+
+    Ok(url)
+}
+
+/// Reads a project page from a local index: its `index.html` or `index.json` when present,
+/// otherwise the files of the directory itself.
+async fn read_local_page(page: &Url) -> eyre::Result<Vec<DistFile>> {
+    let dir = page
+        .to_file_path()
+        .map_err(|_| eyre::eyre!("Invalid file URL {}", page))?;
+
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    if let Ok(body) = tokio::fs::read_to_string(dir.join("index.json")).await {
+        return parse_json(page, &body);
+    }
+
+    if let Ok(body) = tokio::fs::read_to_string(dir.join("index.html")).await {
+        return Ok(parse_html(page, &body));
+    }
+
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let filename = entry.file_name().to_string_lossy().to_string();
+        if !entry.file_type().await?.is_file() || filename.starts_with("index.") {
+            continue;
+        }
+
+        files.push(DistFile::local(&entry.path())?);
+    }
+
+    Ok(files)
+}
+
+#[derive(Deserialize)]
+struct JsonPage {
+    files: Vec<JsonFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonFile {
+    filename: String,
+    url: String,
+
+    #[serde(default)]
+    hashes: HashMap<String, String>,
+    requires_python: Option<String>,
+
+    /// Either a boolean or the reason the file was yanked.
+    #[serde(default)]
+    yanked: serde_json::Value,
+
+    /// Either a boolean or the digests of the metadata file, `dist-info-metadata` before
+    /// PEP 714.
+    #[serde(default, alias = "dist-info-metadata")]
+    core_metadata: serde_json::Value,
+}
+
+/// Parses a PEP 691 JSON project page.
+fn parse_json(page: &Url, body: &str) -> eyre::Result<Vec<DistFile>> {
+    let json: JsonPage = serde_json::from_str(body)
+        .map_err(|e| eyre::eyre!("Invalid project page {}: {}", page, e))?;
+
+    json.files
+        .into_iter()
+        .map(|file| {
+            Ok(DistFile {
+                url: page.join(&file.url)?,
+                filename: file.filename,
+                hashes: file.hashes,
+                requires_python: file.requires_python,
+                yanked: match file.yanked {
+                    serde_json::Value::Bool(yanked) => yanked,
+                    serde_json::Value::String(_) => true,
+                    _ => false,
+                },
+                core_metadata: match file.core_metadata {
+                    serde_json::Value::Bool(true) => Some(HashMap::new()),
+                    serde_json::Value::Object(hashes) => Some(
+                        hashes
+                            .into_iter()
+                            .filter_map(|(algorithm, digest)| {
+                                Some((algorithm, digest.as_str()?.to_string()))
+                            })
+                            .collect(),
+                    ),
+                    _ => None,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Parses the anchors of a PEP 503 HTML project page.
+fn parse_html(page: &Url, body: &str) -> Vec<DistFile> {
+    let mut files = Vec::new();
+    let mut rest = body;
+
+    while let Some(start) = find_ignore_case(rest, "<a") {
+        rest = &rest[start + 2..];
+        if !rest.starts_with(|c: char| c.is_whitespace()) {
+            continue;
+        }
+
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+        let attributes = parse_attributes(&rest[..end]);
+        rest = &rest[end + 1..];
+
+        let text = match find_ignore_case(rest, "</a>") {
+            Some(close) => unescape(rest[..close].trim()),
+            None => String::new(),
+        };
+
+        let Some(href) = attributes.get("href") else {
+            continue;
+        };
+        let Ok(mut url) = page.join(href) else {
+            continue;
+        };
+
+        let mut hashes = HashMap::new();
+        if let Some((algorithm, digest)) =
+            url.fragment().and_then(|fragment| fragment.split_once('='))
+        {
+            hashes.insert(algorithm.to_string(), digest.to_string());
+        }
+        url.set_fragment(None);
+
+        let filename = if text.is_empty() {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .unwrap_or_default()
+                .to_string()
+        } else {
+            text
+        };
+
+        files.push(DistFile {
+            filename,
+            url,
+            hashes,
+            requires_python: attributes.get("data-requires-python").cloned(),
+            yanked: attributes.contains_key("data-yanked"),
+            core_metadata: attributes
+                .get("data-core-metadata")
+                .or_else(|| attributes.get("data-dist-info-metadata"))
+                .filter(|value| value.as_str() != "false")
+                .map(|value| {
+                    value
+                        .split_once('=')
+                        .map(|(algorithm, digest)| (algorithm.to_string(), digest.to_string()))
+                        .into_iter()
+                        .collect()
+                }),
+        });
+    }
+
+    files
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    let mut rest = tag.trim().trim_end_matches('/');
+
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let name = rest[..end].to_ascii_lowercase();
+        rest = rest[end..].trim_start();
+
+        let value = if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (value, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let inner = &after[1..];
+                    let close = inner.find(quote).unwrap_or(inner.len());
+                    (&inner[..close], inner.get(close + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let close = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..close], &after[close..])
+                }
+            };
+            rest = remaining.trim_start();
+            unescape(value)
+        } else {
+            String::new()
+        };
+
+        if !name.is_empty() {
+            attributes.insert(name, value);
+        }
+    }
+
+    attributes
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Checks `contents` against the strongest SHA-2 digest advertised by the index, if any.
+/// Files only listed with other digests, such as md5, can't be verified and are accepted with
+/// a warning.
+pub fn verify(file: &DistFile, contents: &[u8]) -> eyre::Result<()> {
+    let Some((algorithm, expected)) = ["sha512", "sha384", "sha256"]
+        .into_iter()
+        .find_map(|algorithm| Some((algorithm, file.hashes.get(algorithm)?)))
+    else {
+        let mut algorithms = file.hashes.keys().map(String::as_str).collect::<Vec<_>>();
+        if !algorithms.is_empty() {
+            algorithms.sort_unstable();
+            println!(
+                "Warning: can't verify {}, the index only lists unsupported {} digests",
+                file.filename,
+                algorithms.join(", ")
+            );
+        }
+        return Ok(());
+    };
+
+    let actual = match algorithm {
+        "sha512" => format!("{:x}", Sha512::digest(contents)),
+        "sha384" => format!("{:x}", Sha384::digest(contents)),
+        _ => format!("{:x}", Sha256::digest(contents)),
+    };
+    if !actual.eq_ignore_ascii_case(expected) {
+        eyre::bail!(
+            "Hash mismatch for {}: expected {} {}, got {}",
+            file.filename,
+            algorithm,
+            expected,
+            actual
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wheel::tests::{temp_dir, write_wheel};
+
+    fn page() -> Url {
+        Url::parse("https://example.org/simple/demo/").unwrap()
+    }
+
+    fn file(filename: &str) -> DistFile {
+        DistFile {
+            filename: filename.to_string(),
+            url: page().join(filename).unwrap(),
+            hashes: HashMap::new(),
+            requires_python: None,
+            yanked: false,
+            core_metadata: None,
+        }
+    }
+
+    fn best(files: Vec<DistFile>, requirement: &str, tags: &Tags) -> Option<String> {
+        let python: Version = "3.12.1".parse().unwrap();
+        best_wheel(files, &requirement.parse().unwrap(), tags, &python)
+            .map(|(_, file)| file.filename)
+    }
+
+    #[test]
+    fn parses_html_pages() {
+        let body = r#"<!DOCTYPE html>
+<html><body>
+  <A HREF="../../files/demo-1.0-py3-none-any.whl#sha256=abc123">demo-1.0-py3-none-any.whl</A><br/>
+  <a href="https://files.example.org/demo-2.0.tar.gz" data-requires-python="&gt;=3.8" data-yanked="" data-core-metadata="true">demo-2.0.tar.gz</a>
+  <a data-requires-python='&gt;=3.9,&lt;4' href=demo-3.0-py3-none-any.whl></a>
+  <abbr>not a link</abbr>
+</body></html>"#;
+
+        let files = parse_html(&page(), body);
+
+        assert_eq!(files.len(), 3);
+        assert_eq!(files[0].filename, "demo-1.0-py3-none-any.whl");
+        assert_eq!(
+            files[0].url.as_str(),
+            "https://example.org/files/demo-1.0-py3-none-any.whl"
+        );
+        assert_eq!(
+            files[0].hashes,
+            HashMap::from([("sha256".to_string(), "abc123".to_string())])
+        );
+        assert!(!files[0].yanked);
+        assert_eq!(files[0].core_metadata, None);
+
+        assert_eq!(files[1].requires_python.as_deref(), Some(">=3.8"));
+        assert!(files[1].yanked);
+        assert_eq!(files[1].core_metadata, Some(HashMap::new()));
+
+        assert_eq!(files[2].filename, "demo-3.0-py3-none-any.whl");
+        assert_eq!(files[2].requires_python.as_deref(), Some(">=3.9,<4"));
+    }
+
+    #[test]
+    fn parses_json_pages() {
+        let body = r#"{
+            "meta": {"api-version": "1.1"},
+            "name": "demo",
+            "files": [
+                {"filename": "demo-1.0-py3-none-any.whl", "url": "/files/demo-1.0-py3-none-any.whl",
+                 "hashes": {"sha256": "abc123"}, "requires-python": ">=3.8",
+                 "core-metadata": {"sha256": "def456"}},
+                {"filename": "demo-1.1-py3-none-any.whl", "url": "demo-1.1-py3-none-any.whl",
+                 "hashes": {}, "yanked": "broken", "dist-info-metadata": true},
+                {"filename": "demo-1.2-py3-none-any.whl", "url": "demo-1.2-py3-none-any.whl",
+                 "hashes": {}, "yanked": false}
+            ]
+        }"#;
+
+        let files = parse_json(&page(), body).unwrap();
+
+        assert_eq!(
+            files[0].url.as_str(),
+            "https://example.org/files/demo-1.0-py3-none-any.whl"
+        );
+        assert_eq!(files[0].hashes["sha256"], "abc123");
+        assert_eq!(files[0].requires_python.as_deref(), Some(">=3.8"));
+        assert_eq!(
+            files.iter().map(|file| file.yanked).collect::<Vec<_>>(),
+            vec![false, true, false]
+        );
+        assert_eq!(
+            files[0].core_metadata,
+            Some(HashMap::from([(
+                "sha256".to_string(),
+                "def456".to_string()
+            )]))
+        );
+        assert_eq!(files[1].core_metadata, Some(HashMap::new()));
+        assert_eq!(files[2].core_metadata, None);
+
+        assert!(parse_json(&page(), "<html>").is_err());
+    }
+
+    #[test]
+    fn picks_newest_then_most_specific_wheel() {
+        let tags = Tags(vec![
+            (
+                "cp312".to_string(),
+                "cp312".to_string(),
+                "linux_x86_64".to_string(),
+            ),
+            ("py3".to_string(), "none".to_string(), "any".to_string()),
+        ]);
+
+        let files = vec![
+            file("demo-1.0-py3-none-any.whl"),
+            file("demo-1.0-cp312-cp312-linux_x86_64.whl"),
+            file("demo-0.9-cp312-cp312-linux_x86_64.whl"),
+            file("demo-2.0-cp311-cp311-linux_x86_64.whl"),
+            file("demo-3.0.tar.gz"),
+            file("other-5.0-py3-none-any.whl"),
+        ];
+        assert_eq!(
+            best(files.clone(), "demo", &tags).as_deref(),
+            Some("demo-1.0-cp312-cp312-linux_x86_64.whl")
+        );
+        assert_eq!(
+            best(files, "demo<1", &tags).as_deref(),
+            Some("demo-0.9-cp312-cp312-linux_x86_64.whl")
+        );
+
+        let mut yanked = file("demo-2.0-py3-none-any.whl");
+        yanked.yanked = true;
+        let mut newer_python = file("demo-1.5-py3-none-any.whl");
+        newer_python.requires_python = Some(">=3.13".to_string());
+        let files = vec![
+            yanked,
+            newer_python,
+            file("demo-1.4rc1-py3-none-any.whl"),
+            file("demo-1.0-py3-none-any.whl"),
+        ];
+        assert_eq!(
+            best(files.clone(), "demo", &tags).as_deref(),
+            Some("demo-1.0-py3-none-any.whl")
+        );
+        assert_eq!(
+            best(files.clone(), "demo>=1.4rc1", &tags).as_deref(),
+            Some("demo-1.4rc1-py3-none-any.whl")
+        );
+
+        // Pre-releases are picked when nothing else satisfies the requirement.
+        assert_eq!(
+            best(files, "demo>1.0", &tags).as_deref(),
+            Some("demo-1.4rc1-py3-none-any.whl")
+        );
+        let files = vec![
+            file("demo-2.0b1-py3-none-any.whl"),
+            file("demo-2.0a1-py3-none-any.whl"),
+        ];
+        assert_eq!(
+            best(files, "demo", &tags).as_deref(),
+            Some("demo-2.0b1-py3-none-any.whl")
+        );
+    }
+
+    #[test]
+    fn verifies_strongest_digest() {
+        let mut file = file("demo-1.0-py3-none-any.whl");
+        let contents = b"wheel";
+
+        assert!(verify(&file, contents).is_ok());
+
+        file.hashes.insert(
+            "sha256".to_string(),
+            format!("{:x}", Sha256::digest(contents)),
+        );
+        assert!(verify(&file, contents).is_ok());
+        assert!(verify(&file, b"tampered").is_err());
+
+        file.hashes.insert("sha512".to_string(), "00".to_string());
+        let error = verify(&file, contents).unwrap_err().to_string();
+        assert!(
+            error.starts_with("Hash mismatch for demo-1.0-py3-none-any.whl: expected sha512 00")
+        );
+
+        file.hashes.insert(
+            "sha512".to_string(),
+            format!("{:X}", Sha512::digest(contents)),
+        );
+        assert!(verify(&file, contents).is_ok());
+    }
+
+    #[tokio::test]
+    async fn reads_local_index() {
+        let dir = temp_dir("local-index");
+
+        let demo = dir.join("demo");
+        std::fs::create_dir_all(&demo).unwrap();
+        write_wheel(&demo, "demo-1.0-py3-none-any.whl", &[], &[]);
+        write_wheel(&demo, "demo-1.1-py3-none-any.whl", &[], &[]);
+
+        let listed = dir.join("listed");
+        std::fs::create_dir_all(&listed).unwrap();
+        std::fs::write(
+            listed.join("index.html"),
+            "<a href=\"../demo/demo-1.1-py3-none-any.whl\" data-yanked>listed-1.1-py3-none-any.whl</a>",
+        )
+        .unwrap();
+
+        let index = Index::new(Some(&dir.to_string_lossy()), &[]).unwrap();
+
+        let mut files = index
+            .files("Demo")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| file.filename)
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(
+            files,
+            vec!["demo-1.0-py3-none-any.whl", "demo-1.1-py3-none-any.whl"]
+        );
+
+        let listed = index.files("listed").await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].yanked);
+        assert_eq!(
+            listed[0].url.to_file_path().unwrap(),
+            demo.join("demo-1.1-py3-none-any.whl")
+        );
+
+        assert!(index.files("missing").await.unwrap().is_empty());
+
+        let tags = Tags(vec![(
+            "py3".to_string(),
+            "none".to_string(),
+            "any".to_string(),
+        )]);
+        let (wheel, file) = index
+            .find_wheel(&"demo".parse().unwrap(), &tags, &"3.12".parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(wheel.version.to_string(), "1.1");
+
+        let downloads = dir.join("downloads");
+        let path = download(&file, &downloads).await.unwrap();
+        assert_eq!(path, downloads.join("demo-1.1-py3-none-any.whl"));
+        assert_eq!(
+            std::fs::read(&path).unwrap(),
+            std::fs::read(demo.join("demo-1.1-py3-none-any.whl")).unwrap()
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
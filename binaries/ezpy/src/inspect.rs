@@ -0,0 +1,168 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::pyvenv::PyvenvConfig;
+
+/// Metadata about a virtual environment, as shown by `ezpy env list`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvInfo {
+    pub name: String,
+
+    /// Either `global` or `local`.
+    pub kind: String,
+    pub path: PathBuf,
+
+    pub python_version: Option<String>,
+    pub base_interpreter: Option<PathBuf>,
+    pub base_exists: bool,
+
+    /// Total size on disk, in bytes.
+    pub size: u64,
+    pub packages: usize,
+
+    /// Creation time, in seconds since the Unix epoch.
+    pub created: Option<u64>,
+}
+
+impl EnvInfo {
+    pub async fn collect(name: String, kind: &str, path: PathBuf) -> Self {
+        let config = PyvenvConfig::read(&path).await.ok();
+
+        let packages = match &config {
+            Some(config) => installed_distributions(&config.site_packages(&path))
+                .await
+                .map(|distributions| distributions.len())
+                .unwrap_or(0),
+            None => 0,
+        };
+
+        let created = tokio::fs::metadata(path.join("pyvenv.cfg"))
+            .await
+            .ok()
+            .and_then(|metadata| metadata.created().or_else(|_| metadata.modified()).ok())
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs());
+
+        let walked = path.clone();
+        let size = tokio::task::spawn_blocking(move || dir_size(&walked))
+            .await
+            .unwrap_or(0);
+
+        EnvInfo {
+            name,
+            kind: kind.to_string(),
+            python_version: config.as_ref().map(|config| config.version.clone()),
+            base_interpreter: config.as_ref().map(|config| config.executable.clone()),
+            base_exists: config
+                .as_ref()
+                .is_some_and(|config| config.executable.exists()),
+            path,
+            size,
+            packages,
+            created,
+        }
+    }
+}
+
+/// Lists the `(name, version)` of every distribution installed in `site_packages`.
+pub async fn installed_distributions(site_packages: &Path) -> eyre::Result<Vec<(String, String)>> {
+    let mut distributions = Vec::new();
+
+    let mut entries = tokio::fs::read_dir(site_packages).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(stem) = name.strip_suffix(".dist-info") else {
+            continue;
+        };
+
+        if let Some((name, version)) = stem.split_once('-') {
+            distributions.push((name.to_string(), version.to_string()));
+        }
+    }
+
+    distributions.sort();
+
+    Ok(distributions)
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(kind) if kind.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+pub fn format_size(size: u64) -> String {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, units[0])
+    } else {
+        format!("{:.1} {}", value, units[unit])
+    }
+}
+
+pub fn format_age(created: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(created);
+
+    let seconds = now.saturating_sub(created);
+    let (value, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        86400..=2591999 => (seconds / 86400, "day"),
+        2592000..=31535999 => (seconds / 2592000, "month"),
+        _ => (seconds / 31536000, "year"),
+    };
+
+    format!("{} {}{} ago", value, unit, if value > 1 { "s" } else { "" })
+}
+
+/// Prints rows as a left-aligned table with a header.
+pub fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    let mut widths = header.iter().map(|title| title.len()).collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let format_row = |cells: Vec<String>| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    println!(
+        "{}",
+        format_row(header.iter().map(|title| title.to_string()).collect())
+    );
+    for row in rows {
+        println!("{}", format_row(row.clone()));
+    }
+}
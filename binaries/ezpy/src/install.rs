@@ -1,57 +1,922 @@
-use crate::venv::get_nearest_env;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+
+use crate::index::{best_wheel, DistFile, Index};
+use crate::pep440::{SpecifierSet, Version};
+use crate::pep508::{InterpreterInfo, MarkerEnvironment, Requirement};
+use crate::tags::Tags;
+use crate::venv::resolve_env;
+use crate::wheel::{Target, Wheel, WheelFilename};
 
 pub async fn install_packages(packages: Vec<String>, global: Option<String>) -> eyre::Result<()> {
-    let venv = match global {
-        Some(global) => crate::venv::get_global_env(global)?,
-        None => get_nearest_env().await?,
-    };
+    let venv = resolve_env(global).await?;
+
+    let mut args = vec!["install".to_string()];
+    args.extend(packages);
 
-    let bin = venv.join(crate::env_bin_path());
+    crate::pip::run(&venv, &args, "Failed to install packages").await
+}
+
+pub async fn install_from_requirements(
+    requirements_file: &str,
+    global: Option<String>,
+) -> eyre::Result<()> {
+    crate::requirements::RequirementsFile::read(Path::new(requirements_file))?.check()?;
 
-    let mut cmd = tokio::process::Command::new(bin);
-    cmd.arg("-m")
-        .arg("pip")
-        .arg("install")
-        .args(packages.clone());
+    let venv = resolve_env(global).await?;
 
-    let out = cmd.spawn()?.wait_with_output().await?;
+    let args = ["install", "-r", requirements_file].map(String::from);
 
-    if !out.status.success() {
-        eyre::bail!(
-            "Failed to install packages: {}",
-            String::from_utf8_lossy(&out.stderr)
+    crate::pip::run(
+        &venv,
+        &args,
+        "Failed to install packages from requirements file",
+    )
+    .await
+}
+
+/// Installs `packages` into the nearest environment, creating `.venv` next to the nearest
+/// `pyproject.toml` when there's none, then records them as dependencies of the project.
+pub async fn add_packages(
+    packages: Vec<String>,
+    group: Option<String>,
+    python: Option<VersionString>,
+) -> eyre::Result<()> {
+    let path = project_file()?;
+
+    for package in &packages {
+        package
+            .parse::<Requirement>()
+            .map_err(|e| eyre::eyre!("Invalid requirement '{}': {}", package, e))?;
+    }
+
+    // Edit the project before installing anything, so an unusable pyproject.toml fails early.
+    let mut contents = tokio::fs::read_to_string(&path).await?;
+    for package in &packages {
+        contents = crate::pyproject::add_dependency(&contents, &path, package, group.as_deref())?;
+    }
+
+    // Only a missing environment is created, errors finding one are reported as they are.
+    if crate::venv::locate_env(&std::env::current_dir()?)?.is_none() {
+        let project = path.parent().unwrap();
+        let resolved = crate::pin::resolve(python, false).await?;
+        crate::pin::check_requires_python(&resolved).await?;
+
+        let prompt = project
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".venv".to_string());
+        crate::venv::create_env(
+            &resolved.version,
+            &project.join(".venv"),
+            &prompt,
+            Default::default(),
+        )
+        .await?;
+    }
+
+    install_packages(packages.clone(), None).await?;
+
+    tokio::fs::write(&path, contents).await?;
+
+    println!(
+        "Added {} to {}",
+        packages.join(", "),
+        dependencies_table(group.as_deref())
+    );
+
+    Ok(())
+}
+
+/// Uninstalls `packages` from the nearest environment and drops them from the dependencies
+/// of the project.
+pub async fn remove_packages(packages: Vec<String>, group: Option<String>) -> eyre::Result<()> {
+    let path = project_file()?;
+
+    let mut contents = tokio::fs::read_to_string(&path).await?;
+    for package in &packages {
+        let (edited, removed) =
+            crate::pyproject::remove_dependency(&contents, &path, package, group.as_deref())?;
+        contents = edited;
+
+        if !removed {
+            println!(
+                "Warning: {} isn't listed in {} of {}",
+                package,
+                dependencies_table(group.as_deref()),
+                path.display()
+            );
+        }
+    }
+
+    tokio::fs::write(&path, contents).await?;
+
+    // Packages the project still depends on through another group stay installed.
+    let mut declared = HashSet::new();
+    if let Some(project) = crate::pyproject::PyProject::read(&path).await?.project {
+        for requirement in project
+            .dependencies
+            .iter()
+            .chain(project.optional_dependencies.values().flatten())
+        {
+            if let Ok(requirement) = requirement.parse::<Requirement>() {
+                declared.insert(requirement.normalized_name());
+            }
+        }
+    }
+
+    let (kept, removed): (Vec<_>, Vec<_>) = packages
+        .into_iter()
+        .partition(|package| declared.contains(&crate::pep508::normalize_name(package)));
+
+    for package in kept {
+        println!(
+            "Keeping {} installed, {} still depends on it",
+            package,
+            path.display()
         );
     }
 
+    if removed.is_empty() {
+        return Ok(());
+    }
+
+    uninstall_packages(removed, None).await
+}
+
+fn project_file() -> eyre::Result<PathBuf> {
+    crate::pyproject::find_pyproject(&std::env::current_dir()?)
+        .ok_or_else(|| eyre::eyre!("No pyproject.toml found"))
+}
+
+fn dependencies_table(group: Option<&str>) -> String {
+    match group {
+        Some(group) => format!("[project.optional-dependencies].{}", group),
+        None => "[project].dependencies".to_string(),
+    }
+}
+
+/// Whether pip is installed in `venv`.
+fn has_pip(target: &Target) -> eyre::Result<bool> {
+    Ok(target.installed()?.contains_key("pip"))
+}
+
+/// Removes `packages` from the environment, with pip when it's available.
+pub async fn uninstall_packages(packages: Vec<String>, global: Option<String>) -> eyre::Result<()> {
+    let venv = resolve_env(global).await?;
+    let target = Target::new(&venv).await?;
+
+    if has_pip(&target)? {
+        let mut args = vec!["uninstall".to_string(), "--yes".to_string()];
+        args.extend(packages);
+
+        return crate::pip::run(&venv, &args, "Failed to uninstall packages").await;
+    }
+
+    for package in packages {
+        match crate::wheel::uninstall(&target, &package)? {
+            Some(version) => println!("Removed {}-{}", package, version),
+            None => println!("{} is not installed", package),
+        }
+    }
+
     Ok(())
 }
 
-pub async fn install_from_requirements(
-    requirements_file: &str,
+/// Upgrades `packages`, or every installed package when `all` is set. With `outdated_only`,
+/// only the packages pip reports as outdated are upgraded.
+pub async fn upgrade_packages(
+    packages: Vec<String>,
+    all: bool,
+    outdated_only: bool,
     global: Option<String>,
 ) -> eyre::Result<()> {
-    let venv = match global {
-        Some(global) => crate::venv::get_global_env(global)?,
-        None => get_nearest_env().await?,
+    let venv = resolve_env(global).await?;
+    let target = Target::new(&venv).await?;
+
+    if !has_pip(&target)? {
+        eyre::bail!(
+            "Upgrading requires pip, which isn't installed in {}",
+            venv.display()
+        );
+    }
+
+    let mut selected = if all || (outdated_only && packages.is_empty()) {
+        let mut installed = target.installed()?.into_keys().collect::<Vec<_>>();
+        installed.sort();
+        installed
+    } else if packages.is_empty() {
+        eyre::bail!("Name the packages to upgrade, or pass --all or --outdated-only");
+    } else {
+        packages
     };
 
-    let bin = venv.join(crate::env_bin_path());
+    if outdated_only {
+        let outdated = outdated_packages(&venv).await?;
+        selected.retain(|name| outdated.contains(&crate::pep508::normalize_name(name)));
+    }
+
+    if selected.is_empty() {
+        println!("Everything is up to date");
+        return Ok(());
+    }
+
+    let mut args = vec!["install".to_string(), "--upgrade".to_string()];
+    args.extend(selected);
 
-    let mut cmd = tokio::process::Command::new(bin);
-    cmd.arg("-m")
-        .arg("pip")
-        .arg("install")
-        .arg("-r")
-        .arg(requirements_file);
+    crate::pip::run(&venv, &args, "Failed to upgrade packages").await
+}
+
+/// The normalized names of the packages pip finds a newer version of.
+async fn outdated_packages(venv: &Path) -> eyre::Result<HashSet<String>> {
+    #[derive(serde::Deserialize)]
+    struct Outdated {
+        name: String,
+    }
 
-    let out = cmd.spawn()?.wait_with_output().await?;
+    let out = tokio::process::Command::new(venv.join(crate::env_bin_path()))
+        .args(["-m", "pip", "list", "--outdated", "--format=json"])
+        .output()
+        .await?;
 
     if !out.status.success() {
         eyre::bail!(
-            "Failed to install packages from requirements file: {}",
+            "Failed to list outdated packages: {}",
             String::from_utf8_lossy(&out.stderr)
         );
     }
 
+    let outdated: Vec<Outdated> = serde_json::from_slice(&out.stdout)?;
+
+    Ok(outdated
+        .into_iter()
+        .map(|package| crate::pep508::normalize_name(&package.name))
+        .collect())
+}
+
+/// The `(name, version)` of the packages installed in the environment, leaving out the
+/// ones seeded into every environment unless `seeded` is set.
+async fn installed_packages(
+    global: Option<String>,
+    seeded: bool,
+) -> eyre::Result<Vec<(String, String)>> {
+    let venv = resolve_env(global).await?;
+    let target = Target::new(&venv).await?;
+
+    let packages = crate::inspect::installed_distributions(&target.site_packages)
+        .await
+        .map_err(|e| {
+            eyre::eyre!(
+                "Failed to read the packages installed in {}: {}",
+                target.site_packages.display(),
+                e
+            )
+        })?
+        .into_iter()
+        .filter(|(name, _)| {
+            seeded
+                || !crate::venv::SEEDED_PACKAGES
+                    .contains(&crate::pep508::normalize_name(name).as_str())
+        })
+        .collect();
+
+    Ok(packages)
+}
+
+/// Prints the installed packages as requirements: `name==version`, or `-e <path>` and
+/// `name @ url` for the packages installed from a local project or a URL.
+pub async fn freeze(global: Option<String>, all: bool) -> eyre::Result<()> {
+    let site_packages = Target::new(&resolve_env(global.clone()).await?)
+        .await?
+        .site_packages;
+
+    for (name, version) in installed_packages(global, all).await? {
+        println!(
+            "{}",
+            frozen_requirement(&site_packages, &name, &version).await
+        );
+    }
+
     Ok(())
 }
+
+/// The requirement reinstalling the distribution `name` installed in `site_packages`, from the
+/// `direct_url.json` it was installed with or `name==version` otherwise.
+pub async fn frozen_requirement(site_packages: &Path, name: &str, version: &str) -> String {
+    let direct_url = site_packages
+        .join(format!("{}-{}.dist-info", name, version))
+        .join("direct_url.json");
+
+    let frozen = match tokio::fs::read(&direct_url).await {
+        Ok(contents) => match serde_json::from_slice(&contents) {
+            Ok(value) => frozen_direct_url(name, &value),
+            Err(e) => {
+                if crate::is_verbose() {
+                    eprintln!("ezpy: ignoring invalid {}: {}", direct_url.display(), e);
+                }
+                None
+            }
+        },
+        Err(_) => None,
+    };
+
+    frozen.unwrap_or_else(|| format!("{}=={}", name, version))
+}
+
+/// The requirement reinstalling a distribution from the `direct_url.json` it was installed
+/// with, as described by PEP 610.
+fn frozen_direct_url(name: &str, direct_url: &serde_json::Value) -> Option<String> {
+    let url = direct_url["url"].as_str()?;
+
+    if direct_url["dir_info"]["editable"].as_bool() == Some(true) {
+        let path = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|| url.to_string());
+        return Some(format!("-e {}", path));
+    }
+
+    let vcs = &direct_url["vcs_info"];
+    if let Some(vcs_name) = vcs["vcs"].as_str() {
+        let commit = vcs["commit_id"].as_str()?;
+        return Some(format!("{} @ {}+{}@{}", name, vcs_name, url, commit));
+    }
+
+    Some(format!("{} @ {}", name, url))
+}
+
+/// Prints a table of the installed packages.
+pub async fn list_packages(global: Option<String>) -> eyre::Result<()> {
+    let rows = installed_packages(global, true)
+        .await?
+        .into_iter()
+        .map(|(name, version)| vec![name, version])
+        .collect::<Vec<_>>();
+
+    crate::inspect::print_table(&["PACKAGE", "VERSION"], &rows);
+
+    Ok(())
+}
+
+/// Whether `package` names a wheel file rather than a requirement.
+pub fn is_wheel_path(package: &str) -> bool {
+    package.ends_with(".whl") && Path::new(package).is_file()
+}
+
+/// Where the native installer looks for wheels: local `--find-links` directories first,
+/// then the package index when one is configured.
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    pub find_links: Vec<PathBuf>,
+    pub index: Option<Index>,
+}
+
+impl Sources {
+    /// Lists the files of `project` found in the `--find-links` directories.
+    pub fn local_files(&self, project: &str) -> eyre::Result<Vec<DistFile>> {
+        let name = crate::pep508::normalize_name(project);
+        let mut files = Vec::new();
+
+        for dir in &self.find_links {
+            let entries = std::fs::read_dir(dir).map_err(|e| {
+                eyre::eyre!(
+                    "Failed to read find-links directory {}: {}",
+                    dir.display(),
+                    e
+                )
+            })?;
+
+            for entry in entries {
+                let path = entry?.path();
+                let matches = path
+                    .file_name()
+                    .and_then(|filename| filename.to_str()?.parse::<WheelFilename>().ok())
+                    .is_some_and(|wheel| crate::pep508::normalize_name(&wheel.name) == name);
+
+                if matches {
+                    files.push(DistFile::local(&path)?);
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Lists the files of `project` in the `--find-links` directories and the index.
+    pub async fn files(&self, project: &str) -> eyre::Result<Vec<DistFile>> {
+        let mut files = self.local_files(project)?;
+
+        if let Some(index) = &self.index {
+            files.extend(index.files(project).await?);
+        }
+
+        Ok(files)
+    }
+
+    /// Returns a local path for `file`, downloading it into the cache when it's remote.
+    pub async fn fetch(&self, file: &DistFile) -> eyre::Result<PathBuf> {
+        if file.url.scheme() == "file" {
+            let path = file
+                .url
+                .to_file_path()
+                .map_err(|_| eyre::eyre!("Invalid file URL {}", file.url))?;
+            crate::index::verify(file, &tokio::fs::read(&path).await?)?;
+
+            return Ok(path);
+        }
+
+        println!("Downloading {}", file.filename);
+        crate::index::download(file, &crate::cache_home_ezpy()?.join("downloads")).await
+    }
+}
+
+/// Installs local wheel files and requirements found in `sources` without spawning pip,
+/// along with their dependencies.
+pub async fn install_wheels(
+    packages: Vec<String>,
+    sources: Sources,
+    global: Option<String>,
+) -> eyre::Result<()> {
+    let venv = resolve_env(global).await?;
+    let target = Target::new(&venv).await?;
+
+    let markers = InterpreterInfo::query(&target.python).await?.markers();
+    let wheels = plan_wheels(&packages, &sources, &target, &markers).await?;
+
+    if wheels.is_empty() {
+        println!("Nothing to install");
+        return Ok(());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        for path in &wheels {
+            let metadata = Wheel::open(path)?.install(&target)?;
+            println!("Installed {}-{}", metadata.name, metadata.version);
+        }
+
+        Ok(())
+    })
+    .await?
+}
+
+/// Collects the wheels to install for `packages`, following `Requires-Dist` recursively.
+async fn plan_wheels(
+    packages: &[String],
+    sources: &Sources,
+    target: &Target,
+    markers: &MarkerEnvironment,
+) -> eyre::Result<Vec<PathBuf>> {
+    let installed = target.installed()?;
+    let python: Version = target.version.parse()?;
+    let tags = Tags::for_interpreter(&target.version);
+
+    let mut queue = VecDeque::new();
+    let mut requested = Vec::new();
+    let mut planned: HashMap<String, Planned> = HashMap::new();
+    let mut wheels = Vec::new();
+
+    for package in packages {
+        if is_wheel_path(package) {
+            let path = PathBuf::from(package);
+            let filename: WheelFilename = path
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .parse()?;
+
+            if !tags.is_compatible(&filename) {
+                eyre::bail!(
+                    "{} is not compatible with Python {} on this platform",
+                    package,
+                    target.version
+                );
+            }
+
+            queue.push_back((None, Some(path)));
+        } else {
+            let requirement: Requirement = package.parse()?;
+            if !requirement.evaluate(markers, &[]) {
+                println!("Ignoring {}, its marker doesn't apply", requirement);
+                continue;
+            }
+
+            requested.push((Some(requirement), None));
+        }
+    }
+    // Wheels given by path are planned first, so requirements on them don't pick another.
+    queue.extend(requested);
+
+    while let Some((requirement, path)) = queue.pop_front() {
+        let (path, extras) = match (requirement, path) {
+            (_, Some(path)) => (path, Vec::new()),
+            (Some(requirement), None) => {
+                let name = requirement.normalized_name();
+
+                if let Some(planned) = planned.get_mut(&name) {
+                    queue.extend(planned.merge(&requirement, markers)?);
+                    continue;
+                }
+
+                if let Some(url) = &requirement.url {
+                    (direct_wheel(&requirement, url, &tags)?, requirement.extras)
+                } else if let Some(version) = installed
+                    .get(&name)
+                    .and_then(|version| version.parse::<Version>().ok())
+                    .filter(|version| requirement.specifiers.contains(version))
+                {
+                    println!(
+                        "Requirement already satisfied: {}=={}",
+                        requirement.name, version
+                    );
+
+                    // The installed distribution is kept, but extras may need more of it.
+                    let requires_dist = target
+                        .metadata_of(&name)?
+                        .map(|metadata| metadata.requires_dist)
+                        .unwrap_or_default();
+                    let mut entry = Planned {
+                        name: requirement.name.clone(),
+                        version,
+                        extras: Vec::new(),
+                        requires_dist,
+                    };
+                    queue.extend(entry.merge(&requirement, markers)?);
+                    planned.insert(name, entry);
+                    continue;
+                } else {
+                    let path = find_wheel(&requirement, sources, &tags, &python).await?;
+                    (path, requirement.extras)
+                }
+            }
+            (None, None) => continue,
+        };
+
+        let metadata = Wheel::open(&path)?.metadata()?;
+
+        if let Some(requires) = &metadata.requires_python {
+            let requires: SpecifierSet = requires.parse()?;
+            if !requires.contains(&python) {
+                eyre::bail!(
+                    "{} {} requires Python '{}', but the environment uses Python {}",
+                    metadata.name,
+                    metadata.version,
+                    requires,
+                    target.version
+                );
+            }
+        }
+
+        for dependency in &metadata.requires_dist {
+            let dependency: Requirement = dependency.parse()?;
+
+            if !dependency.evaluate(markers, &extras) {
+                if crate::is_verbose() {
+                    eprintln!("ezpy: skipping {}, its marker doesn't apply", dependency);
+                }
+                continue;
+            }
+
+            queue.push_back((Some(dependency), None));
+        }
+
+        planned.insert(
+            crate::pep508::normalize_name(&metadata.name),
+            Planned {
+                name: metadata.name,
+                version: metadata.version.parse()?,
+                extras,
+                requires_dist: metadata.requires_dist,
+            },
+        );
+        wheels.push(path);
+    }
+
+    Ok(wheels)
+}
+
+/// A distribution already chosen while planning an install, or kept from the environment.
+struct Planned {
+    name: String,
+    version: Version,
+    extras: Vec<String>,
+    requires_dist: Vec<String>,
+}
+
+impl Planned {
+    /// Checks a further `requirement` on the distribution against the chosen version, and
+    /// returns the dependencies brought in by the extras it adds.
+    fn merge(
+        &mut self,
+        requirement: &Requirement,
+        markers: &MarkerEnvironment,
+    ) -> eyre::Result<Vec<(Option<Requirement>, Option<PathBuf>)>> {
+        if !requirement.specifiers.contains(&self.version) {
+            eyre::bail!(
+                "Can't install {}, {} {} was already chosen for another requirement",
+                requirement,
+                self.name,
+                self.version
+            );
+        }
+
+        let previous = self.extras.clone();
+        for extra in &requirement.extras {
+            if !self.extras.contains(extra) {
+                self.extras.push(extra.clone());
+            }
+        }
+        if self.extras == previous {
+            return Ok(Vec::new());
+        }
+
+        let mut dependencies = Vec::new();
+        for dependency in &self.requires_dist {
+            let dependency: Requirement = dependency.parse()?;
+            if dependency.evaluate(markers, &self.extras)
+                && !dependency.evaluate(markers, &previous)
+            {
+                dependencies.push((Some(dependency), None));
+            }
+        }
+
+        Ok(dependencies)
+    }
+}
+
+/// The local wheel a `name @ file:///path/to/name.whl` requirement points to. Other direct
+/// references, such as remote URLs or source trees, are left to pip.
+fn direct_wheel(requirement: &Requirement, url: &str, tags: &Tags) -> eyre::Result<PathBuf> {
+    let path = reqwest::Url::parse(url)
+        .ok()
+        .filter(|url| url.scheme() == "file")
+        .and_then(|url| url.to_file_path().ok())
+        .filter(|path| is_wheel_path(&path.to_string_lossy()))
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "Can't install {}, only direct references to local wheels are supported without pip",
+                requirement
+            )
+        })?;
+
+    let filename: WheelFilename = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .parse()?;
+    if crate::pep508::normalize_name(&filename.name) != requirement.normalized_name() {
+        eyre::bail!("{} points to a wheel of {}", requirement, filename.name);
+    }
+    if !tags.is_compatible(&filename) {
+        eyre::bail!("{} is not compatible with this platform", path.display());
+    }
+
+    Ok(path)
+}
+
+/// Finds the best wheel satisfying `requirement`, preferring the `--find-links` directories
+/// and downloading from the index otherwise.
+async fn find_wheel(
+    requirement: &Requirement,
+    sources: &Sources,
+    tags: &Tags,
+    python: &Version,
+) -> eyre::Result<PathBuf> {
+    let local = sources.local_files(&requirement.name)?;
+    if let Some((_, file)) = best_wheel(local, requirement, tags, python) {
+        return sources.fetch(&file).await;
+    }
+
+    if let Some(index) = &sources.index {
+        if let Some((_, file)) = index.find_wheel(requirement, tags, python).await? {
+            return sources.fetch(&file).await;
+        }
+
+        eyre::bail!(
+            "No compatible wheel satisfying '{}' found for Python {} on this platform",
+            requirement,
+            python
+        );
+    }
+
+    if sources.find_links.is_empty() {
+        eyre::bail!(
+            "No wheel found for '{}', pass a `--find-links` directory or an `--index-url`",
+            requirement
+        );
+    }
+
+    eyre::bail!(
+        "No compatible wheel satisfying '{}' found in the find-links directories",
+        requirement
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wheel::tests::{temp_dir, test_target, write_wheel};
+
+    fn file_names(wheels: &[PathBuf]) -> Vec<String> {
+        wheels
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn plans_dependencies_with_markers_and_extras() {
+        let dir = temp_dir("plan-wheels");
+        let links = dir.join("wheels");
+        std::fs::create_dir_all(&links).unwrap();
+
+        write_wheel(
+            &links,
+            "demo-1.0-py3-none-any.whl",
+            &[
+                "dep>=1",
+                "win-only; sys_platform == 'win32'",
+                "fancy; extra == 'cli'",
+            ],
+            &[],
+        );
+        write_wheel(&links, "dep-1.0-py3-none-any.whl", &[], &[]);
+        write_wheel(&links, "dep-2.0-py3-none-any.whl", &[], &[]);
+        write_wheel(&links, "fancy-1.0-py3-none-any.whl", &[], &[]);
+
+        let sources = Sources {
+            find_links: vec![links.clone()],
+            index: None,
+        };
+        let target = test_target(&dir.join("env"));
+        let mut markers = MarkerEnvironment::current("3.12.1");
+        markers
+            .0
+            .insert("sys_platform".to_string(), "linux".to_string());
+
+        let wheels = plan_wheels(&["demo".to_string()], &sources, &target, &markers)
+            .await
+            .unwrap();
+        assert_eq!(
+            file_names(&wheels),
+            vec!["demo-1.0-py3-none-any.whl", "dep-2.0-py3-none-any.whl"]
+        );
+
+        let wheels = plan_wheels(&["demo[cli]".to_string()], &sources, &target, &markers)
+            .await
+            .unwrap();
+        assert_eq!(
+            file_names(&wheels),
+            vec![
+                "demo-1.0-py3-none-any.whl",
+                "dep-2.0-py3-none-any.whl",
+                "fancy-1.0-py3-none-any.whl"
+            ]
+        );
+
+        // Installed distributions satisfying the requirement are kept.
+        Wheel::open(&links.join("dep-1.0-py3-none-any.whl"))
+            .unwrap()
+            .install(&target)
+            .unwrap();
+        let wheels = plan_wheels(&["demo".to_string()], &sources, &target, &markers)
+            .await
+            .unwrap();
+        assert_eq!(file_names(&wheels), vec!["demo-1.0-py3-none-any.whl"]);
+
+        let error = plan_wheels(&["missing".to_string()], &sources, &target, &markers)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No compatible wheel satisfying 'missing' found in the find-links directories"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn merges_later_requirements_on_planned_distributions() {
+        let dir = temp_dir("plan-merge");
+        let links = dir.join("wheels");
+        std::fs::create_dir_all(&links).unwrap();
+
+        write_wheel(
+            &links,
+            "demo-1.0-py3-none-any.whl",
+            &["fancy; extra == 'cli'"],
+            &[],
+        );
+        write_wheel(&links, "fancy-1.0-py3-none-any.whl", &[], &[]);
+        write_wheel(&links, "dep-1.0-py3-none-any.whl", &[], &[]);
+        write_wheel(&links, "dep-2.0-py3-none-any.whl", &[], &[]);
+        write_wheel(&links, "other-1.0-py3-none-any.whl", &["dep>=2"], &[]);
+
+        let sources = Sources {
+            find_links: vec![links.clone()],
+            index: None,
+        };
+        let target = test_target(&dir.join("env"));
+        let markers = MarkerEnvironment::current("3.12.1");
+        let plan = |packages: &[&str]| {
+            let packages = packages.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+            let (sources, target, markers) = (&sources, &target, &markers);
+            async move { plan_wheels(&packages, sources, target, markers).await }
+        };
+
+        // Extras requested later still bring in their dependencies.
+        assert_eq!(
+            file_names(&plan(&["demo", "demo[cli]"]).await.unwrap()),
+            vec!["demo-1.0-py3-none-any.whl", "fancy-1.0-py3-none-any.whl"]
+        );
+
+        let error = plan(&["dep<2", "other"]).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Can't install dep>=2, dep 1.0 was already chosen for another requirement"
+        );
+
+        // The same holds for distributions kept from the environment.
+        Wheel::open(&links.join("demo-1.0-py3-none-any.whl"))
+            .unwrap()
+            .install(&target)
+            .unwrap();
+        assert_eq!(
+            file_names(&plan(&["demo", "demo[cli]"]).await.unwrap()),
+            vec!["fancy-1.0-py3-none-any.whl"]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn freezes_from_direct_url() {
+        let dir = temp_dir("freeze");
+        let write = |dist_info: &str, contents: &str| {
+            std::fs::create_dir_all(dir.join(dist_info)).unwrap();
+            std::fs::write(dir.join(dist_info).join("direct_url.json"), contents).unwrap();
+        };
+        write(
+            "local-1.0.dist-info",
+            r#"{"url": "https://example.com/local-1.0-py3-none-any.whl", "archive_info": {}}"#,
+        );
+        write("broken-2.0.dist-info", "{not json");
+
+        assert_eq!(
+            frozen_requirement(&dir, "local", "1.0").await,
+            "local @ https://example.com/local-1.0-py3-none-any.whl"
+        );
+        assert_eq!(
+            frozen_requirement(&dir, "broken", "2.0").await,
+            "broken==2.0"
+        );
+        assert_eq!(frozen_requirement(&dir, "plain", "3.0").await, "plain==3.0");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn plans_direct_references_to_local_wheels() {
+        let dir = temp_dir("plan-direct");
+        let local = write_wheel(&dir, "local-3.0-py3-none-any.whl", &["dep"], &[]);
+        let links = dir.join("wheels");
+        std::fs::create_dir_all(&links).unwrap();
+        write_wheel(&links, "dep-1.0-py3-none-any.whl", &[], &[]);
+
+        let sources = Sources {
+            find_links: vec![links],
+            index: None,
+        };
+        let target = test_target(&dir.join("env"));
+        let markers = MarkerEnvironment::current("3.12.1");
+        let url = reqwest::Url::from_file_path(&local).unwrap();
+
+        let wheels = plan_wheels(&[format!("local @ {}", url)], &sources, &target, &markers)
+            .await
+            .unwrap();
+        assert_eq!(
+            file_names(&wheels),
+            vec!["local-3.0-py3-none-any.whl", "dep-1.0-py3-none-any.whl"]
+        );
+
+        let error = plan_wheels(&[format!("other @ {}", url)], &sources, &target, &markers)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("other @ {} points to a wheel of local", url)
+        );
+
+        let remote = "remote @ https://example.com/remote-1.0-py3-none-any.whl".to_string();
+        let error = plan_wheels(&[remote], &sources, &target, &markers)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Can't install remote @ https://example.com/"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
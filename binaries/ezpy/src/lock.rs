@@ -0,0 +1,551 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::index::DistFile;
+use crate::install::Sources;
+use crate::pep508::{normalize_name, InterpreterInfo, MarkerEnvironment, Requirement};
+use crate::requirements::{RequirementsFile, Spec};
+use crate::tags::Tags;
+use crate::wheel::{Target, Wheel, WheelFilename};
+
+pub const LOCK_FILE: &str = "ezpy.lock";
+pub const REQUIREMENTS_IN: &str = "requirements.in";
+
+const LOCK_VERSION: u32 = 1;
+
+/// The content of `ezpy.lock`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lock {
+    pub version: u32,
+
+    /// The interpreter the lock was resolved for.
+    pub python: String,
+
+    /// The project requirements the lock was resolved from.
+    pub requires: Vec<String>,
+
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<LockedDependency>,
+
+    #[serde(default)]
+    pub wheels: Vec<LockedWheel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedDependency {
+    pub name: String,
+
+    /// The platforms the dependency is limited to, as a PEP 508 marker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockedWheel {
+    pub filename: String,
+    pub url: String,
+
+    /// The digest of the file, as `sha256:<hex>`.
+    pub hash: String,
+}
+
+impl LockedWheel {
+    fn dist_file(&self) -> eyre::Result<DistFile> {
+        let (algorithm, digest) = self
+            .hash
+            .split_once(':')
+            .ok_or_else(|| eyre::eyre!("Invalid hash '{}' for {}", self.hash, self.filename))?;
+
+        Ok(DistFile {
+            filename: self.filename.clone(),
+            url: self
+                .url
+                .parse()
+                .map_err(|e| eyre::eyre!("Invalid URL '{}': {}", self.url, e))?,
+            hashes: HashMap::from([(algorithm.to_string(), digest.to_string())]),
+            requires_python: None,
+            yanked: false,
+            core_metadata: None,
+        })
+    }
+}
+
+impl Lock {
+    pub async fn read(path: &Path) -> eyre::Result<Self> {
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+        let lock: Lock = toml::from_str(&contents)
+            .map_err(|e| eyre::eyre!("Failed to parse {}: {}", path.display(), e))?;
+
+        if lock.version != LOCK_VERSION {
+            eyre::bail!(
+                "{} uses lockfile version {}, this ezpy only supports version {}",
+                path.display(),
+                lock.version,
+                LOCK_VERSION
+            );
+        }
+
+        Ok(lock)
+    }
+}
+
+/// Finds the project's requirements: `[project].dependencies` of the nearest
+/// `pyproject.toml` (plus the requested optional groups) or the nearest `requirements.in`.
+/// Returns the project directory along with them.
+pub async fn project_requirements(extras: &[String]) -> eyre::Result<(PathBuf, Vec<String>)> {
+    let current = std::env::current_dir()?;
+
+    if let Some(path) = crate::pyproject::find_pyproject(&current) {
+        let pyproject = crate::pyproject::PyProject::read(&path).await?;
+
+        if let Some(project) = pyproject.project {
+            let mut requirements = project.dependencies;
+            for extra in extras {
+                let group = project.optional_dependencies.get(extra).ok_or_else(|| {
+                    eyre::eyre!(
+                        "{} has no optional dependencies named '{}'",
+                        path.display(),
+                        extra
+                    )
+                })?;
+                requirements.extend(group.iter().cloned());
+            }
+
+            return Ok((path.parent().unwrap().to_path_buf(), requirements));
+        }
+    }
+
+    if let Some(path) = crate::find_upwards(&current, REQUIREMENTS_IN) {
+        let file = RequirementsFile::read(&path)?;
+        file.check()?;
+        for warning in &file.warnings {
+            println!("Warning: {}", warning);
+        }
+
+        let requirements = file
+            .requirements
+            .iter()
+            .map(|entry| match &entry.spec {
+                Spec::Named(requirement) if !entry.editable && requirement.url.is_none() => {
+                    Ok(requirement.to_string())
+                }
+                spec => eyre::bail!(
+                    "{}:{}: only requirements on index distributions can be locked, not '{}'",
+                    entry.file.display(),
+                    entry.line,
+                    spec
+                ),
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        return Ok((path.parent().unwrap().to_path_buf(), requirements));
+    }
+
+    eyre::bail!(
+        "No pyproject.toml with a [project] table or {} found",
+        REQUIREMENTS_IN
+    )
+}
+
+/// Resolves the project's requirements and writes them, pinned with hashes, to `ezpy.lock`.
+pub async fn lock(python: Option<String>, extras: &[String], sources: Sources) -> eyre::Result<()> {
+    let (project, requires) = project_requirements(extras).await?;
+    let resolved = crate::pin::resolve_version(python).await?;
+
+    let requirements = requires
+        .iter()
+        .map(|requirement| requirement.parse::<Requirement>())
+        .collect::<eyre::Result<Vec<_>>>()?;
+
+    println!(
+        "Resolving {} requirement(s) for Python {}...",
+        requirements.len(),
+        resolved.version
+    );
+
+    let packages = crate::resolver::Resolver::new(&sources, &resolved.version)?
+        .resolve(&requirements)
+        .await?;
+
+    let locked = locked_packages(&packages).await?;
+
+    let lock = Lock {
+        version: LOCK_VERSION,
+        python: resolved.version.clone(),
+        requires,
+        packages: locked,
+    };
+
+    let path = project.join(LOCK_FILE);
+    tokio::fs::write(&path, toml::to_string(&lock)?).await?;
+
+    println!(
+        "Locked {} package(s) in {}",
+        lock.packages.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+/// The lock entries of resolved `packages`, with the wheels whose digest is known.
+async fn locked_packages(
+    packages: &BTreeMap<String, crate::resolver::Package>,
+) -> eyre::Result<Vec<LockedPackage>> {
+    let mut locked = Vec::new();
+
+    for package in packages.values() {
+        let mut wheels = Vec::new();
+        for file in &package.wheels {
+            match file_hash(file).await? {
+                Some(hash) => wheels.push(LockedWheel {
+                    filename: file.filename.clone(),
+                    url: file.url.to_string(),
+                    hash,
+                }),
+                None if crate::is_verbose() => {
+                    eprintln!(
+                        "ezpy: leaving {} out of the lock, its digest is unknown",
+                        file.filename
+                    )
+                }
+                None => {}
+            }
+        }
+
+        locked.push(LockedPackage {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            dependencies: package
+                .dependencies
+                .iter()
+                .map(|(name, marker)| LockedDependency {
+                    name: name.clone(),
+                    marker: marker.clone(),
+                })
+                .collect(),
+            wheels,
+        });
+    }
+
+    Ok(locked)
+}
+
+/// The `sha256:<hex>` digest of `file`, from the index or computed from a local copy.
+async fn file_hash(file: &DistFile) -> eyre::Result<Option<String>> {
+    if let Some(digest) = file.hashes.get("sha256") {
+        return Ok(Some(format!("sha256:{}", digest.to_ascii_lowercase())));
+    }
+
+    let path = if file.url.scheme() == "file" {
+        file.url.to_file_path().ok()
+    } else {
+        Some(
+            crate::cache_home_ezpy()?
+                .join("downloads")
+                .join(&file.filename),
+        )
+    };
+
+    match path {
+        Some(path) if path.is_file() => {
+            let contents = tokio::fs::read(&path).await?;
+            Ok(Some(format!("sha256:{:x}", Sha256::digest(&contents))))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Makes the project's `.venv` match `ezpy.lock` exactly, creating the environment if needed.
+pub async fn sync(extras: &[String], python: Option<String>) -> eyre::Result<()> {
+    let current = std::env::current_dir()?;
+    let path = crate::find_upwards(&current, LOCK_FILE)
+        .ok_or_else(|| eyre::eyre!("No {} found, create one with `ezpy lock`", LOCK_FILE))?;
+    let lock = Lock::read(&path).await?;
+
+    if let Some(python) = python {
+        if !crate::pin::matches_request(&lock.python, &python) {
+            eyre::bail!(
+                "{} was resolved for Python {}, not Python {} from --python, run `ezpy --python {} lock` first",
+                path.display(),
+                lock.python,
+                python,
+                python
+            );
+        }
+    }
+    let project = path.parent().unwrap().to_path_buf();
+
+    if let Ok((_, requires)) = project_requirements(extras).await {
+        if requires != lock.requires {
+            println!(
+                "Warning: {} is out of date with the project requirements, run `ezpy lock` to update it",
+                path.display()
+            );
+        }
+    }
+
+    let env = project.join(".venv");
+    if !crate::venv::is_valid_env(&env) {
+        let version = crate::pin::resolve_installed(&lock.python)?;
+        let prompt = project
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| ".venv".to_string());
+
+        crate::venv::create_env(&version, &env, &prompt, Default::default()).await?;
+    }
+
+    let target = Target::new(&env).await?;
+    if crate::pyvenv::short_version(&target.version) != crate::pyvenv::short_version(&lock.python) {
+        eyre::bail!(
+            "{} was resolved for Python {} but {} uses Python {}, run `ezpy lock` again",
+            path.display(),
+            lock.python,
+            env.display(),
+            target.version
+        );
+    }
+
+    let markers = InterpreterInfo::query(&target.python).await?.markers();
+    let changes = apply_lock(&lock, &path, &target, &markers).await?;
+
+    if changes == 0 {
+        println!("{} is up to date with {}", env.display(), path.display());
+    }
+
+    Ok(())
+}
+
+/// Installs and removes packages so `target` matches `lock` (read from `path`) for the
+/// interpreter described by `markers`, returning how many packages changed.
+async fn apply_lock(
+    lock: &Lock,
+    path: &Path,
+    target: &Target,
+    markers: &MarkerEnvironment,
+) -> eyre::Result<usize> {
+    let tags = Tags::for_interpreter(&target.version);
+
+    let mut roots = Vec::new();
+    for requirement in &lock.requires {
+        let requirement: Requirement = requirement.parse()?;
+        if requirement.evaluate(markers, &[]) {
+            roots.push(requirement.normalized_name());
+        }
+    }
+
+    let packages = lock
+        .packages
+        .iter()
+        .map(|package| (normalize_name(&package.name), package))
+        .collect::<BTreeMap<_, _>>();
+    let dependencies = packages
+        .iter()
+        .map(|(name, package)| {
+            let dependencies = package
+                .dependencies
+                .iter()
+                .map(|dependency| (normalize_name(&dependency.name), dependency.marker.clone()))
+                .collect();
+            (name.clone(), dependencies)
+        })
+        .collect::<HashMap<_, _>>();
+
+    let wanted = crate::resolver::reachable(&roots, &dependencies, markers)?;
+    let installed = target.installed()?;
+    let sources = Sources::default();
+    let mut changes = 0;
+
+    for (name, version) in &installed {
+        if wanted.contains(name) || crate::venv::SEEDED_PACKAGES.contains(&name.as_str()) {
+            continue;
+        }
+
+        crate::wheel::uninstall(target, name)?;
+        println!("Removed {}-{}", name, version);
+        changes += 1;
+    }
+
+    for (name, package) in &packages {
+        let current = installed
+            .get(name)
+            .and_then(|version| version.parse::<crate::pep440::Version>().ok());
+        let locked = package.version.parse::<crate::pep440::Version>()?;
+        if !wanted.contains(name) || current.as_ref() == Some(&locked) {
+            continue;
+        }
+
+        let wheel = package
+            .wheels
+            .iter()
+            .filter_map(|wheel| {
+                let filename = wheel.filename.parse::<WheelFilename>().ok()?;
+                Some((tags.priority(&filename)?, wheel))
+            })
+            .min_by_key(|(priority, _)| *priority)
+            .map(|(_, wheel)| wheel)
+            .ok_or_else(|| {
+                eyre::eyre!(
+                    "No wheel of {} {} in {} is compatible with Python {} on this platform",
+                    package.name,
+                    package.version,
+                    path.display(),
+                    target.version
+                )
+            })?;
+
+        let file = sources.fetch(&wheel.dist_file()?).await?;
+        let install_target = target.clone();
+        tokio::task::spawn_blocking(move || Wheel::open(&file)?.install(&install_target)).await??;
+
+        match installed.get(name) {
+            Some(previous) => println!(
+                "Updated {} {} -> {}",
+                package.name, previous, package.version
+            ),
+            None => println!("Installed {}-{}", package.name, package.version),
+        }
+        changes += 1;
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::tests::{local_index, publish};
+    use crate::wheel::tests::{temp_dir, test_target};
+
+    async fn resolve_lock(sources: &Sources, requires: &[&str]) -> Lock {
+        let requirements = requires
+            .iter()
+            .map(|requirement| requirement.parse().unwrap())
+            .collect::<Vec<_>>();
+        let packages = crate::resolver::Resolver::new(sources, "3.12.1")
+            .unwrap()
+            .resolve(&requirements)
+            .await
+            .unwrap();
+
+        Lock {
+            version: LOCK_VERSION,
+            python: "3.12.1".to_string(),
+            requires: requires.iter().map(|r| r.to_string()).collect(),
+            packages: locked_packages(&packages).await.unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn round_trips_lock_file() {
+        let dir = temp_dir("lock-round-trip");
+        let app = publish(
+            &dir,
+            "app-1.0-py3-none-any.whl",
+            &["lib", "winonly; sys_platform == 'other-os'"],
+        );
+        publish(&dir, "lib-1.0-py3-none-any.whl", &[]);
+        publish(&dir, "winonly-1.0-cp312-cp312-other_os.whl", &[]);
+
+        let lock = resolve_lock(&local_index(&dir), &["app"]).await;
+        let path = dir.join(LOCK_FILE);
+        tokio::fs::write(&path, toml::to_string(&lock).unwrap())
+            .await
+            .unwrap();
+        let read = Lock::read(&path).await.unwrap();
+
+        assert_eq!(read.requires, vec!["app"]);
+        assert_eq!(
+            read.packages
+                .iter()
+                .map(|package| format!("{}=={}", package.name, package.version))
+                .collect::<Vec<_>>(),
+            vec!["app==1.0", "lib==1.0", "winonly==1.0"]
+        );
+
+        let app_entry = &read.packages[0];
+        assert_eq!(app_entry.dependencies.len(), 2);
+        assert_eq!(app_entry.dependencies[0].name, "lib");
+        assert_eq!(app_entry.dependencies[0].marker, None);
+        assert_eq!(
+            app_entry.dependencies[1].marker.as_deref(),
+            Some("sys_platform == \"other-os\"")
+        );
+        assert_eq!(
+            app_entry.wheels[0].hash,
+            format!("sha256:{:x}", Sha256::digest(std::fs::read(&app).unwrap()))
+        );
+        assert_eq!(
+            app_entry.wheels[0].dist_file().unwrap().url,
+            reqwest::Url::from_file_path(&app).unwrap()
+        );
+
+        tokio::fs::write(&path, "version = 2\npython = \"3.12\"\nrequires = []\n")
+            .await
+            .unwrap();
+        assert!(Lock::read(&path)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("uses lockfile version 2"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn sync_installs_locked_and_removes_extraneous_packages() {
+        let dir = temp_dir("lock-sync");
+        let index = dir.join("index");
+        publish(&index, "app-1.0-py3-none-any.whl", &["lib"]);
+        publish(&index, "lib-1.0-py3-none-any.whl", &[]);
+        publish(&index, "lib-2.0-py3-none-any.whl", &[]);
+        let stray = publish(&index, "stray-1.0-py3-none-any.whl", &[]);
+        let sources = local_index(&index);
+
+        let lock = resolve_lock(&sources, &["app", "lib<2"]).await;
+        let path = dir.join(LOCK_FILE);
+        let target = test_target(&dir.join("env"));
+        let markers = MarkerEnvironment::current("3.12.1");
+
+        // An outdated locked package and one the lock doesn't know about.
+        Wheel::open(&index.join("lib").join("lib-2.0-py3-none-any.whl"))
+            .unwrap()
+            .install(&target)
+            .unwrap();
+        Wheel::open(&stray).unwrap().install(&target).unwrap();
+
+        assert_eq!(
+            apply_lock(&lock, &path, &target, &markers).await.unwrap(),
+            3
+        );
+        assert_eq!(
+            target.installed().unwrap(),
+            HashMap::from([
+                ("app".to_string(), "1.0".to_string()),
+                ("lib".to_string(), "1.0".to_string()),
+            ])
+        );
+
+        assert_eq!(
+            apply_lock(&lock, &path, &target, &markers).await.unwrap(),
+            0
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
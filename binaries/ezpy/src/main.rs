@@ -1,15 +1,56 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use eyre::{OptionExt, Result};
 use indygreg::metadata::VersionString;
 
+pub mod build;
+pub mod bundle;
+pub mod config;
+pub mod doctor;
+pub mod editable;
+pub mod export;
+pub mod hook;
+pub mod index;
+pub mod inspect;
 pub mod install;
+pub mod lock;
+pub mod pep440;
+pub mod pep508;
+pub mod pep517;
 pub mod pin;
+pub mod pip;
+pub mod publish;
+pub mod pyproject;
+pub mod pyvenv;
+pub mod relocate;
+pub mod requirements;
+pub mod resolver;
+pub mod run;
+pub mod script;
+pub mod shell;
+pub mod shim;
+pub mod tags;
+pub mod tool;
 pub mod venv;
+pub mod wheel;
 
 pub mod tui;
 
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Whether `--verbose` was passed or `EZPY_VERBOSE` is set.
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed) || std::env::var_os("EZPY_VERBOSE").is_some()
+}
+
+/// Whether `--quiet` was passed or `EZPY_QUIET` is set.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed) || std::env::var_os("EZPY_QUIET").is_some()
+}
+
 pub fn install_home_ezpy() -> eyre::Result<PathBuf> {
     if cfg!(windows) {
         Ok(simple_home_dir::home_dir()
@@ -29,6 +70,18 @@ pub fn install_home_ezpy() -> eyre::Result<PathBuf> {
     }
 }
 
+pub fn cache_home_ezpy() -> eyre::Result<PathBuf> {
+    Ok(install_home_ezpy()?.join("cache"))
+}
+
+/// Looks for `name` in `start` and each of its ancestors, returning the first match.
+pub fn find_upwards(start: &Path, name: &str) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(name))
+        .find(|path| path.exists())
+}
+
 pub fn python_bin_path() -> PathBuf {
     if cfg!(windows) {
         PathBuf::from("python.exe")
@@ -45,6 +98,26 @@ pub fn env_bin_path() -> PathBuf {
     }
 }
 
+pub fn env_scripts_path() -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from("Scripts")
+    } else {
+        PathBuf::from("bin")
+    }
+}
+
+pub fn stdlib_path(version: &str) -> PathBuf {
+    if cfg!(windows) {
+        PathBuf::from("Lib")
+    } else {
+        PathBuf::from("lib").join(format!("python{}", pyvenv::short_version(version)))
+    }
+}
+
+pub fn site_packages_path(version: &str) -> PathBuf {
+    stdlib_path(version).join("site-packages")
+}
+
 #[derive(Parser)]
 #[command(
     name = env!("CARGO_PKG_NAME"),
@@ -54,6 +127,31 @@ pub fn env_bin_path() -> PathBuf {
 struct EzpyCLI {
     #[command(subcommand)]
     command: Option<EzpyCommands>,
+
+    #[arg(
+        long = "python",
+        value_name = "VERSION",
+        global = true,
+        help = "Python version to use, overriding EZPY_PYTHON, .python-version, pyproject.toml and the global pin."
+    )]
+    python: Option<VersionString>,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        global = true,
+        help = "Explain which environment and interpreter are used."
+    )]
+    verbose: bool,
+
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        global = true,
+        conflicts_with = "verbose",
+        help = "Don't show the output of pip unless it fails."
+    )]
+    quiet: bool,
 }
 
 #[derive(Subcommand)]
@@ -63,16 +161,84 @@ enum EzpyCommands {
     )]
     Install(InstallArgs),
 
+    #[command(
+        about = "Install packages into the project's environment and add them to pyproject.toml."
+    )]
+    Add(AddArgs),
+
+    #[command(
+        about = "Uninstall packages from the project's environment and remove them from pyproject.toml."
+    )]
+    Remove(AddArgs),
+
+    #[command(about = "Uninstall packages from the current environment.")]
+    Uninstall(UninstallArgs),
+
+    #[command(about = "Upgrade packages of the current environment to their latest version.")]
+    Upgrade(UpgradeArgs),
+
+    #[command(about = "Print the packages of the current environment as pinned requirements.")]
+    Freeze(FreezeArgs),
+
     #[command(about = "Manage Python environments (create, activate, deactivate, list, etc.)")]
     Env(EnvArgs),
 
     #[command(
-        about = "Pin a Python binary to a specific version so it's used in all following commands."
+        about = "Pin a Python version for the current directory (or globally) so it's used in all following commands."
     )]
     Pin(PinArgs),
 
     #[command(about = "List available Python versions and packages.")]
-    List,
+    List(PackagesArgs),
+
+    #[command(
+        about = "Run a command inside the nearest virtual environment without activating it."
+    )]
+    Run(RunArgs),
+
+    #[command(about = "Edit the inline metadata of single-file scripts.")]
+    Script(ScriptArgs),
+
+    #[command(about = "Spawn a subshell with a virtual environment activated.")]
+    Shell(ShellArgs),
+
+    #[command(
+        about = "Print a shell hook activating the nearest environment on every directory change."
+    )]
+    Hook(HookArgs),
+
+    #[command(about = "Manage the python/pip shims routing to the right interpreter.")]
+    Shims(ShimsArgs),
+
+    #[command(
+        about = "Create or install a self-contained bundle of an environment and its interpreter."
+    )]
+    Bundle(BundleArgs),
+
+    #[command(about = "Build the sdist and wheel of a local project into its dist directory.")]
+    Build(BuildArgs),
+
+    #[command(about = "Upload wheels and sdists to a package index.")]
+    Publish(PublishArgs),
+
+    #[command(about = "Work with requirements files.")]
+    Requirements(RequirementsArgs),
+
+    #[command(
+        about = "Resolve the project's dependencies from pyproject.toml or requirements.in into ezpy.lock."
+    )]
+    Lock(LockArgs),
+
+    #[command(about = "Make the project's .venv match ezpy.lock exactly.")]
+    Sync(SyncArgs),
+
+    #[command(
+        about = "Install command-line tools in isolated environments, or run them without installing."
+    )]
+    Tool(ToolArgs),
+
+    #[command(name = "hook-env", hide = true)]
+    HookEnv(HookArgs),
 }
 
 #[derive(Parser)]
@@ -83,8 +249,428 @@ struct InstallArgs {
     #[arg(value_name = "PACKAGES OR `python <VERSION>'")]
     packages: Vec<String>,
 
+    #[arg(
+        short = 'e',
+        long = "editable",
+        value_name = "PATH",
+        help = "Install a local project in editable mode, so changes to its sources apply without reinstalling."
+    )]
+    editable: Vec<PathBuf>,
+
     #[arg(value_name = "NAME", long = "global")]
     global: Option<String>,
+
+    #[command(flatten)]
+    index: IndexArgs,
+}
+
+#[derive(Parser)]
+struct AddArgs {
+    #[arg(value_name = "PACKAGES", required = true)]
+    packages: Vec<String>,
+
+    #[arg(
+        long = "group",
+        value_name = "NAME",
+        help = "Use this group of [project.optional-dependencies] instead of [project].dependencies."
+    )]
+    group: Option<String>,
+}
+
+#[derive(Parser)]
+struct UninstallArgs {
+    #[arg(value_name = "PACKAGES", required = true)]
+    packages: Vec<String>,
+
+    #[arg(value_name = "NAME", long = "global")]
+    global: Option<String>,
+}
+
+#[derive(Parser)]
+struct UpgradeArgs {
+    #[arg(value_name = "PACKAGES")]
+    packages: Vec<String>,
+
+    #[arg(
+        long = "all",
+        conflicts_with = "packages",
+        help = "Upgrade every installed package."
+    )]
+    all: bool,
+
+    #[arg(
+        long = "outdated-only",
+        help = "Only upgrade the packages that have a newer version available."
+    )]
+    outdated_only: bool,
+
+    #[arg(value_name = "NAME", long = "global")]
+    global: Option<String>,
+}
+
+#[derive(Parser)]
+struct FreezeArgs {
+    #[arg(value_name = "NAME", long = "global")]
+    global: Option<String>,
+
+    #[arg(
+        long = "all",
+        help = "Include pip, which is seeded into every environment."
+    )]
+    all: bool,
+}
+
+#[derive(Parser)]
+struct PackagesArgs {
+    #[arg(
+        long = "packages",
+        help = "List the packages installed in the current environment instead."
+    )]
+    packages: bool,
+
+    #[arg(value_name = "NAME", long = "global", requires = "packages")]
+    global: Option<String>,
+}
+
+/// Where to look for wheels; passing any of these makes `ezpy install` skip pip.
+#[derive(Args, Clone)]
+struct IndexArgs {
+    #[arg(
+        long = "find-links",
+        value_name = "DIR",
+        help = "Look for wheels in a local directory."
+    )]
+    find_links: Vec<PathBuf>,
+
+    #[arg(
+        long = "index-url",
+        value_name = "URL",
+        help = "Simple repository to look for wheels in (HTTP or file://), defaults to PyPI."
+    )]
+    index_url: Option<String>,
+
+    #[arg(
+        long = "extra-index-url",
+        value_name = "URL",
+        help = "Additional simple repository to look for wheels in."
+    )]
+    extra_index_url: Vec<String>,
+
+    #[arg(
+        long = "no-index",
+        help = "Only look for wheels in the find-links directories."
+    )]
+    no_index: bool,
+}
+
+impl IndexArgs {
+    fn is_set(&self) -> bool {
+        !self.find_links.is_empty()
+            || self.index_url.is_some()
+            || !self.extra_index_url.is_empty()
+            || self.no_index
+    }
+
+    /// The wheel sources, querying the default index unless `--no-index` is passed or
+    /// `default_index` is unset and no index was given.
+    fn sources(self, default_index: bool) -> Result<install::Sources> {
+        let wants_index =
+            default_index || self.index_url.is_some() || !self.extra_index_url.is_empty();
+
+        let index = if wants_index && !self.no_index {
+            Some(index::Index::new(
+                self.index_url.as_deref(),
+                &self.extra_index_url,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(install::Sources {
+            find_links: self.find_links,
+            index,
+        })
+    }
+}
+
+#[derive(Parser)]
+struct BuildArgs {
+    #[arg(value_name = "PATH", default_value = ".")]
+    project: PathBuf,
+
+    #[arg(long = "sdist", help = "Only build the sdist.")]
+    sdist: bool,
+
+    #[arg(long = "wheel", help = "Only build the wheel.")]
+    wheel: bool,
+
+    #[arg(
+        short = 'o',
+        long = "outdir",
+        value_name = "DIR",
+        help = "Directory to write the distributions to, defaults to the dist directory of the project."
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct PublishArgs {
+    #[arg(value_name = "FILES", required = true)]
+    files: Vec<PathBuf>,
+
+    #[arg(
+        long = "repository",
+        value_name = "NAME",
+        conflicts_with = "repository_url",
+        help = "Upload to a repository of the ezpy configuration file, defaults to PyPI."
+    )]
+    repository: Option<String>,
+
+    #[arg(
+        long = "repository-url",
+        value_name = "URL",
+        help = "Upload to this legacy upload API URL."
+    )]
+    repository_url: Option<String>,
+}
+
+#[derive(Parser)]
+struct RequirementsArgs {
+    #[command(subcommand)]
+    command: RequirementsCommand,
+}
+
+#[derive(Subcommand)]
+enum RequirementsCommand {
+    #[command(
+        about = "Report syntax errors of a requirements file and which requirements apply to the environment."
+    )]
+    Check(RequirementsCheckArgs),
+}
+
+#[derive(Parser)]
+struct RequirementsCheckArgs {
+    #[arg(value_name = "FILE")]
+    file: PathBuf,
+
+    #[arg(value_name = "NAME", long = "global")]
+    global: Option<String>,
+}
+
+#[derive(Parser)]
+struct LockArgs {
+    #[arg(
+        long = "extra",
+        value_name = "NAME",
+        help = "Include an optional dependency group of pyproject.toml."
+    )]
+    extras: Vec<String>,
+
+    #[command(flatten)]
+    index: IndexArgs,
+}
+
+#[derive(Parser)]
+struct SyncArgs {
+    #[arg(
+        long = "extra",
+        value_name = "NAME",
+        help = "Optional dependency groups the lockfile was created with."
+    )]
+    extras: Vec<String>,
+}
+
+#[derive(Parser)]
+struct RunArgs {
+    #[arg(value_name = "NAME", long = "global")]
+    global: Option<String>,
+
+    #[arg(
+        value_name = "COMMAND",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    command: Vec<String>,
+}
+
+#[derive(Parser)]
+struct ScriptArgs {
+    #[command(subcommand)]
+    command: ScriptCommand,
+}
+
+#[derive(Subcommand)]
+enum ScriptCommand {
+    #[command(
+        about = "Add dependencies to the `# /// script` block of a script, creating it if needed."
+    )]
+    AddDep(ScriptAddDepArgs),
+}
+
+#[derive(Parser)]
+struct ScriptAddDepArgs {
+    #[arg(value_name = "SCRIPT")]
+    script: PathBuf,
+
+    #[arg(value_name = "PACKAGES", required = true)]
+    packages: Vec<String>,
+}
+
+#[derive(Parser)]
+struct ShellArgs {
+    #[arg(
+        value_name = "NAME",
+        help = "Global environment to activate instead of the nearest one."
+    )]
+    name: Option<String>,
+
+    #[arg(
+        long = "shell",
+        value_name = "SHELL",
+        help = "Shell to spawn instead of the detected one."
+    )]
+    shell: Option<shell::Shell>,
+}
+
+#[derive(Parser)]
+struct HookArgs {
+    #[arg(value_name = "SHELL")]
+    shell: shell::Shell,
+}
+
+#[derive(Parser)]
+struct ShimsArgs {
+    #[command(subcommand)]
+    command: ShimsCommand,
+}
+
+#[derive(Parser)]
+struct BundleArgs {
+    #[command(subcommand)]
+    command: BundleCommand,
+}
+
+#[derive(Subcommand)]
+enum BundleCommand {
+    #[command(
+        about = "Pack a global virtual environment and its Python interpreter into an archive."
+    )]
+    Create(BundleCreateArgs),
+
+    #[command(about = "Unpack a bundle and point its environment at the bundled interpreter.")]
+    Install(BundleInstallArgs),
+}
+
+#[derive(Parser)]
+struct BundleCreateArgs {
+    #[arg(
+        long = "env",
+        value_name = "NAME",
+        help = "The global virtual environment to bundle."
+    )]
+    env: String,
+
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_name = "FILE",
+        help = "Path of the archive to create."
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct BundleInstallArgs {
+    #[arg(value_name = "ARCHIVE")]
+    archive: PathBuf,
+
+    #[arg(
+        long = "dir",
+        value_name = "DIR",
+        help = "Where to unpack the bundle, defaults to ./<NAME>."
+    )]
+    dir: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+struct ToolArgs {
+    #[command(subcommand)]
+    command: ToolCommand,
+}
+
+#[derive(Subcommand)]
+enum ToolCommand {
+    #[command(
+        about = "Install a package in its own environment and link its scripts into the tool bin directory."
+    )]
+    Install(ToolInstallArgs),
+
+    #[command(about = "List the installed tools and their scripts.")]
+    List,
+
+    #[command(about = "Upgrade installed tools to their latest version.")]
+    Upgrade(ToolUpgradeArgs),
+
+    #[command(about = "Remove installed tools and their scripts.")]
+    Uninstall(ToolUninstallArgs),
+
+    #[command(
+        about = "Run a tool once from a temporary environment, or from its installed environment."
+    )]
+    Run(ToolRunArgs),
+}
+
+#[derive(Parser)]
+struct ToolInstallArgs {
+    #[arg(value_name = "PACKAGE")]
+    package: String,
+}
+
+#[derive(Parser)]
+struct ToolUpgradeArgs {
+    #[arg(value_name = "TOOLS")]
+    tools: Vec<String>,
+
+    #[arg(
+        long = "all",
+        conflicts_with = "tools",
+        help = "Upgrade every installed tool."
+    )]
+    all: bool,
+}
+
+#[derive(Parser)]
+struct ToolUninstallArgs {
+    #[arg(value_name = "TOOLS", required = true)]
+    tools: Vec<String>,
+}
+
+#[derive(Parser)]
+struct ToolRunArgs {
+    #[arg(
+        long = "from",
+        value_name = "PACKAGE",
+        help = "Package providing the command, defaults to the command name."
+    )]
+    from: Option<String>,
+
+    #[arg(
+        value_name = "COMMAND",
+        required = true,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    command: Vec<String>,
+}
+
+#[derive(Subcommand)]
+enum ShimsCommand {
+    #[command(about = "Install python, python3, pip and pip3 shims in the ezpy shims directory.")]
+    Install,
+
+    #[command(about = "Remove the shims directory.")]
+    Uninstall,
 }
 
 #[derive(Parser)]
@@ -93,12 +679,46 @@ struct EnvArgs {
     command: Option<EnvCommand>,
     #[arg(required = false)]
     version: Option<VersionString>,
+
+    #[arg(
+        long = "use-stdlib-venv",
+        global = true,
+        help = "Create the environment with `python -m venv` instead of natively."
+    )]
+    use_stdlib_venv: bool,
+
+    #[arg(
+        long = "without-pip",
+        global = true,
+        help = "Don't seed pip into the new environment."
+    )]
+    without_pip: bool,
+
+    #[arg(
+        long = "install-missing",
+        global = true,
+        help = "Install the requested Python version (or the best match for requires-python) if it's missing."
+    )]
+    install_missing: bool,
 }
 
 #[derive(Parser)]
 struct PinArgs {
-    #[arg(value_name = "VERSION")]
-    version: VersionString,
+    #[arg(value_name = "VERSION", required_unless_present = "show")]
+    version: Option<VersionString>,
+
+    #[arg(
+        long = "global",
+        help = "Pin the version for every project instead of writing .python-version."
+    )]
+    global: bool,
+
+    #[arg(
+        long = "show",
+        conflicts_with_all = ["version", "global"],
+        help = "Show the effective Python version and where it comes from."
+    )]
+    show: bool,
 }
 
 #[derive(Subcommand)]
@@ -114,7 +734,7 @@ enum EnvCommand {
     Activate(ActivateArgs),
 
     #[command(about = "Get the deactivate command to run in your shell.")]
-    Deactivate,
+    Deactivate(DeactivateArgs),
 
     #[command(about = "Show the path to the current virtual environment.")]
     Path(ActivateArgs),
@@ -122,8 +742,38 @@ enum EnvCommand {
     #[command(about = "Delete a global virtual environment.")]
     Delete(GlobalArgs),
 
-    #[command(about = "List all available global virtual environments.")]
-    List,
+    #[command(about = "List the nearest local and all global virtual environments.")]
+    List(ListArgs),
+
+    #[command(about = "Detect environments whose base interpreter was removed or replaced.")]
+    Doctor,
+
+    #[command(
+        about = "Recreate a global virtual environment on another Python version (set with --python), keeping its packages."
+    )]
+    Rebase(GlobalArgs),
+
+    #[command(about = "Copy a global virtual environment under a new name.")]
+    Clone(CloneArgs),
+
+    #[command(about = "Rename a global virtual environment.")]
+    Rename(CloneArgs),
+
+    #[command(
+        about = "Print the Python version and frozen packages of a global virtual environment as TOML."
+    )]
+    Export(GlobalArgs),
+
+    #[command(
+        about = "Create a global virtual environment from a file written by `ezpy env export`."
+    )]
+    Import(ImportArgs),
+}
+
+#[derive(Parser)]
+struct ListArgs {
+    #[arg(long = "json", help = "Print the environments as JSON.")]
+    json: bool,
 }
 
 #[derive(Parser)]
@@ -132,22 +782,103 @@ struct GlobalArgs {
     global: String,
 }
 
+#[derive(Parser)]
+struct CloneArgs {
+    #[arg(value_name = "SOURCE")]
+    source: String,
+
+    #[arg(value_name = "DESTINATION")]
+    destination: String,
+}
+
+#[derive(Parser)]
+struct ImportArgs {
+    #[arg(value_name = "FILE")]
+    file: PathBuf,
+
+    #[arg(
+        long = "name",
+        value_name = "NAME",
+        help = "Name of the new environment, defaults to the exported name."
+    )]
+    name: Option<String>,
+}
+
 #[derive(Parser)]
 struct ActivateArgs {
     #[arg(value_name = "NAME")]
     name: Option<String>,
+
+    #[arg(
+        long = "eval",
+        value_name = "SHELL",
+        help = "Print shell code to evaluate, e.g. `eval \"$(ezpy env activate --eval bash)\"`."
+    )]
+    eval: Option<shell::Shell>,
+}
+
+#[derive(Parser)]
+struct DeactivateArgs {
+    #[arg(
+        long = "eval",
+        value_name = "SHELL",
+        help = "Print shell code to evaluate."
+    )]
+    eval: Option<shell::Shell>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    if let Some(name) = shim::invoked_as() {
+        return shim::run_shim(&name).await;
+    }
+
     let cli = EzpyCLI::parse();
+    VERBOSE.store(cli.verbose, Ordering::Relaxed);
+    QUIET.store(cli.quiet, Ordering::Relaxed);
 
     if let Some(command) = cli.command {
         match command {
-            EzpyCommands::Install(args) => handle_install(args).await?,
-            EzpyCommands::Env(env_args) => handle_env(env_args).await?,
-            EzpyCommands::Pin(args) => handle_pin(args).await?,
-            EzpyCommands::List => handle_list().await?,
+            EzpyCommands::Install(args) => handle_install(args, cli.python).await?,
+            EzpyCommands::Env(env_args) => handle_env(env_args, cli.python).await?,
+            EzpyCommands::Pin(args) => handle_pin(args, cli.python).await?,
+            EzpyCommands::Add(args) => handle_add(args, cli.python).await?,
+            EzpyCommands::Remove(args) => handle_remove(args, cli.python).await?,
+            EzpyCommands::Uninstall(args) => handle_uninstall(args, cli.python).await?,
+            EzpyCommands::Upgrade(args) => handle_upgrade(args, cli.python).await?,
+            EzpyCommands::Freeze(args) => handle_freeze(args, cli.python).await?,
+            EzpyCommands::List(args) => handle_list(args, cli.python).await?,
+            EzpyCommands::Run(args) => handle_run(args, cli.python).await?,
+            EzpyCommands::Script(args) => {
+                no_python_effect(&cli.python, "script")?;
+                handle_script(args).await?
+            }
+            EzpyCommands::Shell(args) => handle_shell(args, cli.python).await?,
+            EzpyCommands::Hook(args) => {
+                no_python_effect(&cli.python, "hook")?;
+                handle_hook(args).await?
+            }
+            EzpyCommands::HookEnv(args) => {
+                no_python_effect(&cli.python, "hook-env")?;
+                handle_hook_env(args).await?
+            }
+            EzpyCommands::Shims(args) => {
+                no_python_effect(&cli.python, "shims")?;
+                handle_shims(args).await?
+            }
+            EzpyCommands::Bundle(args) => {
+                no_python_effect(&cli.python, "bundle")?;
+                handle_bundle(args).await?
+            }
+            EzpyCommands::Build(args) => handle_build(args, cli.python).await?,
+            EzpyCommands::Publish(args) => {
+                no_python_effect(&cli.python, "publish")?;
+                handle_publish(args).await?
+            }
+            EzpyCommands::Requirements(args) => handle_requirements(args, cli.python).await?,
+            EzpyCommands::Lock(args) => handle_lock(args, cli.python).await?,
+            EzpyCommands::Sync(args) => handle_sync(args, cli.python).await?,
+            EzpyCommands::Tool(args) => handle_tool(args, cli.python).await?,
         }
     } else {
         handle_no_command().await?;
@@ -161,7 +892,37 @@ async fn handle_no_command() -> Result<()> {
     Ok(())
 }
 
-async fn handle_install(args: InstallArgs) -> Result<()> {
+/// Fails when `--python` was given to a command that doesn't pick an interpreter.
+fn no_python_effect(python: &Option<VersionString>, command: &str) -> Result<()> {
+    if python.is_some() {
+        eyre::bail!("--python has no effect on `ezpy {}`", command);
+    }
+
+    Ok(())
+}
+
+/// Fails when `--python` was given and the environment the command works in, the global one
+/// named `global` or the nearest one, uses another version.
+async fn check_env_python(python: Option<VersionString>, global: Option<String>) -> Result<()> {
+    let Some(python) = python else {
+        return Ok(());
+    };
+
+    let env = venv::resolve_env(global).await?;
+    venv::check_env_python(&env, &python).await
+}
+
+async fn handle_install(args: InstallArgs, python: Option<VersionString>) -> Result<()> {
+    if args.requirements.is_none() && args.packages.first().is_some_and(|p| p == "python") {
+        no_python_effect(&python, "install python")?;
+    } else {
+        check_env_python(python, args.global.clone()).await?;
+    }
+
+    if !args.editable.is_empty() {
+        install_editable(&args).await?;
+    }
+
     if let Some(requirements_file) = args.requirements {
         install_from_requirements(&requirements_file, args.global).await?;
     } else if !args.packages.is_empty() {
@@ -181,24 +942,74 @@ async fn handle_install(args: InstallArgs) -> Result<()> {
     Ok(())
 }
 
-async fn handle_env(env_args: EnvArgs) -> Result<()> {
-    if env_args.command.is_none() {
-        create_local_env(env_args.version).await?;
-    } else {
-        match env_args.command.unwrap() {
-            EnvCommand::Global(args) => create_global_env(env_args.version, args).await?,
+async fn handle_env(env_args: EnvArgs, python: Option<VersionString>) -> Result<()> {
+    let version = env_args.version.or(python.clone());
+    let options = venv::EnvOptions {
+        use_stdlib_venv: env_args.use_stdlib_venv,
+        without_pip: env_args.without_pip,
+        install_missing: env_args.install_missing,
+    };
+
+    if let Some(command) = env_args.command {
+        if !matches!(
+            command,
+            EnvCommand::Global(_) | EnvCommand::Rebase(_) | EnvCommand::Import(_)
+        ) {
+            no_python_effect(&python, "env")?;
+        }
+
+        match command {
+            EnvCommand::Global(args) => create_global_env(version, args, options).await?,
             EnvCommand::Activate(args) => activate_env(args).await?,
-            EnvCommand::Deactivate => deactivate_env().await?,
+            EnvCommand::Deactivate(args) => deactivate_env(args).await?,
             EnvCommand::Delete(args) => delete_env(args).await?,
-            EnvCommand::List => list_envs().await?,
+            EnvCommand::List(args) => list_envs(args).await?,
+            EnvCommand::Doctor => doctor_envs().await?,
+            EnvCommand::Rebase(args) => rebase_env(version, args, options).await?,
+            EnvCommand::Clone(args) => clone_env(args).await?,
+            EnvCommand::Rename(args) => rename_env(args).await?,
+            EnvCommand::Export(args) => export_env(args).await?,
+            EnvCommand::Import(args) => import_env(args, options).await?,
             EnvCommand::Path(args) => show_env_path(args).await?,
         }
+    } else {
+        create_local_env(version, options).await?;
     }
 
     Ok(())
 }
 
-async fn handle_list() -> Result<()> {
+async fn handle_add(args: AddArgs, python: Option<VersionString>) -> Result<()> {
+    install::add_packages(args.packages, args.group, python).await
+}
+
+async fn handle_remove(args: AddArgs, python: Option<VersionString>) -> Result<()> {
+    check_env_python(python, None).await?;
+    install::remove_packages(args.packages, args.group).await
+}
+
+async fn handle_uninstall(args: UninstallArgs, python: Option<VersionString>) -> Result<()> {
+    check_env_python(python, args.global.clone()).await?;
+    install::uninstall_packages(args.packages, args.global).await
+}
+
+async fn handle_upgrade(args: UpgradeArgs, python: Option<VersionString>) -> Result<()> {
+    check_env_python(python, args.global.clone()).await?;
+    install::upgrade_packages(args.packages, args.all, args.outdated_only, args.global).await
+}
+
+async fn handle_freeze(args: FreezeArgs, python: Option<VersionString>) -> Result<()> {
+    check_env_python(python, args.global.clone()).await?;
+    install::freeze(args.global, args.all).await
+}
+
+async fn handle_list(args: PackagesArgs, python: Option<VersionString>) -> Result<()> {
+    if args.packages {
+        check_env_python(python, args.global.clone()).await?;
+        return install::list_packages(args.global).await;
+    }
+    no_python_effect(&python, "list")?;
+
     let packages = indygreg::package::available_packages().await?;
 
     for key in packages.keys() {
@@ -208,20 +1019,120 @@ async fn handle_list() -> Result<()> {
     Ok(())
 }
 
-async fn create_local_env(version: Option<VersionString>) -> Result<()> {
-    venv::create_local_env(version).await
+async fn handle_run(args: RunArgs, python: Option<VersionString>) -> Result<()> {
+    if args.global.is_none() && script::is_script(&args.command[0]).await? {
+        return script::run_script(Path::new(&args.command[0]), &args.command[1..], python).await;
+    }
+
+    check_env_python(python, args.global.clone()).await?;
+    run::run(args.command, args.global).await
+}
+
+async fn handle_script(args: ScriptArgs) -> Result<()> {
+    match args.command {
+        ScriptCommand::AddDep(args) => script::add_dependencies(&args.script, args.packages).await,
+    }
+}
+
+async fn handle_shell(args: ShellArgs, python: Option<VersionString>) -> Result<()> {
+    check_env_python(python, args.name.clone()).await?;
+    shell::spawn_shell(args.name, args.shell).await
+}
+
+async fn handle_hook(args: HookArgs) -> Result<()> {
+    hook::print_hook(args.shell)
 }
 
-async fn create_global_env(version: Option<VersionString>, args: GlobalArgs) -> Result<()> {
-    venv::create_global_env(version, args.global).await
+async fn handle_hook_env(args: HookArgs) -> Result<()> {
+    hook::print_hook_env(args.shell)
+}
+
+async fn handle_shims(args: ShimsArgs) -> Result<()> {
+    match args.command {
+        ShimsCommand::Install => shim::install_shims().await,
+        ShimsCommand::Uninstall => shim::uninstall_shims().await,
+    }
+}
+
+async fn handle_build(args: BuildArgs, python: Option<VersionString>) -> Result<()> {
+    // Without either flag, both are built.
+    let targets = build::Targets {
+        sdist: args.sdist || !args.wheel,
+        wheel: args.wheel || !args.sdist,
+    };
+
+    build::build(&args.project, args.output, targets, python).await
+}
+
+async fn handle_publish(args: PublishArgs) -> Result<()> {
+    publish::publish(args.files, args.repository, args.repository_url).await
+}
+
+async fn handle_requirements(args: RequirementsArgs, python: Option<VersionString>) -> Result<()> {
+    match args.command {
+        RequirementsCommand::Check(args) => {
+            requirements::check(&args.file, args.global, python).await
+        }
+    }
+}
+
+async fn handle_lock(args: LockArgs, python: Option<VersionString>) -> Result<()> {
+    lock::lock(python, &args.extras, args.index.sources(true)?).await
+}
+
+async fn handle_sync(args: SyncArgs, python: Option<VersionString>) -> Result<()> {
+    lock::sync(&args.extras, python).await
+}
+
+async fn handle_tool(args: ToolArgs, python: Option<VersionString>) -> Result<()> {
+    match args.command {
+        ToolCommand::Install(args) => tool::install_tool(args.package, python).await,
+        ToolCommand::List => {
+            no_python_effect(&python, "tool list")?;
+            tool::list_tools().await
+        }
+        ToolCommand::Upgrade(args) => {
+            no_python_effect(&python, "tool upgrade")?;
+            tool::upgrade_tools(args.tools, args.all).await
+        }
+        ToolCommand::Uninstall(args) => {
+            no_python_effect(&python, "tool uninstall")?;
+            tool::uninstall_tools(args.tools).await
+        }
+        ToolCommand::Run(args) => tool::run_tool(args.command, args.from, python).await,
+    }
+}
+
+async fn handle_bundle(args: BundleArgs) -> Result<()> {
+    match args.command {
+        BundleCommand::Create(args) => {
+            let output = args
+                .output
+                .unwrap_or_else(|| PathBuf::from(format!("{}.tar.zst", args.env)));
+            bundle::create_bundle(args.env, output).await
+        }
+        BundleCommand::Install(args) => bundle::install_bundle(args.archive, args.dir).await,
+    }
+}
+
+async fn create_local_env(version: Option<VersionString>, options: venv::EnvOptions) -> Result<()> {
+    venv::create_local_env(version, options).await
+}
+
+async fn create_global_env(
+    version: Option<VersionString>,
+    args: GlobalArgs,
+    options: venv::EnvOptions,
+) -> Result<()> {
+    venv::create_global_env(version, args.global, options).await
 }
 
 async fn activate_env(args: ActivateArgs) -> Result<()> {
-    venv::activate_env(args.name).await
+    venv::activate_env(args.name, args.eval).await
 }
 
-async fn deactivate_env() -> Result<()> {
-    venv::deactivate_env().await
+async fn deactivate_env(args: DeactivateArgs) -> Result<()> {
+    venv::deactivate_env(args.eval).await
 }
 
 async fn show_env_path(args: ActivateArgs) -> Result<()> {
@@ -232,12 +1143,43 @@ async fn delete_env(args: GlobalArgs) -> Result<()> {
     venv::delete_global_env(args.global).await
 }
 
-async fn list_envs() -> Result<()> {
-    venv::list_global_envs().await
+async fn list_envs(args: ListArgs) -> Result<()> {
+    venv::list_envs(args.json).await
+}
+
+async fn doctor_envs() -> Result<()> {
+    doctor::doctor().await
+}
+
+async fn rebase_env(
+    version: Option<VersionString>,
+    args: GlobalArgs,
+    options: venv::EnvOptions,
+) -> Result<()> {
+    doctor::rebase_env(args.global, version, options).await
+}
+
+async fn clone_env(args: CloneArgs) -> Result<()> {
+    venv::clone_global_env(args.source, args.destination).await
+}
+
+async fn rename_env(args: CloneArgs) -> Result<()> {
+    venv::rename_global_env(args.source, args.destination).await
+}
+
+async fn export_env(args: GlobalArgs) -> Result<()> {
+    export::export_env(args.global).await
 }
 
-async fn handle_pin(args: PinArgs) -> Result<()> {
-    pin::pin_version(args.version).await
+async fn import_env(args: ImportArgs, options: venv::EnvOptions) -> Result<()> {
+    export::import_env(&args.file, args.name, options).await
+}
+
+async fn handle_pin(args: PinArgs, python: Option<VersionString>) -> Result<()> {
+    match args.version {
+        Some(version) if !args.show => pin::pin_version(version, args.global).await,
+        _ => pin::show_pinned_version(python).await,
+    }
 }
 
 async fn install_python_version(version: VersionString) -> Result<()> {
@@ -254,9 +1196,24 @@ async fn install_python_version(version: VersionString) -> Result<()> {
 }
 
 async fn install_packages(args: InstallArgs) -> Result<()> {
+    if args.index.is_set() || args.packages.iter().all(|p| install::is_wheel_path(p)) {
+        let sources = args.index.sources(false)?;
+        return install::install_wheels(args.packages, sources, args.global).await;
+    }
+
     install::install_packages(args.packages, args.global).await
 }
 
+async fn install_editable(args: &InstallArgs) -> Result<()> {
+    let sources = if args.index.is_set() {
+        Some(args.index.clone().sources(false)?)
+    } else {
+        None
+    };
+
+    editable::install_editable(args.editable.clone(), sources, args.global.clone()).await
+}
+
 async fn install_from_requirements(requirements_file: &str, global: Option<String>) -> Result<()> {
     install::install_from_requirements(requirements_file, global).await
 }
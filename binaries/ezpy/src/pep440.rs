@@ -0,0 +1,640 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// A Python package or interpreter version, as described by PEP 440.
+#[derive(Debug, Clone, Default)]
+pub struct Version {
+    pub epoch: u64,
+    pub release: Vec<u64>,
+
+    /// Pre-release phase (`a`, `b` or `rc`) and number.
+    pub pre: Option<(String, u64)>,
+    pub post: Option<u64>,
+    pub dev: Option<u64>,
+
+    /// Local version label segments (the part after `+`).
+    pub local: Vec<String>,
+}
+
+impl Version {
+    pub fn is_prerelease(&self) -> bool {
+        self.pre.is_some() || self.dev.is_some()
+    }
+
+    pub fn is_postrelease(&self) -> bool {
+        self.post.is_some()
+    }
+
+    /// The version without its local label.
+    pub fn public(&self) -> Version {
+        Version {
+            local: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// The version reduced to its epoch and release segments.
+    pub fn base(&self) -> Version {
+        Version {
+            epoch: self.epoch,
+            release: self.release.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn trimmed_release(&self) -> &[u64] {
+        let len = self
+            .release
+            .iter()
+            .rposition(|part| *part != 0)
+            .map_or(0, |i| i + 1);
+
+        &self.release[..len]
+    }
+
+    fn pre_key(&self) -> (i8, u64) {
+        match (&self.pre, self.post, self.dev) {
+            // A bare dev release sorts before every pre-release of the same version.
+            (None, None, Some(_)) => (-1, 0),
+            (None, _, _) => (4, 0),
+            (Some((phase, n)), _, _) => match phase.as_str() {
+                "a" => (0, *n),
+                "b" => (1, *n),
+                _ => (2, *n),
+            },
+        }
+    }
+
+    fn post_key(&self) -> (i8, u64) {
+        self.post.map_or((-1, 0), |n| (0, n))
+    }
+
+    fn dev_key(&self) -> (i8, u64) {
+        self.dev.map_or((1, 0), |n| (0, n))
+    }
+
+    fn cmp_local(&self, other: &Version) -> Ordering {
+        for (a, b) in self.local.iter().zip(other.local.iter()) {
+            let ordering = match (a.parse::<u64>(), b.parse::<u64>()) {
+                (Ok(a), Ok(b)) => a.cmp(&b),
+                (Ok(_), Err(_)) => Ordering::Greater,
+                (Err(_), Ok(_)) => Ordering::Less,
+                (Err(_), Err(_)) => a.cmp(b),
+            };
+
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+
+        self.local.len().cmp(&other.local.len())
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.epoch
+            .cmp(&other.epoch)
+            .then_with(|| self.trimmed_release().cmp(other.trimmed_release()))
+            .then_with(|| self.pre_key().cmp(&other.pre_key()))
+            .then_with(|| self.post_key().cmp(&other.post_key()))
+            .then_with(|| self.dev_key().cmp(&other.dev_key()))
+            .then_with(|| self.cmp_local(other))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Version {}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.epoch != 0 {
+            write!(f, "{}!", self.epoch)?;
+        }
+
+        let release = self
+            .release
+            .iter()
+            .map(|part| part.to_string())
+            .collect::<Vec<_>>()
+            .join(".");
+        write!(f, "{}", release)?;
+
+        if let Some((phase, n)) = &self.pre {
+            write!(f, "{}{}", phase, n)?;
+        }
+        if let Some(n) = self.post {
+            write!(f, ".post{}", n)?;
+        }
+        if let Some(n) = self.dev {
+            write!(f, ".dev{}", n)?;
+        }
+        if !self.local.is_empty() {
+            write!(f, "+{}", self.local.join("."))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Version {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let input = s.trim().to_lowercase();
+        let mut rest = input.strip_prefix('v').unwrap_or(&input);
+
+        let mut version = Version::default();
+
+        if let Some((epoch, tail)) = rest.split_once('!') {
+            version.epoch = epoch
+                .parse()
+                .map_err(|_| eyre::eyre!("Invalid epoch in version '{}'", s))?;
+            rest = tail;
+        }
+
+        if let Some((public, local)) = rest.split_once('+') {
+            version.local = local
+                .split(['.', '-', '_'])
+                .map(|segment| segment.to_string())
+                .collect();
+
+            if version.local.iter().any(|segment| {
+                segment.is_empty() || !segment.chars().all(|c| c.is_ascii_alphanumeric())
+            }) {
+                eyre::bail!("Invalid local label in version '{}'", s);
+            }
+
+            rest = public;
+        }
+
+        let mut chars = Scanner::new(rest);
+
+        loop {
+            let part = chars
+                .number()
+                .ok_or_else(|| eyre::eyre!("Invalid version '{}'", s))?;
+            version.release.push(part);
+
+            if chars.peek() == Some('.') && chars.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        let save = chars.position();
+        chars.separator();
+        if let Some(phase) = chars.word(&["alpha", "beta", "preview", "pre", "rc", "a", "b", "c"]) {
+            let phase = match phase {
+                "alpha" | "a" => "a",
+                "beta" | "b" => "b",
+                _ => "rc",
+            };
+            chars.separator();
+            version.pre = Some((phase.to_string(), chars.number().unwrap_or(0)));
+        } else {
+            chars.reset(save);
+        }
+
+        let save = chars.position();
+        if chars.peek() == Some('-') && chars.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) {
+            chars.next();
+            version.post = chars.number();
+        } else {
+            chars.separator();
+            if chars.word(&["post", "rev", "r"]).is_some() {
+                chars.separator();
+                version.post = Some(chars.number().unwrap_or(0));
+            } else {
+                chars.reset(save);
+            }
+        }
+
+        let save = chars.position();
+        chars.separator();
+        if chars.word(&["dev"]).is_some() {
+            chars.separator();
+            version.dev = Some(chars.number().unwrap_or(0));
+        } else {
+            chars.reset(save);
+        }
+
+        if !chars.is_empty() {
+            eyre::bail!("Invalid version '{}'", s);
+        }
+
+        Ok(version)
+    }
+}
+
+struct Scanner<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Scanner { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rest().is_empty()
+    }
+
+    fn position(&self) -> usize {
+        self.position
+    }
+
+    fn reset(&mut self, position: usize) {
+        self.position = position;
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn separator(&mut self) {
+        if matches!(self.peek(), Some('.' | '-' | '_')) {
+            self.next();
+        }
+    }
+
+    fn number(&mut self) -> Option<u64> {
+        let digits = self
+            .rest()
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if digits == 0 {
+            return None;
+        }
+
+        let number = self.rest()[..digits].parse().ok();
+        self.position += digits;
+        number
+    }
+
+    fn word(&mut self, words: &[&'static str]) -> Option<&'static str> {
+        let word = words.iter().find(|word| self.rest().starts_with(**word))?;
+        self.position += word.len();
+        Some(word)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Compatible,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    Arbitrary,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            Operator::Compatible => "~=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::LessEqual => "<=",
+            Operator::GreaterEqual => ">=",
+            Operator::Less => "<",
+            Operator::Greater => ">",
+            Operator::Arbitrary => "===",
+        };
+
+        write!(f, "{}", operator)
+    }
+}
+
+/// A single version clause such as `>=3.9` or `==1.2.*`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Specifier {
+    pub operator: Operator,
+    pub version: Version,
+
+    /// Whether the version ends with `.*` (only valid with `==` and `!=`).
+    pub wildcard: bool,
+
+    /// The raw version text, used by the arbitrary equality operator.
+    pub raw: String,
+}
+
+impl Specifier {
+    pub fn contains(&self, version: &Version) -> bool {
+        match self.operator {
+            Operator::Arbitrary => version.to_string() == self.raw,
+            Operator::Equal if self.wildcard => self.matches_prefix(version),
+            Operator::NotEqual if self.wildcard => !self.matches_prefix(version),
+            Operator::Equal => self.matches_exactly(version),
+            Operator::NotEqual => !self.matches_exactly(version),
+            Operator::LessEqual => version.public() <= self.version,
+            Operator::GreaterEqual => version.public() >= self.version,
+            Operator::Less => {
+                version.public() < self.version
+                    && !(!self.version.is_prerelease()
+                        && version.is_prerelease()
+                        && version.base() == self.version.base())
+            }
+            Operator::Greater => {
+                version.public() > self.version
+                    && !(!self.version.is_postrelease()
+                        && version.is_postrelease()
+                        && version.base() == self.version.base())
+            }
+            Operator::Compatible => {
+                let mut prefix = self.version.base();
+                prefix.release.pop();
+
+                version.public() >= self.version
+                    && Specifier {
+                        operator: Operator::Equal,
+                        version: prefix,
+                        wildcard: true,
+                        raw: String::new(),
+                    }
+                    .matches_prefix(version)
+            }
+        }
+    }
+
+    fn matches_exactly(&self, version: &Version) -> bool {
+        if self.version.local.is_empty() {
+            version.public() == self.version
+        } else {
+            *version == self.version
+        }
+    }
+
+    fn matches_prefix(&self, version: &Version) -> bool {
+        let prefix = &self.version.release;
+        let mut release = version.release.clone();
+        if release.len() < prefix.len() {
+            release.resize(prefix.len(), 0);
+        }
+
+        version.epoch == self.version.epoch && release[..prefix.len()] == prefix[..]
+    }
+}
+
+impl fmt::Display for Specifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.operator, self.raw)
+    }
+}
+
+impl FromStr for Specifier {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let (operator, rest) = [
+            ("===", Operator::Arbitrary),
+            ("~=", Operator::Compatible),
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessEqual),
+            (">=", Operator::GreaterEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+        ]
+        .iter()
+        .find_map(|(prefix, operator)| s.strip_prefix(prefix).map(|rest| (*operator, rest.trim())))
+        .ok_or_else(|| eyre::eyre!("Invalid version specifier '{}'", s))?;
+
+        if rest.is_empty() {
+            eyre::bail!("Missing version in specifier '{}'", s);
+        }
+
+        if operator == Operator::Arbitrary {
+            return Ok(Specifier {
+                operator,
+                version: rest.parse().unwrap_or_default(),
+                wildcard: false,
+                raw: rest.to_string(),
+            });
+        }
+
+        let (text, wildcard) = match rest.strip_suffix(".*") {
+            Some(text) => (text, true),
+            None => (rest, false),
+        };
+
+        if wildcard && !matches!(operator, Operator::Equal | Operator::NotEqual) {
+            eyre::bail!("Wildcards are only allowed with '==' and '!=': '{}'", s);
+        }
+
+        let version: Version = text
+            .parse()
+            .map_err(|_| eyre::eyre!("Invalid version in specifier '{}'", s))?;
+
+        if operator == Operator::Compatible && version.release.len() < 2 {
+            eyre::bail!("'~=' requires at least two release segments: '{}'", s);
+        }
+
+        Ok(Specifier {
+            operator,
+            version,
+            wildcard,
+            raw: rest.to_string(),
+        })
+    }
+}
+
+/// A comma separated list of specifiers, all of which must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpecifierSet(pub Vec<Specifier>);
+
+impl SpecifierSet {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, version: &Version) -> bool {
+        self.0.iter().all(|specifier| specifier.contains(version))
+    }
+
+    /// Whether pre-releases were explicitly requested by one of the specifiers.
+    pub fn allows_prereleases(&self) -> bool {
+        self.0.iter().any(|specifier| {
+            specifier.operator != Operator::NotEqual && specifier.version.is_prerelease()
+        })
+    }
+}
+
+impl fmt::Display for SpecifierSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let specifiers = self
+            .0
+            .iter()
+            .map(|specifier| specifier.to_string())
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", specifiers.join(","))
+    }
+}
+
+impl FromStr for SpecifierSet {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let specifiers = s
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(str::parse)
+            .collect::<eyre::Result<Vec<_>>>()?;
+
+        Ok(SpecifierSet(specifiers))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(version: &str) -> Version {
+        version
+            .parse()
+            .unwrap_or_else(|e| panic!("{}: {}", version, e))
+    }
+
+    fn contains(specifiers: &str, candidate: &str) -> bool {
+        specifiers
+            .parse::<SpecifierSet>()
+            .unwrap_or_else(|e| panic!("{}: {}", specifiers, e))
+            .contains(&version(candidate))
+    }
+
+    #[test]
+    fn versions_sort_by_phase() {
+        let ordered = [
+            "1.0.dev1",
+            "1.0a1.dev1",
+            "1.0a1",
+            "1.0a2",
+            "1.0b1",
+            "1.0rc1",
+            "1.0",
+            "1.0+local",
+            "1.0.post1.dev1",
+            "1.0.post1",
+            "1.0.1",
+            "1!0.1",
+        ];
+
+        for pair in ordered.windows(2) {
+            assert!(
+                version(pair[0]) < version(pair[1]),
+                "{} < {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn versions_normalize() {
+        assert_eq!(version("1.0"), version("1.0.0"));
+        assert_eq!(version("1.0alpha1"), version("1.0a1"));
+        assert_eq!(version("1.0-post2"), version("1.0.post2"));
+        assert_eq!(version("v1.0RC1"), version("1.0rc1"));
+        assert_eq!(version("1!2.0").epoch, 1);
+        assert_eq!(version("1.0+Ubuntu.1").local, ["ubuntu", "1"]);
+        assert!(version("1.0+abc") > version("1.0"));
+        assert!(version("1.0+abc.5") > version("1.0+abc.4"));
+        assert!("1.0.x".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn wildcard_equality() {
+        assert!(contains("==1.2.*", "1.2"));
+        assert!(contains("==1.2.*", "1.2.9"));
+        assert!(contains("==1.2.*", "1.2.0a1"));
+        assert!(!contains("==1.2.*", "1.3"));
+        assert!(!contains("==1.2.*", "1!1.2"));
+        assert!(contains("!=1.2.*", "1.3"));
+        assert!(!contains("!=1.2.*", "1.2.5"));
+        assert!("~=1.2.*".parse::<SpecifierSet>().is_err());
+    }
+
+    #[test]
+    fn exact_equality_ignores_local_unless_given() {
+        assert!(contains("==1.0", "1.0+local"));
+        assert!(contains("==1.0", "1.0.0"));
+        assert!(!contains("==1.0+other", "1.0+local"));
+        assert!(contains("!=1.0", "1.0.post1"));
+        assert!(!contains("!=1.0", "1.0+local"));
+        assert!(contains("===1.0+local", "1.0+local"));
+    }
+
+    #[test]
+    fn compatible_release() {
+        assert!(contains("~=2.2", "2.2"));
+        assert!(contains("~=2.2", "2.9"));
+        assert!(!contains("~=2.2", "3.0"));
+        assert!(contains("~=1.4.5", "1.4.9"));
+        assert!(!contains("~=1.4.5", "1.5.0"));
+        assert!(!contains("~=1.4.5", "1.4.4"));
+        assert!(contains("~=2.2.post3", "2.2.post3"));
+        assert!("~=1".parse::<SpecifierSet>().is_err());
+    }
+
+    #[test]
+    fn exclusive_comparisons_exclude_pre_and_post_releases_of_the_bound() {
+        assert!(!contains("<3.0", "3.0a1"));
+        assert!(!contains("<3.0", "3.0.dev0"));
+        assert!(contains("<3.0", "2.9"));
+        assert!(contains("<3.0rc1", "3.0b1"));
+        assert!(!contains(">1.7", "1.7.post1"));
+        assert!(contains(">1.7", "1.7.1"));
+        assert!(contains(">1.7.post2", "1.7.post3"));
+        assert!(!contains(">1.7", "1.7+local"));
+    }
+
+    #[test]
+    fn specifier_sets() {
+        let set: SpecifierSet = ">=1.0, !=1.3.*, <2".parse().unwrap();
+        assert_eq!(set.to_string(), ">=1.0,!=1.3.*,<2");
+        assert!(set.contains(&version("1.2")));
+        assert!(!set.contains(&version("1.3.1")));
+        assert!(!set.contains(&version("2.0")));
+        assert!(!set.allows_prereleases());
+        assert!(">=1.0b1"
+            .parse::<SpecifierSet>()
+            .unwrap()
+            .allows_prereleases());
+        assert!("".parse::<SpecifierSet>().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1016 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::pep440::{Specifier, SpecifierSet, Version};
+
+/// A dependency specification such as `requests[socks]>=2.31; python_version >= "3.8"`, as
+/// described by PEP 508.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub name: String,
+    pub extras: Vec<String>,
+    pub specifiers: SpecifierSet,
+
+    /// The direct reference following `@`, exclusive with the specifiers.
+    pub url: Option<String>,
+
+    /// The environment marker following `;`.
+    pub marker: Option<MarkerTree>,
+}
+
+impl Requirement {
+    /// The name normalized as described by PEP 503, used to compare distributions.
+    pub fn normalized_name(&self) -> String {
+        normalize_name(&self.name)
+    }
+
+    /// Whether the requirement applies in `env` when the dependent was requested with `extras`.
+    pub fn evaluate(&self, env: &MarkerEnvironment, extras: &[String]) -> bool {
+        self.marker
+            .as_ref()
+            .is_none_or(|marker| marker.evaluate(env, extras))
+    }
+}
+
+impl fmt::Display for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if !self.extras.is_empty() {
+            write!(f, "[{}]", self.extras.join(","))?;
+        }
+        write!(f, "{}", self.specifiers)?;
+        if let Some(url) = &self.url {
+            // The marker must be separated from the URL by whitespace.
+            write!(f, " @ {}", url)?;
+            if self.marker.is_some() {
+                write!(f, " ")?;
+            }
+        }
+        if let Some(marker) = &self.marker {
+            write!(f, "; {}", marker)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Requirement {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let requirement = s.trim();
+
+        let end = requirement
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+            .unwrap_or(requirement.len());
+        let name = &requirement[..end];
+        if name.is_empty() {
+            eyre::bail!("Invalid requirement '{}', expected a package name", s);
+        }
+        if !is_valid_name(name) {
+            eyre::bail!(
+                "Invalid requirement '{}', names must start and end with a letter or digit",
+                s
+            );
+        }
+
+        let mut rest = requirement[end..].trim_start();
+
+        let mut extras = Vec::new();
+        if let Some(inner) = rest.strip_prefix('[') {
+            let (list, after) = inner
+                .split_once(']')
+                .ok_or_else(|| eyre::eyre!("Invalid requirement '{}', unclosed extras", s))?;
+            for extra in list.split(',').map(str::trim) {
+                if extra.is_empty() && list.trim().is_empty() {
+                    continue;
+                }
+                if !is_valid_name(extra) {
+                    eyre::bail!("Invalid requirement '{}', invalid extra '{}'", s, extra);
+                }
+                extras.push(extra.to_string());
+            }
+            rest = after.trim_start();
+        }
+
+        if let Some(reference) = rest.strip_prefix('@') {
+            let reference = reference.trim_start();
+            let end = reference
+                .find(char::is_whitespace)
+                .unwrap_or(reference.len());
+            let url = &reference[..end];
+            if url.is_empty() {
+                eyre::bail!("Invalid requirement '{}', expected a URL after '@'", s);
+            }
+
+            let after = reference[end..].trim_start();
+            let marker = match after.strip_prefix(';') {
+                Some(marker) => parse_marker(marker)?,
+                None if after.is_empty() => None,
+                None => eyre::bail!(
+                    "Invalid requirement '{}', unexpected '{}' after the URL",
+                    s,
+                    after
+                ),
+            };
+
+            return Ok(Requirement {
+                name: name.to_string(),
+                extras,
+                specifiers: SpecifierSet::default(),
+                url: Some(url.to_string()),
+                marker,
+            });
+        }
+
+        let (rest, marker) = match rest.split_once(';') {
+            Some((rest, marker)) => (rest.trim(), parse_marker(marker)?),
+            None => (rest, None),
+        };
+
+        // Older metadata wraps the specifiers in parentheses.
+        let rest = rest
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .unwrap_or(rest);
+
+        Ok(Requirement {
+            name: name.to_string(),
+            extras,
+            specifiers: rest
+                .parse()
+                .map_err(|e| eyre::eyre!("Invalid requirement '{}': {}", s, e))?,
+            url: None,
+            marker,
+        })
+    }
+}
+
+fn parse_marker(marker: &str) -> eyre::Result<Option<MarkerTree>> {
+    let marker = marker.trim();
+    if marker.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(marker.parse()?))
+}
+
+/// Whether `name` is a valid distribution or extra name: letters, digits, `-`, `_` and `.`,
+/// starting and ending with a letter or digit.
+fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Normalizes a distribution name as described by PEP 503.
+pub fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+
+    normalized
+}
+
+/// Marker variables describing the platform rather than the interpreter.
+const PLATFORM_VARIABLES: &[&str] = &[
+    "os_name",
+    "sys_platform",
+    "platform_system",
+    "platform_machine",
+    "platform_release",
+    "platform_version",
+];
+
+/// The values of the marker variables for a target interpreter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkerEnvironment(pub HashMap<String, String>);
+
+impl MarkerEnvironment {
+    /// Describes CPython `version` running on the current platform.
+    pub fn current(version: &str) -> Self {
+        let (os_name, sys_platform, platform_system) = match std::env::consts::OS {
+            "windows" => ("nt", "win32", "Windows"),
+            "macos" => ("posix", "darwin", "Darwin"),
+            "linux" => ("posix", "linux", "Linux"),
+            other => ("posix", other, other),
+        };
+
+        let platform_machine = match (std::env::consts::OS, std::env::consts::ARCH) {
+            ("windows", "x86_64") => "AMD64",
+            ("windows", "aarch64") => "ARM64",
+            ("macos", "aarch64") => "arm64",
+            (_, arch) => arch,
+        };
+
+        let values = [
+            ("os_name", os_name),
+            ("sys_platform", sys_platform),
+            ("platform_system", platform_system),
+            ("platform_machine", platform_machine),
+            ("platform_release", ""),
+            ("platform_version", ""),
+            ("platform_python_implementation", "CPython"),
+            ("implementation_name", "cpython"),
+            ("implementation_version", version),
+            ("python_full_version", version),
+            ("python_version", &crate::pyvenv::short_version(version)),
+        ];
+
+        MarkerEnvironment(
+            values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+}
+
+/// Prints the marker variables of the running interpreter as JSON.
+const INTERPRETER_INFO_SCRIPT: &str = r#"
+import json, os, platform, sys
+
+def format_version(info):
+    version = "{0.major}.{0.minor}.{0.micro}".format(info)
+    if info.releaselevel != "final":
+        version += info.releaselevel[0] + str(info.serial)
+    return version
+
+print(json.dumps({
+    "os_name": os.name,
+    "sys_platform": sys.platform,
+    "platform_system": platform.system(),
+    "platform_machine": platform.machine(),
+    "platform_release": platform.release(),
+    "platform_version": platform.version(),
+    "platform_python_implementation": platform.python_implementation(),
+    "implementation_name": sys.implementation.name,
+    "implementation_version": format_version(sys.implementation.version),
+    "python_full_version": platform.python_version(),
+    "python_version": ".".join(platform.python_version_tuple()[:2]),
+}))
+"#;
+
+/// The marker variables of an interpreter, as reported by the interpreter itself.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct InterpreterInfo {
+    pub os_name: String,
+    pub sys_platform: String,
+    pub platform_system: String,
+    pub platform_machine: String,
+    pub platform_release: String,
+    pub platform_version: String,
+    pub platform_python_implementation: String,
+    pub implementation_name: String,
+    pub implementation_version: String,
+    pub python_full_version: String,
+    pub python_version: String,
+}
+
+impl InterpreterInfo {
+    /// Runs `python` to find out its marker variables.
+    pub async fn query(python: &Path) -> eyre::Result<Self> {
+        let out = tokio::process::Command::new(python)
+            .arg("-c")
+            .arg(INTERPRETER_INFO_SCRIPT)
+            .output()
+            .await
+            .map_err(|e| eyre::eyre!("Failed to run {}: {}", python.display(), e))?;
+
+        if !out.status.success() {
+            eyre::bail!(
+                "Failed to query {}: {}",
+                python.display(),
+                String::from_utf8_lossy(&out.stderr)
+            );
+        }
+
+        serde_json::from_slice(&out.stdout)
+            .map_err(|e| eyre::eyre!("Invalid output from {}: {}", python.display(), e))
+    }
+
+    pub fn markers(&self) -> MarkerEnvironment {
+        let values = [
+            ("os_name", &self.os_name),
+            ("sys_platform", &self.sys_platform),
+            ("platform_system", &self.platform_system),
+            ("platform_machine", &self.platform_machine),
+            ("platform_release", &self.platform_release),
+            ("platform_version", &self.platform_version),
+            (
+                "platform_python_implementation",
+                &self.platform_python_implementation,
+            ),
+            ("implementation_name", &self.implementation_name),
+            ("implementation_version", &self.implementation_version),
+            ("python_full_version", &self.python_full_version),
+            ("python_version", &self.python_version),
+        ];
+
+        MarkerEnvironment(
+            values
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerOperator {
+    Compare(&'static str),
+    In,
+    NotIn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerValue {
+    Variable(String),
+    Literal(String),
+}
+
+/// A parsed environment marker such as `python_version >= "3.8" and sys_platform == "win32"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkerTree {
+    And(Vec<MarkerTree>),
+    Or(Vec<MarkerTree>),
+    Expression {
+        left: MarkerValue,
+        operator: MarkerOperator,
+        right: MarkerValue,
+    },
+}
+
+impl MarkerTree {
+    pub fn evaluate(&self, env: &MarkerEnvironment, extras: &[String]) -> bool {
+        match self {
+            MarkerTree::And(markers) => markers.iter().all(|marker| marker.evaluate(env, extras)),
+            MarkerTree::Or(markers) => markers.iter().any(|marker| marker.evaluate(env, extras)),
+            MarkerTree::Expression {
+                left,
+                operator,
+                right,
+            } => {
+                let variable = match (left, right) {
+                    (MarkerValue::Variable(name), _) | (_, MarkerValue::Variable(name)) => {
+                        Some(name.as_str())
+                    }
+                    _ => None,
+                };
+                if variable == Some("extra") {
+                    let literal = match (left, right) {
+                        (MarkerValue::Literal(value), _) | (_, MarkerValue::Literal(value)) => {
+                            normalize_name(value)
+                        }
+                        _ => return false,
+                    };
+                    let requested = extras.iter().any(|extra| normalize_name(extra) == literal);
+
+                    return match operator {
+                        MarkerOperator::Compare("==") => requested,
+                        MarkerOperator::Compare("!=") => !requested,
+                        _ => false,
+                    };
+                }
+
+                let resolve = |value: &MarkerValue| match value {
+                    MarkerValue::Variable(name) => env.0.get(name).cloned().unwrap_or_default(),
+                    MarkerValue::Literal(value) => value.clone(),
+                };
+
+                compare(&resolve(left), *operator, &resolve(right))
+            }
+        }
+    }
+
+    /// Evaluates everything but the platform variables, returning `None` when the marker
+    /// can't hold in `env`, `Some(None)` when it always holds and otherwise the remaining
+    /// platform conditions.
+    pub fn restrict(
+        &self,
+        env: &MarkerEnvironment,
+        extras: &[String],
+    ) -> Option<Option<MarkerTree>> {
+        match self {
+            MarkerTree::And(markers) => {
+                let mut remaining = Vec::new();
+                for marker in markers {
+                    if let Some(marker) = marker.restrict(env, extras)? {
+                        remaining.push(marker);
+                    }
+                }
+
+                Some(match remaining.len() {
+                    0 => None,
+                    1 => remaining.pop(),
+                    _ => Some(MarkerTree::And(remaining)),
+                })
+            }
+            MarkerTree::Or(markers) => {
+                let mut remaining = Vec::new();
+                for marker in markers {
+                    match marker.restrict(env, extras) {
+                        Some(None) => return Some(None),
+                        Some(Some(marker)) => remaining.push(marker),
+                        None => {}
+                    }
+                }
+
+                match remaining.len() {
+                    0 => None,
+                    1 => Some(remaining.pop()),
+                    _ => Some(Some(MarkerTree::Or(remaining))),
+                }
+            }
+            MarkerTree::Expression { left, right, .. } => {
+                let platform = [left, right].into_iter().any(|value| {
+                    matches!(value, MarkerValue::Variable(name) if PLATFORM_VARIABLES.contains(&name.as_str()))
+                });
+
+                if platform {
+                    Some(Some(self.clone()))
+                } else if self.evaluate(env, extras) {
+                    Some(None)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Compares as PEP 440 versions when both sides are valid ones, falling back to Python's
+/// string comparison otherwise.
+fn compare(left: &str, operator: MarkerOperator, right: &str) -> bool {
+    match operator {
+        MarkerOperator::In => right.contains(left),
+        MarkerOperator::NotIn => !right.contains(left),
+        MarkerOperator::Compare(operator) => {
+            let specifier = format!("{}{}", operator, right).parse::<Specifier>();
+            if let (Ok(specifier), Ok(version)) = (specifier, left.parse::<Version>()) {
+                if operator != "===" {
+                    return specifier.contains(&version);
+                }
+            }
+
+            match operator {
+                "==" | "===" => left == right,
+                "!=" => left != right,
+                "<" => left < right,
+                "<=" => left <= right,
+                ">" => left > right,
+                ">=" => left >= right,
+                _ => false,
+            }
+        }
+    }
+}
+
+impl fmt::Display for MarkerValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkerValue::Variable(name) => write!(f, "{}", name),
+            MarkerValue::Literal(value) if value.contains('"') => write!(f, "'{}'", value),
+            MarkerValue::Literal(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+impl fmt::Display for MarkerTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |f: &mut fmt::Formatter<'_>, markers: &[MarkerTree], separator: &str| {
+            for (index, marker) in markers.iter().enumerate() {
+                if index > 0 {
+                    write!(f, " {} ", separator)?;
+                }
+                match marker {
+                    MarkerTree::Expression { .. } => write!(f, "{}", marker)?,
+                    _ => write!(f, "({})", marker)?,
+                }
+            }
+            Ok(())
+        };
+
+        match self {
+            MarkerTree::And(markers) => join(f, markers, "and"),
+            MarkerTree::Or(markers) => join(f, markers, "or"),
+            MarkerTree::Expression {
+                left,
+                operator,
+                right,
+            } => {
+                let operator = match operator {
+                    MarkerOperator::Compare(operator) => operator,
+                    MarkerOperator::In => "in",
+                    MarkerOperator::NotIn => "not in",
+                };
+                write!(f, "{} {} {}", left, operator, right)
+            }
+        }
+    }
+}
+
+impl FromStr for MarkerTree {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = MarkerParser {
+            input: s,
+            position: 0,
+        };
+
+        let marker = parser.parse_or()?;
+        parser.skip_whitespace();
+        if parser.position != s.len() {
+            eyre::bail!(
+                "Invalid marker '{}', unexpected '{}'",
+                s,
+                &s[parser.position..]
+            );
+        }
+
+        Ok(marker)
+    }
+}
+
+struct MarkerParser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> MarkerParser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Consumes `keyword` when it's followed by a word boundary.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+
+        let rest = self.rest();
+        let matches = rest.starts_with(keyword)
+            && !rest[keyword.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+        if matches {
+            self.position += keyword.len();
+        }
+
+        matches
+    }
+
+    fn parse_or(&mut self) -> eyre::Result<MarkerTree> {
+        let mut markers = vec![self.parse_and()?];
+        while self.keyword("or") {
+            markers.push(self.parse_and()?);
+        }
+
+        Ok(if markers.len() == 1 {
+            markers.remove(0)
+        } else {
+            MarkerTree::Or(markers)
+        })
+    }
+
+    fn parse_and(&mut self) -> eyre::Result<MarkerTree> {
+        let mut markers = vec![self.parse_expression()?];
+        while self.keyword("and") {
+            markers.push(self.parse_expression()?);
+        }
+
+        Ok(if markers.len() == 1 {
+            markers.remove(0)
+        } else {
+            MarkerTree::And(markers)
+        })
+    }
+
+    fn parse_expression(&mut self) -> eyre::Result<MarkerTree> {
+        self.skip_whitespace();
+
+        if self.rest().starts_with('(') {
+            self.position += 1;
+            let marker = self.parse_or()?;
+            self.skip_whitespace();
+            if !self.rest().starts_with(')') {
+                eyre::bail!("Invalid marker '{}', expected ')'", self.input);
+            }
+            self.position += 1;
+
+            return Ok(marker);
+        }
+
+        let left = self.parse_value()?;
+        let operator = self.parse_operator()?;
+        let right = self.parse_value()?;
+
+        Ok(MarkerTree::Expression {
+            left,
+            operator,
+            right,
+        })
+    }
+
+    fn parse_value(&mut self) -> eyre::Result<MarkerValue> {
+        self.skip_whitespace();
+        let rest = self.rest();
+
+        if let Some(quote) = rest.chars().next().filter(|c| *c == '"' || *c == '\'') {
+            let end = rest[1..].find(quote).ok_or_else(|| {
+                eyre::eyre!("Invalid marker '{}', unterminated string", self.input)
+            })?;
+            let value = rest[1..end + 1].to_string();
+            self.position += end + 2;
+
+            return Ok(MarkerValue::Literal(value));
+        }
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+
+        // `os.name` style names are accepted as aliases of their underscore forms.
+        let name = match name {
+            "os.name" => "os_name",
+            "sys.platform" => "sys_platform",
+            "platform.version" => "platform_version",
+            "platform.machine" => "platform_machine",
+            "platform.python_implementation" => "platform_python_implementation",
+            "python_implementation" => "platform_python_implementation",
+            name => name,
+        };
+
+        let known = [
+            "os_name",
+            "sys_platform",
+            "platform_system",
+            "platform_machine",
+            "platform_release",
+            "platform_version",
+            "platform_python_implementation",
+            "implementation_name",
+            "implementation_version",
+            "python_version",
+            "python_full_version",
+            "extra",
+        ];
+        if !known.contains(&name) {
+            eyre::bail!(
+                "Invalid marker '{}', unknown variable '{}'",
+                self.input,
+                &rest[..end]
+            );
+        }
+
+        self.position += end;
+
+        Ok(MarkerValue::Variable(name.to_string()))
+    }
+
+    fn parse_operator(&mut self) -> eyre::Result<MarkerOperator> {
+        self.skip_whitespace();
+
+        if self.keyword("in") {
+            return Ok(MarkerOperator::In);
+        }
+
+        if self.keyword("not") {
+            if self.keyword("in") {
+                return Ok(MarkerOperator::NotIn);
+            }
+            eyre::bail!("Invalid marker '{}', expected 'in' after 'not'", self.input);
+        }
+
+        for operator in ["===", "==", "!=", "<=", ">=", "~=", "<", ">"] {
+            if self.rest().starts_with(operator) {
+                self.position += operator.len();
+                return Ok(MarkerOperator::Compare(operator));
+            }
+        }
+
+        eyre::bail!(
+            "Invalid marker '{}', expected an operator at '{}'",
+            self.input,
+            self.rest()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn environment(overrides: &[(&str, &str)]) -> MarkerEnvironment {
+        let mut env = MarkerEnvironment(
+            [
+                ("os_name", "posix"),
+                ("sys_platform", "linux"),
+                ("platform_system", "Linux"),
+                ("platform_machine", "x86_64"),
+                ("platform_release", "5.10.0"),
+                ("platform_version", "#1 SMP"),
+                ("platform_python_implementation", "CPython"),
+                ("implementation_name", "cpython"),
+                ("implementation_version", "3.12.1"),
+                ("python_full_version", "3.12.1"),
+                ("python_version", "3.12"),
+            ]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+        );
+
+        for (key, value) in overrides {
+            env.0.insert(key.to_string(), value.to_string());
+        }
+
+        env
+    }
+
+    fn marker(marker: &str) -> MarkerTree {
+        marker
+            .parse()
+            .unwrap_or_else(|e| panic!("{}: {}", marker, e))
+    }
+
+    /// The examples of the "Complete Grammar" section of PEP 508.
+    #[test]
+    fn pep_examples_parse() {
+        let examples = [
+            "A",
+            "A.B-C_D",
+            "aa",
+            "name",
+            "name<=1",
+            "name>=3",
+            "name>=3,<2",
+            "name@http://foo.com",
+            "name [fred,bar] @ http://foo.com ; python_version=='2.7'",
+            "name[quux, strange];python_version<'2.7' and platform_version=='2'",
+            "name; os_name=='a' or os_name=='b'",
+            "name; os_name=='a' and os_name=='b' or os_name=='c'",
+            "name; os_name=='a' and (os_name=='b' or os_name=='c')",
+            "name; os_name=='a' or os_name=='b' and os_name=='c'",
+            "name; (os_name=='a' or os_name=='b') and os_name=='c'",
+        ];
+
+        for example in examples {
+            let requirement = example
+                .parse::<Requirement>()
+                .unwrap_or_else(|e| panic!("{}: {}", example, e));
+
+            // Printing and parsing again must be lossless.
+            let printed = requirement.to_string();
+            assert_eq!(
+                printed.parse::<Requirement>().unwrap(),
+                requirement,
+                "{}",
+                printed
+            );
+        }
+    }
+
+    #[test]
+    fn requirement_parts() {
+        let requirement: Requirement =
+            "requests [security,tests] >= 2.8.1, == 2.8.* ; python_version < \"2.7\""
+                .parse()
+                .unwrap();
+        assert_eq!(requirement.name, "requests");
+        assert_eq!(requirement.extras, ["security", "tests"]);
+        assert_eq!(requirement.specifiers.to_string(), ">=2.8.1,==2.8.*");
+        assert_eq!(requirement.url, None);
+        assert_eq!(
+            requirement.marker.unwrap().to_string(),
+            "python_version < \"2.7\""
+        );
+
+        let requirement: Requirement =
+            "pip @ https://github.com/pypa/pip/archive/1.3.1.zip#sha1=da9234ee9982d4bbb3c72346a6de940a148ea686"
+                .parse()
+                .unwrap();
+        assert_eq!(requirement.name, "pip");
+        assert!(requirement.specifiers.is_empty());
+        assert_eq!(
+            requirement.url.as_deref(),
+            Some("https://github.com/pypa/pip/archive/1.3.1.zip#sha1=da9234ee9982d4bbb3c72346a6de940a148ea686")
+        );
+
+        let requirement: Requirement = "name [fred,bar] @ http://foo.com ; python_version=='2.7'"
+            .parse()
+            .unwrap();
+        assert_eq!(requirement.extras, ["fred", "bar"]);
+        assert_eq!(requirement.url.as_deref(), Some("http://foo.com"));
+        assert!(requirement.marker.is_some());
+
+        // A `;` without whitespace belongs to the URL.
+        let requirement: Requirement = "name @ http://foo.com/a;b".parse().unwrap();
+        assert_eq!(requirement.url.as_deref(), Some("http://foo.com/a;b"));
+        assert_eq!(requirement.marker, None);
+
+        let requirement: Requirement = "zope.interface (>=3.5.0)".parse().unwrap();
+        assert_eq!(requirement.specifiers.to_string(), ">=3.5.0");
+
+        let requirement: Requirement = "name[]".parse().unwrap();
+        assert!(requirement.extras.is_empty());
+    }
+
+    #[test]
+    fn invalid_requirements() {
+        let invalid = [
+            "",
+            "-name",
+            "name-",
+            ".name",
+            "name[",
+            "name[fred,]",
+            "name[-fred]",
+            "name @",
+            "name @ http://foo.com extra",
+            "name >= ",
+            "name >= 1.0 extra",
+            "name; os_name ==",
+            "name; os_name == 'a' and",
+            "name; (os_name == 'a'",
+            "name; os_name = 'a'",
+            "name; os_name == 'a",
+            "name; unknown_variable == 'a'",
+            "name; os_name not 'a'",
+        ];
+
+        for requirement in invalid {
+            assert!(
+                requirement.parse::<Requirement>().is_err(),
+                "'{}' should be rejected",
+                requirement
+            );
+        }
+    }
+
+    #[test]
+    fn marker_precedence() {
+        // `and` binds tighter than `or`.
+        assert_eq!(
+            marker("os_name=='a' and os_name=='b' or os_name=='c'"),
+            MarkerTree::Or(vec![
+                MarkerTree::And(vec![marker("os_name == 'a'"), marker("os_name == 'b'")]),
+                marker("os_name == 'c'"),
+            ])
+        );
+        assert_eq!(
+            marker("os_name=='a' or os_name=='b' and os_name=='c'"),
+            MarkerTree::Or(vec![
+                marker("os_name == 'a'"),
+                MarkerTree::And(vec![marker("os_name == 'b'"), marker("os_name == 'c'")]),
+            ])
+        );
+
+        let env = environment(&[("os_name", "c")]);
+        assert!(marker("os_name=='a' and os_name=='b' or os_name=='c'").evaluate(&env, &[]));
+        assert!(!marker("os_name=='a' and (os_name=='b' or os_name=='c')").evaluate(&env, &[]));
+        assert!(marker("(os_name=='a' or os_name=='b') or os_name=='c'").evaluate(&env, &[]));
+        assert!(!marker("(os_name=='a' or os_name=='b') and os_name=='c'").evaluate(&env, &[]));
+    }
+
+    #[test]
+    fn version_comparisons() {
+        let env = environment(&[]);
+
+        let cases = [
+            ("python_version >= '3.8'", true),
+            ("python_version < '3.8'", false),
+            ("python_version == '3.12'", true),
+            ("python_version == '3.12.0'", true),
+            ("python_version > '3.9'", true),
+            ("python_version > '3.10'", true),
+            ("python_version == '3.1*'", false),
+            ("python_version == '3.*'", true),
+            ("python_version != '3.*'", false),
+            ("python_version ~= '3.10'", true),
+            ("python_full_version >= '3.12.1'", true),
+            ("python_full_version < '3.12.1'", false),
+            ("implementation_version == '3.12.1'", true),
+            ("'3.13' > python_version", true),
+            ("python_version in '3.11 3.12'", true),
+            ("python_version not in '3.11 3.12'", false),
+            ("platform_release >= '5.4'", true),
+        ];
+
+        for (expression, expected) in cases {
+            assert_eq!(
+                marker(expression).evaluate(&env, &[]),
+                expected,
+                "{}",
+                expression
+            );
+        }
+
+        let prerelease = environment(&[("python_full_version", "3.13.0rc1")]);
+        assert!(marker("python_full_version >= '3.12'").evaluate(&prerelease, &[]));
+        // As with `packaging`, `<V` excludes the pre-releases of V itself.
+        assert!(!marker("python_full_version < '3.13'").evaluate(&prerelease, &[]));
+    }
+
+    #[test]
+    fn string_comparisons() {
+        let env = environment(&[]);
+
+        let cases = [
+            ("sys_platform == 'linux'", true),
+            ("sys_platform != 'win32'", true),
+            ("'linux' == sys_platform", true),
+            ("sys_platform == \"linux\"", true),
+            ("platform_system == 'linux'", false),
+            ("os.name == 'posix'", true),
+            ("sys.platform == 'linux'", true),
+            ("python_implementation == 'CPython'", true),
+            ("platform_machine in 'x86_64 aarch64'", true),
+            ("'arm' in platform_machine", false),
+            ("platform_machine not in 'arm64 aarch64'", true),
+            ("implementation_name === 'cpython'", true),
+            ("platform_version ~= 'SMP'", false),
+            ("os_name < 'z'", true),
+        ];
+
+        for (expression, expected) in cases {
+            assert_eq!(
+                marker(expression).evaluate(&env, &[]),
+                expected,
+                "{}",
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn extras() {
+        let env = environment(&[]);
+        let marker = marker("extra == 'Test_Suite'");
+
+        assert!(!marker.evaluate(&env, &[]));
+        assert!(marker.evaluate(&env, &["test-suite".to_string()]));
+        assert!(marker.evaluate(&env, &["test.suite".to_string()]));
+        assert!(!marker.evaluate(&env, &["docs".to_string()]));
+
+        let requirement: Requirement = "pytest; extra == 'test' and python_version >= '3.8'"
+            .parse()
+            .unwrap();
+        assert!(requirement.evaluate(&env, &["test".to_string()]));
+        assert!(!requirement.evaluate(&env, &[]));
+        assert!(!requirement.evaluate(
+            &environment(&[("python_version", "3.7")]),
+            &["test".to_string()]
+        ));
+    }
+
+    #[test]
+    fn restrict_keeps_platform_conditions() {
+        let env = environment(&[]);
+
+        assert_eq!(
+            marker("python_version >= '3.8' and sys_platform == 'win32'").restrict(&env, &[]),
+            Some(Some(marker("sys_platform == 'win32'")))
+        );
+        assert_eq!(
+            marker("python_version < '3.8' and sys_platform == 'win32'").restrict(&env, &[]),
+            None
+        );
+        assert_eq!(
+            marker("python_version >= '3.8' or sys_platform == 'win32'").restrict(&env, &[]),
+            Some(None)
+        );
+    }
+
+    #[test]
+    fn normalized_names() {
+        for name in [
+            "friendly-bard",
+            "Friendly-Bard",
+            "FRIENDLY-BARD",
+            "friendly.bard",
+            "friendly_bard",
+            "friendly--bard",
+            "FrIeNdLy-._.-bArD",
+        ] {
+            assert_eq!(normalize_name(name), "friendly-bard", "{}", name);
+        }
+    }
+}
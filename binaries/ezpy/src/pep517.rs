@@ -0,0 +1,252 @@
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+use serde_json::json;
+
+use crate::pyproject::{BuildSystem, PyProject};
+
+/// Imports the build backend and calls one of its hooks, as described by PEP 517. The hook
+/// name, a JSON file with the backend and keyword arguments, and the file to write the result
+/// to are passed as arguments; the result is written to a file since backends print freely.
+const HOOK_SCRIPT: &str = r#"
+import importlib, json, os, sys
+
+# Don't let the current directory shadow the installed backend.
+if sys.path and sys.path[0] in ("", os.getcwd()):
+    sys.path.pop(0)
+
+hook_name, input_path, output_path = sys.argv[1:4]
+with open(input_path) as f:
+    spec = json.load(f)
+
+if spec["backend_path"]:
+    sys.path[:0] = [os.path.abspath(path) for path in spec["backend_path"]]
+
+module, _, obj = spec["backend"].partition(":")
+backend = importlib.import_module(module)
+for attr in filter(None, obj.split(".")):
+    backend = getattr(backend, attr)
+
+hook = getattr(backend, hook_name, None)
+result = {"missing": hook is None}
+if hook is not None:
+    result["value"] = hook(**spec["kwargs"])
+
+with open(output_path, "w") as f:
+    json.dump(result, f)
+"#;
+
+/// Runs the build backend of a project in an isolated environment holding only its build
+/// requirements. The environment is removed when the builder is dropped.
+pub struct Builder {
+    pub project: PathBuf,
+    system: BuildSystem,
+    dir: PathBuf,
+    env: PathBuf,
+}
+
+impl Builder {
+    /// Creates the build environment of `project` on Python `version` and installs the
+    /// requirements of its `[build-system]` table into it.
+    pub async fn new(project: &Path, version: &VersionString) -> eyre::Result<Self> {
+        let project = project
+            .canonicalize()
+            .map_err(|e| eyre::eyre!("Failed to open {}: {}", project.display(), e))?;
+
+        let pyproject = project.join("pyproject.toml");
+        let system = if pyproject.exists() {
+            PyProject::read(&pyproject)
+                .await?
+                .build_system
+                .unwrap_or_default()
+        } else if project.join("setup.py").exists() {
+            BuildSystem::default()
+        } else {
+            eyre::bail!(
+                "{} has neither a pyproject.toml nor a setup.py",
+                project.display()
+            );
+        };
+
+        let unique = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos();
+        let dir = crate::cache_home_ezpy()?.join("builds").join(format!(
+            "{}-{}",
+            std::process::id(),
+            unique
+        ));
+        let env = dir.join("env");
+
+        let builder = Builder {
+            project,
+            system,
+            dir,
+            env,
+        };
+
+        if crate::is_verbose() {
+            eprintln!(
+                "ezpy: building with {} in {}",
+                builder.system.backend(),
+                builder.env.display()
+            );
+        }
+
+        crate::venv::create_env(version, &builder.env, "build", Default::default()).await?;
+        builder.install(&builder.system.requires.clone()).await?;
+
+        Ok(builder)
+    }
+
+    /// A scratch directory inside the build directory.
+    pub async fn scratch(&self, name: &str) -> eyre::Result<PathBuf> {
+        let dir = self.dir.join(name);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        Ok(dir)
+    }
+
+    async fn install(&self, requirements: &[String]) -> eyre::Result<()> {
+        if requirements.is_empty() {
+            return Ok(());
+        }
+
+        println!("Installing build requirements: {}", requirements.join(", "));
+
+        let mut args = vec!["install".to_string(), "--quiet".to_string()];
+        args.extend(requirements.iter().cloned());
+
+        crate::pip::run(&self.env, &args, "Failed to install build requirements").await
+    }
+
+    /// Calls `hook` with keyword arguments `kwargs`, returning `None` when the backend
+    /// doesn't define it.
+    pub async fn call(
+        &self,
+        hook: &str,
+        kwargs: serde_json::Value,
+    ) -> eyre::Result<Option<serde_json::Value>> {
+        let input = self.dir.join(format!("{}.in.json", hook));
+        let output = self.dir.join(format!("{}.out.json", hook));
+
+        let spec = json!({
+            "backend": self.system.backend(),
+            "backend_path": self.system.backend_path,
+            "kwargs": kwargs,
+        });
+        tokio::fs::write(&input, serde_json::to_vec(&spec)?).await?;
+        tokio::fs::remove_file(&output).await.ok();
+
+        if crate::is_verbose() {
+            eprintln!("ezpy: calling {} of {}", hook, self.system.backend());
+        }
+
+        let out = tokio::process::Command::new(self.env.join(crate::env_bin_path()))
+            .arg("-c")
+            .arg(HOOK_SCRIPT)
+            .arg(hook)
+            .arg(&input)
+            .arg(&output)
+            .current_dir(&self.project)
+            .output()
+            .await?;
+
+        if crate::is_verbose() {
+            eprint!("{}", String::from_utf8_lossy(&out.stdout));
+            eprint!("{}", String::from_utf8_lossy(&out.stderr));
+        }
+
+        if !out.status.success() {
+            // Backends report errors on either stream, setuptools often on stdout.
+            let tail = |stream: &[u8]| {
+                let text = String::from_utf8_lossy(stream);
+                let lines = text.lines().rev().take(20).collect::<Vec<_>>();
+                lines.into_iter().rev().collect::<Vec<_>>().join("\n")
+            };
+            let output = [tail(&out.stdout), tail(&out.stderr)]
+                .into_iter()
+                .filter(|tail| !tail.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n");
+            eyre::bail!(
+                "The build backend {} failed in {}:\n{}",
+                self.system.backend(),
+                hook,
+                output
+            );
+        }
+
+        let result: serde_json::Value = serde_json::from_slice(&tokio::fs::read(&output).await?)?;
+        if result["missing"].as_bool() == Some(true) {
+            return Ok(None);
+        }
+
+        Ok(Some(result["value"].clone()))
+    }
+
+    /// Installs the additional requirements the backend reports through the
+    /// `get_requires_for_build_<kind>` hook.
+    pub async fn install_requires_for(&self, kind: &str) -> eyre::Result<()> {
+        let hook = format!("get_requires_for_build_{}", kind);
+        let Some(requires) = self.call(&hook, json!({})).await? else {
+            return Ok(());
+        };
+
+        let requires: Vec<String> = serde_json::from_value(requires)
+            .map_err(|e| eyre::eyre!("Invalid result from {}: {}", hook, e))?;
+
+        self.install(&requires).await
+    }
+
+    /// Calls `build_<kind>` to write a distribution into `directory`, returning its path, or
+    /// `None` when the backend doesn't support the kind.
+    pub async fn build(&self, kind: &str, directory: &Path) -> eyre::Result<Option<PathBuf>> {
+        let mut kwargs = json!({ "config_settings": null });
+        kwargs[format!(
+            "{}_directory",
+            if kind == "sdist" { "sdist" } else { "wheel" }
+        )] = json!(directory);
+
+        let hook = format!("build_{}", kind);
+        let Some(filename) = self.call(&hook, kwargs).await? else {
+            return Ok(None);
+        };
+
+        let filename = filename
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("Invalid result from {}: {}", hook, filename))?;
+
+        Ok(Some(directory.join(filename)))
+    }
+
+    /// Calls `prepare_metadata_for_build_<kind>`, returning the `.dist-info` directory it
+    /// wrote into `directory`, or `None` when the backend doesn't define the hook.
+    pub async fn prepare_metadata(
+        &self,
+        kind: &str,
+        directory: &Path,
+    ) -> eyre::Result<Option<PathBuf>> {
+        let hook = format!("prepare_metadata_for_build_{}", kind);
+        let kwargs = json!({ "metadata_directory": directory, "config_settings": null });
+
+        let Some(name) = self.call(&hook, kwargs).await? else {
+            return Ok(None);
+        };
+
+        let name = name
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("Invalid result from {}: {}", hook, name))?;
+
+        Ok(Some(directory.join(name)))
+    }
+}
+
+impl Drop for Builder {
+    fn drop(&mut self) {
+        if crate::is_verbose() {
+            eprintln!("ezpy: removing {}", self.dir.display());
+        }
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
@@ -1,15 +1,72 @@
+use std::path::{Path, PathBuf};
+
 use crate::install_home_ezpy;
+use crate::pep440::{SpecifierSet, Version};
 use indygreg::metadata::VersionString;
 
-pub async fn pin_version(version: VersionString) -> eyre::Result<()> {
-    indygreg::ensure_python_version(version.clone())?;
+pub const PYTHON_VERSION_FILE: &str = ".python-version";
+pub const PYTHON_ENV_VAR: &str = "EZPY_PYTHON";
 
-    let path = install_home_ezpy()?.join("python.txt");
+/// Where the effective Python version was found, in order of precedence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSource {
+    Flag,
+    EnvVar,
+    PythonVersionFile(PathBuf),
+    PyProject(PathBuf),
+    Global(PathBuf),
+}
+
+impl std::fmt::Display for VersionSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionSource::Flag => write!(f, "the --python flag"),
+            VersionSource::EnvVar => write!(f, "the {} environment variable", PYTHON_ENV_VAR),
+            VersionSource::PythonVersionFile(path) => write!(f, "{}", path.display()),
+            VersionSource::PyProject(path) => {
+                write!(f, "requires-python in {}", path.display())
+            }
+            VersionSource::Global(path) => write!(f, "the global pin at {}", path.display()),
+        }
+    }
+}
+
+/// An installed Python version together with the place it was requested from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedVersion {
+    pub version: VersionString,
+    pub source: VersionSource,
+}
+
+pub async fn pin_version(version: VersionString, global: bool) -> eyre::Result<()> {
+    resolve_installed(&version)?;
+
+    if global {
+        let path = install_home_ezpy()?.join("python.txt");
+
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        tokio::fs::write(&path, version.to_string()).await?;
+
+        println!("Pinned version {} for future commands", version);
+    } else {
+        let path = std::env::current_dir()?.join(PYTHON_VERSION_FILE);
+
+        tokio::fs::write(&path, format!("{}\n", version)).await?;
+
+        println!("Pinned version {} in {}", version, path.display());
+    }
+
+    Ok(())
+}
 
-    tokio::fs::create_dir_all(path.parent().unwrap()).await?;
-    tokio::fs::write(&path, version.to_string()).await?;
+pub async fn show_pinned_version(flag: Option<VersionString>) -> eyre::Result<()> {
+    let resolved = resolve_version(flag).await?;
 
-    println!("Pinned version {} for future commands", version);
+    println!("Python {} (from {})", resolved.version, resolved.source);
+
+    if let Err(e) = check_requires_python(&resolved).await {
+        println!("Warning: {}", e);
+    }
 
     Ok(())
 }
@@ -23,5 +80,202 @@ pub async fn get_pinned_version() -> eyre::Result<VersionString> {
 
     let contents = tokio::fs::read_to_string(&path).await?;
 
-    Ok(contents)
+    Ok(contents.trim().to_string())
+}
+
+/// Resolves the Python version to use, checking in order the `--python` flag, the
+/// `EZPY_PYTHON` environment variable, the nearest `.python-version` file, `requires-python`
+/// in the nearest `pyproject.toml` and finally the global pin.
+pub async fn resolve_version(flag: Option<VersionString>) -> eyre::Result<ResolvedVersion> {
+    resolve(flag, false).await
+}
+
+/// Same as [`resolve_version`], but when `install_missing` is set, a version that isn't
+/// installed yet is downloaded instead of failing.
+pub async fn resolve(
+    flag: Option<VersionString>,
+    install_missing: bool,
+) -> eyre::Result<ResolvedVersion> {
+    resolve_in(&std::env::current_dir()?, flag, install_missing).await
+}
+
+/// Same as [`resolve`], but looks for `.python-version` and `pyproject.toml` from `dir`
+/// rather than the current directory.
+pub async fn resolve_in(
+    dir: &Path,
+    flag: Option<VersionString>,
+    install_missing: bool,
+) -> eyre::Result<ResolvedVersion> {
+    if let Some(version) = flag {
+        return Ok(ResolvedVersion {
+            version: resolve_or_install(&version, install_missing).await?,
+            source: VersionSource::Flag,
+        });
+    }
+
+    if let Some(version) = std::env::var(PYTHON_ENV_VAR)
+        .ok()
+        .filter(|version| !version.trim().is_empty())
+    {
+        return Ok(ResolvedVersion {
+            version: resolve_or_install(version.trim(), install_missing).await?,
+            source: VersionSource::EnvVar,
+        });
+    }
+
+    if let Some(path) = crate::find_upwards(dir, PYTHON_VERSION_FILE) {
+        let version = read_python_version_file(&path).await?;
+
+        return Ok(ResolvedVersion {
+            version: resolve_or_install(&version, install_missing).await?,
+            source: VersionSource::PythonVersionFile(path),
+        });
+    }
+
+    if let Some(path) = crate::pyproject::find_pyproject(dir) {
+        let pyproject = crate::pyproject::PyProject::read(&path).await?;
+
+        if let Some(requires) = pyproject.requires_python()? {
+            let version = match best_installed(&requires)? {
+                Some(version) => version,
+                None if install_missing => install_best(&requires).await?,
+                None => eyre::bail!(
+                    "No installed Python satisfies requires-python '{}' from {}, install one with `ezpy install python <VERSION>` or pass `--install-missing`",
+                    requires,
+                    path.display()
+                ),
+            };
+
+            return Ok(ResolvedVersion {
+                version,
+                source: VersionSource::PyProject(path),
+            });
+        }
+    }
+
+    let path = install_home_ezpy()?.join("python.txt");
+    let version = get_pinned_version().await?;
+
+    Ok(ResolvedVersion {
+        version: resolve_or_install(&version, install_missing).await?,
+        source: VersionSource::Global(path),
+    })
+}
+
+/// Fails when the resolved version violates `requires-python` of the nearest `pyproject.toml`.
+pub async fn check_requires_python(resolved: &ResolvedVersion) -> eyre::Result<()> {
+    check_requires_python_in(&std::env::current_dir()?, resolved).await
+}
+
+/// Same as [`check_requires_python`], for the nearest `pyproject.toml` from `dir`.
+pub async fn check_requires_python_in(dir: &Path, resolved: &ResolvedVersion) -> eyre::Result<()> {
+    let Some(path) = crate::pyproject::find_pyproject(dir) else {
+        return Ok(());
+    };
+
+    let Some(requires) = crate::pyproject::PyProject::read(&path)
+        .await?
+        .requires_python()?
+    else {
+        return Ok(());
+    };
+
+    let version: Version = resolved.version.parse()?;
+    if !requires.contains(&version) {
+        eyre::bail!(
+            "Python {} (from {}) doesn't satisfy requires-python '{}' from {}",
+            resolved.version,
+            resolved.source,
+            requires,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+async fn read_python_version_file(path: &Path) -> eyre::Result<VersionString> {
+    let contents = tokio::fs::read_to_string(path).await?;
+
+    contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.strip_prefix("python-").unwrap_or(line).to_string())
+        .ok_or_else(|| eyre::eyre!("{} doesn't contain a version", path.display()))
+}
+
+/// Whether `version` is the requested version, possibly partial like `3.12`.
+pub fn matches_request(version: &str, request: &str) -> bool {
+    version == request || version.starts_with(&format!("{}.", request))
+}
+
+/// Maps a requested version, possibly partial like `3.12`, to the newest installed match.
+pub fn resolve_installed(request: &str) -> eyre::Result<VersionString> {
+    if indygreg::python_path(request.to_string())?.exists() {
+        return Ok(request.to_string());
+    }
+
+    let installed = indygreg::installed_versions()?
+        .into_iter()
+        .filter(|version| matches_request(version, request))
+        .filter_map(|version| Some((version.parse::<Version>().ok()?, version)))
+        .max();
+
+    match installed {
+        Some((_, version)) => Ok(version),
+        None => Err(eyre::eyre!(
+            "Python version {} is not installed, please install it with `ezpy install python {}`",
+            request,
+            request
+        )),
+    }
+}
+
+async fn resolve_or_install(request: &str, install_missing: bool) -> eyre::Result<VersionString> {
+    match resolve_installed(request) {
+        Err(_) if install_missing => {
+            let requires: SpecifierSet = format!("=={}.*", request).parse()?;
+            install_best(&requires).await
+        }
+        result => result,
+    }
+}
+
+/// Downloads and installs the newest available Python version satisfying `requires`.
+pub async fn install_best(requires: &SpecifierSet) -> eyre::Result<VersionString> {
+    let packages = indygreg::package::available_packages().await?;
+
+    let (version, package) = packages
+        .into_iter()
+        .filter_map(|(key, package)| {
+            let version = key.split('-').nth(1)?.parse::<Version>().ok()?;
+            Some((version, package))
+        })
+        .filter(|(version, _)| !version.is_prerelease() && requires.contains(version))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .ok_or_else(|| {
+            eyre::eyre!(
+                "No Python release available for this platform satisfies '{}'",
+                requires
+            )
+        })?;
+
+    println!("Installing Python {} to satisfy '{}'...", version, requires);
+
+    indygreg::install::download_install(package).await?;
+
+    println!("Python {} installed successfully", version);
+
+    Ok(version.to_string())
+}
+
+/// Returns the newest installed Python version satisfying `requires`.
+pub fn best_installed(requires: &SpecifierSet) -> eyre::Result<Option<VersionString>> {
+    Ok(indygreg::installed_versions()?
+        .into_iter()
+        .filter_map(|version| Some((version.parse::<Version>().ok()?, version)))
+        .filter(|(parsed, _)| requires.contains(parsed))
+        .max()
+        .map(|(_, version)| version))
 }
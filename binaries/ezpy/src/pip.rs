@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+/// How many of the last output lines of pip are kept for the error message.
+const TAIL_LINES: usize = 20;
+
+/// Why a pip invocation failed, recognized from its output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipFailure {
+    /// No release of the requirement is available for this interpreter and platform.
+    NoMatchingDistribution {
+        requirement: String,
+    },
+
+    /// Building a source distribution failed.
+    BuildFailed {
+        package: Option<String>,
+    },
+
+    /// The requirements can't be satisfied together.
+    Conflict {
+        causes: Vec<String>,
+    },
+
+    Other {
+        status: Option<i32>,
+    },
+}
+
+impl PipFailure {
+    /// Recognizes the failure from the last lines pip printed.
+    pub fn classify(lines: &[String], status: Option<i32>) -> Self {
+        for line in lines {
+            let line = line.trim();
+            for prefix in [
+                "ERROR: No matching distribution found for ",
+                "ERROR: Could not find a version that satisfies the requirement ",
+            ] {
+                if let Some(rest) = line.strip_prefix(prefix) {
+                    let requirement = rest.split(" (").next().unwrap_or(rest).trim();
+                    return PipFailure::NoMatchingDistribution {
+                        requirement: requirement.to_string(),
+                    };
+                }
+            }
+        }
+
+        if lines.iter().any(|line| {
+            line.contains("ResolutionImpossible") || line.contains("conflicting dependencies")
+        }) {
+            let causes = lines
+                .iter()
+                .skip_while(|line| !line.contains("The conflict is caused by"))
+                .skip(1)
+                .take_while(|line| line.starts_with(' ') && !line.trim().is_empty())
+                .map(|line| line.trim().to_string())
+                .collect();
+            return PipFailure::Conflict { causes };
+        }
+
+        let build_failed = lines.iter().any(|line| {
+            line.contains("subprocess-exited-with-error")
+                || line.contains("metadata-generation-failed")
+                || line.contains("Failed building wheel for")
+                || line.contains("Failed to build ")
+        });
+        if build_failed {
+            let package = lines.iter().find_map(|line| {
+                let rest = line
+                    .split_once("Failed building wheel for ")
+                    .or_else(|| line.split_once("Failed to build "))?
+                    .1;
+                Some(rest.split_whitespace().next()?.to_string())
+            });
+            return PipFailure::BuildFailed { package };
+        }
+
+        PipFailure::Other { status }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            PipFailure::NoMatchingDistribution { .. } => Some(
+                "Check the package name and version, whether it supports this Python version (see --python), and the index or --find-links used.",
+            ),
+            PipFailure::BuildFailed { .. } => Some(
+                "The package had to be built from source. Install its build requirements (compiler, headers), or use a Python version it publishes wheels for.",
+            ),
+            PipFailure::Conflict { .. } => Some(
+                "Loosen the version constraints of the packages above, or upgrade the installed packages they conflict with.",
+            ),
+            PipFailure::Other { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for PipFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipFailure::NoMatchingDistribution { requirement } => {
+                write!(f, "no matching distribution found for {}", requirement)
+            }
+            PipFailure::BuildFailed {
+                package: Some(package),
+            } => write!(f, "building {} failed", package),
+            PipFailure::BuildFailed { package: None } => write!(f, "building a package failed"),
+            PipFailure::Conflict { causes } if causes.is_empty() => {
+                write!(f, "the requirements conflict")
+            }
+            PipFailure::Conflict { causes } => {
+                write!(f, "the requirements conflict:")?;
+                for cause in causes {
+                    write!(f, "\n    {}", cause)?;
+                }
+                Ok(())
+            }
+            PipFailure::Other { status: Some(code) } => write!(f, "pip exited with code {}", code),
+            PipFailure::Other { status: None } => write!(f, "pip was terminated"),
+        }
+    }
+}
+
+/// A failed pip invocation, with what it was doing and the end of its output.
+#[derive(Debug)]
+pub struct PipError {
+    pub context: String,
+    pub failure: PipFailure,
+    pub tail: Vec<String>,
+}
+
+impl fmt::Display for PipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.context, self.failure)?;
+
+        if !self.tail.is_empty() {
+            write!(f, "\n\nLast output of pip:")?;
+            for line in &self.tail {
+                write!(f, "\n  {}", line)?;
+            }
+        }
+
+        if let Some(hint) = self.failure.hint() {
+            write!(f, "\n\nhint: {}", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for PipError {}
+
+/// Runs `python -m pip <args>` in `venv`, streaming its output unless `--quiet` is set,
+/// and fails with a [`PipError`] described by `context` when pip does.
+pub async fn run(venv: &Path, args: &[String], context: &str) -> eyre::Result<()> {
+    let bin = venv.join(crate::env_bin_path());
+
+    if crate::is_verbose() {
+        eprintln!("ezpy: running {} -m pip {}", bin.display(), args.join(" "));
+    }
+
+    let mut child = tokio::process::Command::new(bin)
+        .arg("-m")
+        .arg("pip")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdout = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+    let quiet = crate::is_quiet();
+
+    let (stdout, stderr, status) = tokio::join!(
+        forward(stdout, quiet, false),
+        forward(stderr, quiet, true),
+        child.wait()
+    );
+    let (stdout, stderr, status) = (stdout?, stderr?, status?);
+
+    if status.success() {
+        return Ok(());
+    }
+
+    let mut lines = Vec::from(stdout);
+    lines.extend(stderr.iter().cloned());
+
+    let failure = PipFailure::classify(&lines, status.code());
+    let tail = if stderr.is_empty() {
+        lines
+    } else {
+        Vec::from(stderr)
+    };
+
+    Err(PipError {
+        context: context.to_string(),
+        failure,
+        tail,
+    }
+    .into())
+}
+
+/// Prints every line of `reader` unless `quiet`, returning the last ones.
+async fn forward<R: AsyncBufRead + Unpin>(
+    reader: R,
+    quiet: bool,
+    to_stderr: bool,
+) -> std::io::Result<VecDeque<String>> {
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+    let mut lines = reader.lines();
+
+    while let Some(line) = lines.next_line().await? {
+        if !quiet {
+            if to_stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+
+        if tail.len() == TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line);
+    }
+
+    Ok(tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(output: &str) -> PipFailure {
+        let lines = output.lines().map(str::to_string).collect::<Vec<_>>();
+        PipFailure::classify(&lines, Some(1))
+    }
+
+    #[test]
+    fn no_matching_distribution() {
+        let output = "\
+Looking in links: /tmp/wheels
+ERROR: Could not find a version that satisfies the requirement nonexistent-pkg (from versions: none)
+ERROR: No matching distribution found for nonexistent-pkg
+";
+
+        assert_eq!(
+            classify(output),
+            PipFailure::NoMatchingDistribution {
+                requirement: "nonexistent-pkg".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn build_failed_while_building_the_wheel() {
+        let output = "\
+Building wheels for collected packages: badpkg
+  Building wheel for badpkg (pyproject.toml): started
+  Building wheel for badpkg (pyproject.toml): finished with status 'error'
+  error: subprocess-exited-with-error
+  
+  × Building wheel for badpkg (pyproject.toml) did not run successfully.
+  │ exit code: 1
+  ╰─> [4 lines of output]
+      running bdist_wheel
+      running build
+      running build_py
+      error: command 'gcc' failed: No such file or directory
+      [end of output]
+  
+  note: This error originates from a subprocess, and is likely not a problem with pip.
+  ERROR: Failed building wheel for badpkg
+Failed to build badpkg
+ERROR: Could not build wheels for badpkg, which is required to install pyproject.toml-based projects
+";
+
+        assert_eq!(
+            classify(output),
+            PipFailure::BuildFailed {
+                package: Some("badpkg".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn build_failed_while_getting_requirements() {
+        let output = "\
+Processing ./badpkg
+  Installing build dependencies: started
+  Installing build dependencies: finished with status 'done'
+  Getting requirements to build wheel: started
+  Getting requirements to build wheel: finished with status 'error'
+  error: subprocess-exited-with-error
+  
+  × Getting requirements to build wheel did not run successfully.
+  │ exit code: 1
+  ╰─> [1 lines of output]
+      boom: missing compiler
+      [end of output]
+  
+  note: This error originates from a subprocess, and is likely not a problem with pip.
+error: subprocess-exited-with-error
+";
+
+        assert_eq!(classify(output), PipFailure::BuildFailed { package: None });
+    }
+
+    #[test]
+    fn resolution_impossible() {
+        let output = "\
+Requirement already satisfied: wheel>=0.43 in ./site-packages (0.43.0)
+ERROR: Cannot install wheel<0.40 and wheel>=0.43 because these package versions have conflicting dependencies.
+
+The conflict is caused by:
+    The user requested wheel>=0.43
+    The user requested wheel<0.40
+
+To fix this you could try to:
+1. loosen the range of package versions you've specified
+2. remove package versions to allow pip attempt to solve the dependency conflict
+
+ERROR: ResolutionImpossible: for help visit https://pip.pypa.io/en/latest/topics/dependency-resolution/#dealing-with-dependency-conflicts
+";
+
+        assert_eq!(
+            classify(output),
+            PipFailure::Conflict {
+                causes: vec![
+                    "The user requested wheel>=0.43".to_string(),
+                    "The user requested wheel<0.40".to_string(),
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn other_failures_keep_the_status() {
+        assert_eq!(
+            classify("ERROR: Invalid requirement: '=='\n"),
+            PipFailure::Other { status: Some(1) }
+        );
+    }
+}
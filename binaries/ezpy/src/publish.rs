@@ -0,0 +1,383 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use reqwest::multipart::{Form, Part};
+use sha2::{Digest, Sha256};
+
+use crate::config::{RepositoryConfig, UserConfig};
+use crate::wheel::{Wheel, WheelFilename};
+
+pub const DEFAULT_REPOSITORY: &str = "pypi";
+pub const DEFAULT_REPOSITORY_URL: &str = "https://upload.pypi.org/legacy/";
+
+/// Metadata fields that may appear several times, with their upload form names.
+const MULTIPLE_FIELDS: &[(&str, &str)] = &[
+    ("classifier", "classifiers"),
+    ("project-url", "project_urls"),
+    ("platform", "platform"),
+    ("supported-platform", "supported_platform"),
+    ("requires-dist", "requires_dist"),
+    ("provides-dist", "provides_dist"),
+    ("obsoletes-dist", "obsoletes_dist"),
+    ("requires-external", "requires_external"),
+    ("provides-extra", "provides_extra"),
+    ("license-file", "license_file"),
+    ("dynamic", "dynamic"),
+];
+
+/// Uploads wheels and sdists to a package index with the legacy upload API. The index is
+/// `repository_url` when given, otherwise the URL of `repository` in the user
+/// configuration, PyPI by default. Credentials stored in the configuration are only sent to
+/// the URL of their repository.
+pub async fn publish(
+    files: Vec<PathBuf>,
+    repository: Option<String>,
+    repository_url: Option<String>,
+) -> eyre::Result<()> {
+    let config = UserConfig::load()?;
+    let name = repository.unwrap_or_else(|| DEFAULT_REPOSITORY.to_string());
+    let repository = repository_config(&config, &name, repository_url);
+
+    let url = match repository.url.clone() {
+        Some(url) => url,
+        None if name == DEFAULT_REPOSITORY => DEFAULT_REPOSITORY_URL.to_string(),
+        None => eyre::bail!(
+            "No repository named '{}' in {}",
+            name,
+            crate::config::user_config_path()?.display()
+        ),
+    };
+
+    let credentials = credentials(&repository);
+    if credentials.is_none() && crate::is_verbose() {
+        eprintln!("ezpy: no credentials found, uploading anonymously");
+    }
+
+    let client = reqwest::Client::builder()
+        .user_agent(concat!("ezpy/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+
+    for file in files {
+        println!("Uploading {} to {}", file.display(), url);
+        upload(&client, &url, &file, credentials.as_ref()).await?;
+    }
+
+    Ok(())
+}
+
+/// The repository uploads go to: an anonymous one at `repository_url` when given, whose
+/// credentials only come from the environment, otherwise `name` from the user configuration.
+fn repository_config(
+    config: &UserConfig,
+    name: &str,
+    repository_url: Option<String>,
+) -> RepositoryConfig {
+    match repository_url {
+        Some(url) => RepositoryConfig {
+            url: Some(url),
+            ..Default::default()
+        },
+        None => config.repositories.get(name).cloned().unwrap_or_default(),
+    }
+}
+
+/// Uploads the wheel or sdist at `file` to the legacy upload API at `url`.
+async fn upload(
+    client: &reqwest::Client,
+    url: &str,
+    file: &Path,
+    credentials: Option<&(String, String)>,
+) -> eyre::Result<()> {
+    let form = upload_form(file).await?;
+
+    let mut request = client.post(url).multipart(form);
+    if let Some((username, password)) = credentials {
+        request = request.basic_auth(username, Some(password));
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to upload {}: {}", file.display(), e))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        let reason = body
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty() && !line.starts_with('<'))
+            .unwrap_or(status.canonical_reason().unwrap_or_default());
+
+        let hint = match status.as_u16() {
+            401 | 403 => "\n\nhint: Check the credentials set with EZPY_PUBLISH_TOKEN, EZPY_PUBLISH_USERNAME and EZPY_PUBLISH_PASSWORD, or in the configuration file.",
+            409 => "\n\nhint: A file can only be uploaded once, bump the version of the project.",
+            _ => "",
+        };
+
+        eyre::bail!(
+            "Failed to upload {}: {} {}{}",
+            file.display(),
+            status.as_u16(),
+            reason,
+            hint
+        );
+    }
+
+    Ok(())
+}
+
+/// Finds credentials in `EZPY_PUBLISH_TOKEN`, `EZPY_PUBLISH_USERNAME` and
+/// `EZPY_PUBLISH_PASSWORD`, then in the repository configuration. Tokens use the
+/// `__token__` username.
+fn credentials(repository: &RepositoryConfig) -> Option<(String, String)> {
+    if let Ok(token) = std::env::var("EZPY_PUBLISH_TOKEN") {
+        return Some(("__token__".to_string(), token));
+    }
+
+    let username = std::env::var("EZPY_PUBLISH_USERNAME")
+        .ok()
+        .or(repository.username.clone());
+    let password = std::env::var("EZPY_PUBLISH_PASSWORD")
+        .ok()
+        .or(repository.password.clone())?;
+
+    Some((
+        username.unwrap_or_else(|| "__token__".to_string()),
+        password,
+    ))
+}
+
+/// The multipart form uploading `path`: the core metadata, digests and the file itself.
+async fn upload_form(path: &Path) -> eyre::Result<Form> {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let (filetype, pyversion, metadata) = if filename.ends_with(".whl") {
+        let wheel: WheelFilename = filename.parse()?;
+        let path = path.to_path_buf();
+        let metadata =
+            tokio::task::spawn_blocking(move || Wheel::open(&path)?.metadata_contents()).await??;
+
+        ("bdist_wheel", wheel.python.join("."), metadata)
+    } else if filename.ends_with(".tar.gz") {
+        let path = path.to_path_buf();
+        let metadata = tokio::task::spawn_blocking(move || sdist_metadata(&path)).await??;
+
+        ("sdist", "source".to_string(), metadata)
+    } else {
+        eyre::bail!(
+            "{} is neither a wheel nor an sdist (.tar.gz)",
+            path.display()
+        );
+    };
+
+    let contents = tokio::fs::read(path)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+
+    let mut form = Form::new()
+        .text(":action", "file_upload")
+        .text("protocol_version", "1")
+        .text("filetype", filetype)
+        .text("pyversion", pyversion)
+        .text("sha256_digest", format!("{:x}", Sha256::digest(&contents)));
+
+    let (headers, body) = crate::wheel::parse_headers(&metadata);
+    for (key, value) in headers {
+        let key = key.to_ascii_lowercase();
+        let field = MULTIPLE_FIELDS
+            .iter()
+            .find(|(header, _)| *header == key)
+            .map(|(_, field)| field.to_string())
+            .unwrap_or_else(|| key.replace('-', "_"));
+
+        form = form.text(field, value);
+    }
+
+    if let Some(description) = body {
+        form = form.text("description", description);
+    }
+
+    let part = Part::bytes(contents)
+        .file_name(filename)
+        .mime_str("application/octet-stream")?;
+
+    Ok(form.part("content", part))
+}
+
+/// Reads the `PKG-INFO` file at the root of an sdist.
+fn sdist_metadata(path: &Path) -> eyre::Result<String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| eyre::eyre!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(file));
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let is_pkg_info = {
+            let entry_path = entry.path()?;
+            entry_path.components().count() == 2 && entry_path.ends_with("PKG-INFO")
+        };
+
+        if is_pkg_info {
+            let mut contents = String::new();
+            entry.read_to_string(&mut contents)?;
+            return Ok(contents);
+        }
+    }
+
+    eyre::bail!("{} has no PKG-INFO file", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+    use crate::wheel::tests::{temp_dir, write_wheel};
+
+    /// Accepts one request on `listener`, answers it with `status` and returns its raw bytes.
+    async fn serve_once(listener: tokio::net::TcpListener, status: &str) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut request = Vec::new();
+        let mut buffer = [0; 8192];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|line| {
+                        let (name, value) = line.split_once(':')?;
+                        name.eq_ignore_ascii_case("content-length")
+                            .then(|| value.trim().parse::<usize>().ok())?
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length {
+                    break;
+                }
+            }
+            if read == 0 {
+                break;
+            }
+        }
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            status
+        );
+        stream.write_all(response.as_bytes()).await.unwrap();
+
+        String::from_utf8_lossy(&request).to_string()
+    }
+
+    fn field(request: &str, name: &str) -> Option<String> {
+        let marker = format!("name=\"{}\"", name);
+        let start = request.find(&marker)? + marker.len();
+        let start = start + request[start..].find("\r\n\r\n")? + 4;
+        let end = start + request[start..].find("\r\n--")?;
+        Some(request[start..end].to_string())
+    }
+
+    #[test]
+    fn repository_url_ignores_stored_credentials() {
+        let config: UserConfig =
+            toml::from_str("[repositories.pypi]\nusername = \"me\"\npassword = \"secret\"\n")
+                .unwrap();
+
+        let repository = repository_config(&config, "pypi", None);
+        assert_eq!(repository.password.as_deref(), Some("secret"));
+
+        let repository = repository_config(
+            &config,
+            "pypi",
+            Some("http://localhost/legacy/".to_string()),
+        );
+        assert_eq!(repository.url.as_deref(), Some("http://localhost/legacy/"));
+        assert_eq!(repository.username, None);
+        assert_eq!(repository.password, None);
+    }
+
+    #[tokio::test]
+    async fn uploads_wheel_form_with_basic_auth() {
+        let dir = temp_dir("publish");
+        let wheel = write_wheel(
+            &dir,
+            "demo-1.0-py3-none-any.whl",
+            &["rich>=13", "click"],
+            &[("demo/__init__.py", "")],
+        );
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/legacy/", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener, "200 OK"));
+
+        let credentials = ("__token__".to_string(), "pypi-secret".to_string());
+        upload(&reqwest::Client::new(), &url, &wheel, Some(&credentials))
+            .await
+            .unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /legacy/ HTTP/1.1\r\n"));
+
+        let authorization = request
+            .lines()
+            .find_map(|line| line.strip_prefix("authorization: "))
+            .unwrap();
+        assert_eq!(
+            authorization,
+            format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode("__token__:pypi-secret")
+            )
+        );
+
+        let contents = std::fs::read(&wheel).unwrap();
+        assert_eq!(field(&request, ":action").as_deref(), Some("file_upload"));
+        assert_eq!(field(&request, "protocol_version").as_deref(), Some("1"));
+        assert_eq!(field(&request, "filetype").as_deref(), Some("bdist_wheel"));
+        assert_eq!(field(&request, "pyversion").as_deref(), Some("py3"));
+        assert_eq!(field(&request, "name").as_deref(), Some("demo"));
+        assert_eq!(field(&request, "version").as_deref(), Some("1.0"));
+        assert_eq!(
+            field(&request, "sha256_digest"),
+            Some(format!("{:x}", Sha256::digest(&contents)))
+        );
+        assert_eq!(request.matches("name=\"requires_dist\"").count(), 2);
+        assert!(request.contains(
+            "name=\"content\"; filename=\"demo-1.0-py3-none-any.whl\"\r\nContent-Type: application/octet-stream"
+        ));
+        assert!(request.contains("PK\u{3}\u{4}"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn reports_rejected_uploads() {
+        let dir = temp_dir("publish-conflict");
+        let wheel = write_wheel(&dir, "demo-1.0-py3-none-any.whl", &[], &[]);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/legacy/", listener.local_addr().unwrap());
+        let server = tokio::spawn(serve_once(listener, "409 Conflict"));
+
+        let error = upload(&reqwest::Client::new(), &url, &wheel, None)
+            .await
+            .unwrap_err();
+        let request = server.await.unwrap();
+
+        assert!(!request.to_ascii_lowercase().contains("authorization:"));
+        assert!(error.to_string().starts_with(&format!(
+            "Failed to upload {}: 409 Conflict\n\nhint: A file can only be uploaded once",
+            wheel.display()
+        )));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
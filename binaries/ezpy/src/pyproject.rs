@@ -0,0 +1,368 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::pep440::SpecifierSet;
+use crate::pep508::{normalize_name, Requirement};
+
+/// The subset of `pyproject.toml` that ezpy understands.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PyProject {
+    pub project: Option<Project>,
+
+    #[serde(rename = "build-system")]
+    pub build_system: Option<BuildSystem>,
+}
+
+/// The `[build-system]` table, as specified by PEP 517 and PEP 518.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildSystem {
+    #[serde(default)]
+    pub requires: Vec<String>,
+
+    pub build_backend: Option<String>,
+
+    #[serde(default)]
+    pub backend_path: Vec<String>,
+}
+
+impl Default for BuildSystem {
+    /// The legacy setuptools backend used for projects that don't declare one.
+    fn default() -> Self {
+        BuildSystem {
+            requires: vec!["setuptools>=40.8.0".to_string()],
+            build_backend: None,
+            backend_path: Vec::new(),
+        }
+    }
+}
+
+impl BuildSystem {
+    /// The backend object to import, as `module:object`.
+    pub fn backend(&self) -> &str {
+        self.build_backend
+            .as_deref()
+            .unwrap_or("setuptools.build_meta:__legacy__")
+    }
+}
+
+/// The `[project]` table, as specified by PEP 621.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Project {
+    pub requires_python: Option<String>,
+
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+
+    #[serde(default)]
+    pub optional_dependencies: BTreeMap<String, Vec<String>>,
+}
+
+impl PyProject {
+    pub async fn read(path: &Path) -> eyre::Result<Self> {
+        let contents = tokio::fs::read_to_string(path).await?;
+
+        toml::from_str(&contents)
+            .map_err(|e| eyre::eyre!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    /// The parsed `requires-python` constraint of the project, if any.
+    pub fn requires_python(&self) -> eyre::Result<Option<SpecifierSet>> {
+        match self
+            .project
+            .as_ref()
+            .and_then(|project| project.requires_python.as_ref())
+        {
+            Some(requires) => {
+                Ok(Some(requires.parse().map_err(|e| {
+                    eyre::eyre!("Invalid requires-python '{}': {}", requires, e)
+                })?))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Finds the nearest `pyproject.toml`, starting at `start` and walking up.
+pub fn find_pyproject(start: &Path) -> Option<PathBuf> {
+    crate::find_upwards(start, "pyproject.toml")
+}
+
+/// Records `requirement` in `[project].dependencies` of the `pyproject.toml` contents, or in
+/// the `group` table of `[project.optional-dependencies]`, replacing any requirement on the
+/// same distribution. Returns the edited file, keeping its formatting and comments.
+pub fn add_dependency(
+    contents: &str,
+    path: &Path,
+    requirement: &str,
+    group: Option<&str>,
+) -> eyre::Result<String> {
+    let name = requirement.parse::<Requirement>()?.normalized_name();
+
+    edit_dependencies(contents, path, group, |dependencies| {
+        set_requirement(dependencies, requirement, &name);
+    })
+}
+
+/// Removes the requirement on `name` from the dependencies of the `pyproject.toml` contents,
+/// returning the edited file and whether there was one.
+pub fn remove_dependency(
+    contents: &str,
+    path: &Path,
+    name: &str,
+    group: Option<&str>,
+) -> eyre::Result<(String, bool)> {
+    let name = normalize_name(name);
+    let mut removed = false;
+
+    let contents = edit_dependencies(contents, path, group, |dependencies| {
+        removed = remove_requirement(dependencies, &name);
+    })?;
+
+    Ok((contents, removed))
+}
+
+/// Replaces the requirement on the distribution `name` in `dependencies` with `requirement`,
+/// or appends it when there's none.
+pub fn set_requirement(dependencies: &mut toml_edit::Array, requirement: &str, name: &str) {
+    let position = dependencies
+        .iter()
+        .position(|value| same_distribution(value, name));
+
+    match position {
+        Some(index) => {
+            let decor = dependencies.get(index).map(|value| value.decor().clone());
+            dependencies.replace(index, requirement);
+            if let (Some(decor), Some(value)) = (decor, dependencies.get_mut(index)) {
+                *value.decor_mut() = decor;
+            }
+        }
+        None => {
+            // Put new entries on their own line when the array already spans several, after
+            // a comment trailing the last one.
+            let prefix = dependencies
+                .iter()
+                .last()
+                .and_then(|value| value.decor().prefix()?.as_str())
+                .and_then(|prefix| prefix.rfind('\n').map(|index| prefix[index..].to_string()));
+            let trailing = dependencies
+                .trailing()
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+
+            dependencies.push(requirement);
+            if let (Some(prefix), Some(index)) = (prefix, trailing.find('\n')) {
+                if let Some(value) = dependencies.iter_mut().last() {
+                    value
+                        .decor_mut()
+                        .set_prefix(format!("{}{}", &trailing[..index], prefix));
+                }
+                dependencies.set_trailing(&trailing[index..]);
+            }
+        }
+    }
+}
+
+/// Removes the requirements on the distribution `name` from `dependencies`, returning whether
+/// there were any. Comments trailing a removed entry go with it; the others are kept.
+fn remove_requirement(dependencies: &mut toml_edit::Array, name: &str) -> bool {
+    let mut removed = false;
+
+    loop {
+        let position = dependencies
+            .iter()
+            .position(|value| same_distribution(value, name));
+        let Some(index) = position else {
+            break;
+        };
+
+        let prefix = dependencies
+            .get(index)
+            .and_then(|value| value.decor().prefix()?.as_str())
+            .unwrap_or_default()
+            .to_string();
+        // The first line of the following decor is the rest of the removed entry's line.
+        let head = &prefix[..prefix.find('\n').unwrap_or(prefix.len())];
+
+        dependencies.remove(index);
+        removed = true;
+
+        match dependencies.get_mut(index) {
+            Some(next) => {
+                let next_prefix = next.decor().prefix().and_then(|p| p.as_str()).unwrap_or("");
+                let rest = match next_prefix.find('\n') {
+                    Some(newline) => format!("{}{}", head, &next_prefix[newline..]),
+                    None => prefix.clone(),
+                };
+                next.decor_mut().set_prefix(rest);
+            }
+            None => {
+                let trailing = dependencies
+                    .trailing()
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                if let Some(newline) = trailing.find('\n') {
+                    let trailing = format!("{}{}", head, &trailing[newline..]);
+                    dependencies.set_trailing(trailing);
+                }
+                if dependencies.is_empty() {
+                    dependencies.set_trailing_comma(false);
+                }
+            }
+        }
+    }
+
+    removed
+}
+
+fn same_distribution(value: &toml_edit::Value, name: &str) -> bool {
+    value
+        .as_str()
+        .and_then(|requirement| requirement.parse::<Requirement>().ok())
+        .is_some_and(|requirement| requirement.normalized_name() == name)
+}
+
+/// Applies `edit` to the dependency array selected by `group` in the `pyproject.toml` contents,
+/// creating it when missing, and returns the edited file.
+fn edit_dependencies(
+    contents: &str,
+    path: &Path,
+    group: Option<&str>,
+    edit: impl FnOnce(&mut toml_edit::Array),
+) -> eyre::Result<String> {
+    let mut document = contents
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| eyre::eyre!("Failed to parse {}: {}", path.display(), e))?;
+
+    let project = document
+        .get_mut("project")
+        .and_then(|project| project.as_table_like_mut())
+        .ok_or_else(|| eyre::eyre!("{} has no [project] table", path.display()))?;
+
+    let (table, key) = match group {
+        Some(group) => {
+            if !project.contains_key("optional-dependencies") {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(false);
+                project.insert("optional-dependencies", toml_edit::Item::Table(table));
+            }
+
+            let table = project
+                .get_mut("optional-dependencies")
+                .and_then(|table| table.as_table_like_mut())
+                .ok_or_else(|| {
+                    eyre::eyre!(
+                        "[project.optional-dependencies] of {} isn't a table",
+                        path.display()
+                    )
+                })?;
+            (table, group)
+        }
+        None => (project, "dependencies"),
+    };
+
+    if !table.contains_key(key) {
+        table.insert(key, toml_edit::value(toml_edit::Array::new()));
+    }
+
+    let dependencies = table
+        .get_mut(key)
+        .and_then(|item| item.as_array_mut())
+        .ok_or_else(|| eyre::eyre!("'{}' of {} isn't an array", key, path.display()))?;
+
+    edit(dependencies);
+
+    Ok(document.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(contents: &str, requirement: &str, group: Option<&str>) -> String {
+        add_dependency(contents, Path::new("pyproject.toml"), requirement, group).unwrap()
+    }
+
+    fn remove(contents: &str, name: &str) -> (String, bool) {
+        remove_dependency(contents, Path::new("pyproject.toml"), name, None).unwrap()
+    }
+
+    #[test]
+    fn appends_to_single_line_array() {
+        let contents = "[project]\nname = \"demo\"\ndependencies = [\"a\", \"b>=1\"]  # runtime\n";
+
+        assert_eq!(
+            add(contents, "c", None),
+            "[project]\nname = \"demo\"\ndependencies = [\"a\", \"b>=1\", \"c\"]  # runtime\n"
+        );
+        assert_eq!(
+            add("[project]\ndependencies = []\n", "a", None),
+            "[project]\ndependencies = [\"a\"]\n"
+        );
+    }
+
+    #[test]
+    fn keeps_multi_line_layout_and_comments() {
+        let contents = "# demo\n[project]\ndependencies = [\n    # web\n    \"requests>=2\",\n    \"rich\",  # output\n]\n";
+
+        assert_eq!(
+            add(contents, "click", None),
+            "# demo\n[project]\ndependencies = [\n    # web\n    \"requests>=2\",\n    \"rich\",  # output\n    \"click\",\n]\n"
+        );
+        assert_eq!(
+            add(contents, "Requests[socks]>=2.31", None),
+            "# demo\n[project]\ndependencies = [\n    # web\n    \"Requests[socks]>=2.31\",\n    \"rich\",  # output\n]\n"
+        );
+    }
+
+    #[test]
+    fn adds_optional_dependencies() {
+        let contents = "[project]\nname = \"demo\"\n";
+
+        let edited = add(contents, "pytest", Some("test"));
+        let pyproject: PyProject = toml::from_str(&edited).unwrap();
+        assert_eq!(
+            pyproject.project.unwrap().optional_dependencies["test"],
+            vec!["pytest".to_string()]
+        );
+    }
+
+    #[test]
+    fn removes_dependencies() {
+        let contents =
+            "[project]\ndependencies = [\n    \"requests>=2\",  # web\n    \"rich\",\n]\n";
+
+        let (edited, removed) = remove(contents, "Requests");
+        assert!(removed);
+        assert_eq!(edited, "[project]\ndependencies = [\n    \"rich\",\n]\n");
+
+        let (edited, removed) = remove(contents, "click");
+        assert!(!removed);
+        assert_eq!(edited, contents);
+
+        let contents =
+            "[project]\ndependencies = [\n    \"a\",\n    # output\n    \"rich\",  # pinned\n]\n";
+        assert_eq!(
+            remove(contents, "rich").0,
+            "[project]\ndependencies = [\n    \"a\",\n]\n"
+        );
+        assert_eq!(
+            remove(contents, "a").0,
+            "[project]\ndependencies = [\n    # output\n    \"rich\",  # pinned\n]\n"
+        );
+        assert_eq!(
+            remove("[project]\ndependencies = [\"a\", \"b\", \"c\"]\n", "b").0,
+            "[project]\ndependencies = [\"a\", \"c\"]\n"
+        );
+    }
+
+    #[test]
+    fn rejects_files_without_project() {
+        assert!(add_dependency("[tool.x]\n", Path::new("pyproject.toml"), "a", None).is_err());
+    }
+}
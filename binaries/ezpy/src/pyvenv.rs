@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+
+const ACTIVATE: &str = include_str!("scripts/activate");
+const ACTIVATE_FISH: &str = include_str!("scripts/activate.fish");
+const ACTIVATE_CSH: &str = include_str!("scripts/activate.csh");
+const ACTIVATE_PS1: &str = include_str!("scripts/Activate.ps1");
+const ACTIVATE_BAT: &str = include_str!("scripts/activate.bat");
+const DEACTIVATE_BAT: &str = include_str!("scripts/deactivate.bat");
+
+/// Represents the `pyvenv.cfg` file found at the root of every virtual environment.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PyvenvConfig {
+    /// The directory containing the base interpreter.
+    pub home: PathBuf,
+
+    pub include_system_site_packages: bool,
+
+    /// The full version of the base interpreter (e.g. 3.12.7).
+    pub version: VersionString,
+
+    /// The base interpreter the environment was created from.
+    pub executable: PathBuf,
+
+    pub prompt: Option<String>,
+}
+
+impl std::fmt::Display for PyvenvConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "home = {}", self.home.display())?;
+        writeln!(
+            f,
+            "include-system-site-packages = {}",
+            self.include_system_site_packages
+        )?;
+        writeln!(f, "version = {}", self.version)?;
+        writeln!(f, "executable = {}", self.executable.display())?;
+        if let Some(prompt) = &self.prompt {
+            writeln!(f, "prompt = {}", prompt)?;
+        }
+        writeln!(f, "ezpy = {}", env!("CARGO_PKG_VERSION"))
+    }
+}
+
+impl PyvenvConfig {
+    /// Parses a `pyvenv.cfg`, accepting the keys written by ezpy, `python -m venv` and uv.
+    pub fn parse(contents: &str) -> Self {
+        let mut config = PyvenvConfig::default();
+
+        for line in contents.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            let value = value.trim();
+            match key.trim() {
+                "home" => config.home = PathBuf::from(value),
+                "include-system-site-packages" => {
+                    config.include_system_site_packages = value.eq_ignore_ascii_case("true")
+                }
+                "version" | "version_info" => config.version = value.to_string(),
+                "executable" | "base-executable" => config.executable = PathBuf::from(value),
+                "prompt" => config.prompt = Some(value.trim_matches(['\'', '"']).to_string()),
+                _ => {}
+            }
+        }
+
+        if config.executable.as_os_str().is_empty() && !config.home.as_os_str().is_empty() {
+            config.executable = config.home.join(if cfg!(windows) {
+                "python.exe"
+            } else {
+                "python3"
+            });
+        }
+
+        config
+    }
+
+    pub async fn read(env: &Path) -> eyre::Result<Self> {
+        let path = env.join("pyvenv.cfg");
+        let contents = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+
+        Ok(PyvenvConfig::parse(&contents))
+    }
+
+    pub async fn write(&self, env: &Path) -> eyre::Result<()> {
+        tokio::fs::write(env.join("pyvenv.cfg"), self.to_string()).await?;
+
+        Ok(())
+    }
+
+    /// The site-packages directory of the environment at `env`.
+    pub fn site_packages(&self, env: &Path) -> PathBuf {
+        env.join(crate::site_packages_path(&self.version))
+    }
+}
+
+/// Creates a virtual environment at `dir` for the base interpreter `base`, without spawning Python.
+pub async fn create(
+    base: &Path,
+    version: &VersionString,
+    dir: &Path,
+    prompt: &str,
+) -> eyre::Result<()> {
+    let executable = tokio::fs::canonicalize(base).await?;
+    let home = executable
+        .parent()
+        .ok_or_else(|| eyre::eyre!("Invalid interpreter path {}", executable.display()))?
+        .to_path_buf();
+
+    let scripts = dir.join(crate::env_scripts_path());
+    tokio::fs::create_dir_all(&scripts).await?;
+    tokio::fs::create_dir_all(dir.join(crate::site_packages_path(version))).await?;
+    tokio::fs::create_dir_all(dir.join(if cfg!(windows) { "Include" } else { "include" })).await?;
+
+    link_interpreter(&executable, version, &scripts).await?;
+    write_activation_scripts(dir, prompt).await?;
+
+    let config = PyvenvConfig {
+        home,
+        include_system_site_packages: false,
+        version: version.clone(),
+        executable,
+        prompt: Some(prompt.to_string()),
+    };
+
+    config.write(dir).await
+}
+
+#[cfg(unix)]
+async fn link_interpreter(
+    executable: &Path,
+    version: &VersionString,
+    scripts: &Path,
+) -> eyre::Result<()> {
+    tokio::fs::symlink(executable, scripts.join("python")).await?;
+    tokio::fs::symlink("python", scripts.join("python3")).await?;
+    tokio::fs::symlink(
+        "python",
+        scripts.join(format!("python{}", short_version(version))),
+    )
+    .await?;
+
+    // Mirror `python -m venv`, which adds a `lib64` alias on 64-bit Linux.
+    let env = scripts.parent().unwrap();
+    if cfg!(all(target_os = "linux", target_pointer_width = "64")) && !env.join("lib64").exists() {
+        tokio::fs::symlink("lib", env.join("lib64")).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn link_interpreter(
+    executable: &Path,
+    _version: &VersionString,
+    scripts: &Path,
+) -> eyre::Result<()> {
+    // Windows interpreters ship a launcher that reads `pyvenv.cfg` to find the base
+    // installation; older ones name it after the interpreter itself.
+    let home = executable.parent().unwrap();
+    let launchers = home.join("Lib").join("venv").join("scripts").join("nt");
+
+    for (launcher, legacy, name) in [
+        ("venvlauncher.exe", "python.exe", "python.exe"),
+        ("venvwlauncher.exe", "pythonw.exe", "pythonw.exe"),
+    ] {
+        let source = if launchers.join(launcher).exists() {
+            launchers.join(launcher)
+        } else if launchers.join(legacy).exists() {
+            launchers.join(legacy)
+        } else {
+            home.join(name)
+        };
+
+        tokio::fs::copy(&source, scripts.join(name)).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn write_activation_scripts(dir: &Path, prompt: &str) -> eyre::Result<()> {
+    let scripts = dir.join(crate::env_scripts_path());
+    let bin_name = crate::env_scripts_path();
+
+    let templates: &[(&str, &str)] = if cfg!(windows) {
+        &[
+            ("activate", ACTIVATE),
+            ("Activate.ps1", ACTIVATE_PS1),
+            ("activate.bat", ACTIVATE_BAT),
+            ("deactivate.bat", DEACTIVATE_BAT),
+        ]
+    } else {
+        &[
+            ("activate", ACTIVATE),
+            ("activate.fish", ACTIVATE_FISH),
+            ("activate.csh", ACTIVATE_CSH),
+            ("Activate.ps1", ACTIVATE_PS1),
+        ]
+    };
+
+    for (name, template) in templates {
+        let contents = template
+            .replace("__VENV_DIR__", &dir.display().to_string())
+            .replace("__VENV_BIN_NAME__", &bin_name.display().to_string())
+            .replace("__VENV_PROMPT__", prompt);
+
+        tokio::fs::write(scripts.join(name), contents).await?;
+    }
+
+    Ok(())
+}
+
+/// Installs pip into the environment from a cached wheel, falling back to the wheel bundled
+/// with the base interpreter's `ensurepip`.
+pub async fn seed_pip(version: &VersionString, dir: &Path) -> eyre::Result<()> {
+    let cache = crate::cache_home_ezpy()?.join("wheels");
+
+    let wheel = match newest_pip_wheel(&cache).await? {
+        Some(wheel) => wheel,
+        None => {
+            let bundled = indygreg::python_path(version.clone())?
+                .join(crate::stdlib_path(version))
+                .join("ensurepip")
+                .join("_bundled");
+
+            let bundled = newest_pip_wheel(&bundled).await?.ok_or_else(|| {
+                eyre::eyre!(
+                    "No pip wheel available to seed the environment, use `--without-pip` to skip seeding"
+                )
+            })?;
+
+            tokio::fs::create_dir_all(&cache).await?;
+            let cached = cache.join(bundled.file_name().unwrap());
+            tokio::fs::copy(&bundled, &cached).await?;
+
+            cached
+        }
+    };
+
+    // A pip wheel is importable as is, so it can install itself.
+    let out = tokio::process::Command::new(dir.join(crate::env_bin_path()))
+        .arg(wheel.join("pip"))
+        .arg("install")
+        .arg("--no-index")
+        .arg("--no-deps")
+        .arg("--no-compile")
+        .arg("--quiet")
+        .arg(&wheel)
+        .output()
+        .await?;
+
+    if !out.status.success() {
+        eyre::bail!(
+            "Failed to seed pip into the virtual environment: {}",
+            String::from_utf8_lossy(&out.stderr)
+        );
+    }
+
+    Ok(())
+}
+
+async fn newest_pip_wheel(dir: &Path) -> eyre::Result<Option<PathBuf>> {
+    if !dir.exists() {
+        return Ok(None);
+    }
+
+    let mut newest: Option<(Vec<u64>, PathBuf)> = None;
+
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.starts_with("pip-") || !name.ends_with(".whl") {
+            continue;
+        }
+
+        let version = name
+            .split('-')
+            .nth(1)
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect::<Vec<u64>>();
+
+        if newest.as_ref().is_none_or(|(best, _)| version > *best) {
+            newest = Some((version, entry.path()));
+        }
+    }
+
+    Ok(newest.map(|(_, path)| path))
+}
+
+/// Returns the `major.minor` part of a full version string.
+pub fn short_version(version: &str) -> String {
+    version.split('.').take(2).collect::<Vec<_>>().join(".")
+}
@@ -0,0 +1,210 @@
+use std::path::Path;
+
+/// Copies `src` into `dst` recursively, recreating symlinks instead of following them.
+pub fn copy_dir(src: &Path, dst: &Path) -> eyre::Result<()> {
+    std::fs::create_dir_all(dst)?;
+
+    for entry in std::fs::read_dir(src)? {
+        let entry = entry?;
+        let kind = entry.file_type()?;
+        let target = dst.join(entry.file_name());
+
+        if kind.is_symlink() {
+            copy_symlink(&entry.path(), &target)?;
+        } else if kind.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dst: &Path) -> eyre::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(src)?, dst)?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(src: &Path, dst: &Path) -> eyre::Result<()> {
+    std::fs::copy(src, dst)?;
+
+    Ok(())
+}
+
+/// Replaces every mention of `old` with `new` in the environment's text scripts and
+/// `pyvenv.cfg`, which hard-code the environment's location, and drops stale bytecode.
+/// Launchers, which embed their shebang between the executable and a zip archive, get a
+/// new shebang.
+pub fn rewrite_paths(env: &Path, old: &Path, new: &Path) -> eyre::Result<()> {
+    let old = old.display().to_string();
+    let new = new.display().to_string();
+
+    let mut files = vec![env.join("pyvenv.cfg")];
+    let scripts = env.join(crate::env_scripts_path());
+    if scripts.is_dir() {
+        for entry in std::fs::read_dir(&scripts)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                files.push(entry.path());
+            }
+        }
+    }
+
+    for file in files {
+        let contents = std::fs::read(&file)?;
+
+        let rewritten = match std::str::from_utf8(&contents) {
+            Ok(text) => replace_path(text, &old, &new).map(String::into_bytes),
+            Err(_) => rewrite_launcher(&contents, &old, &new),
+        };
+
+        if let Some(rewritten) = rewritten {
+            std::fs::write(&file, rewritten)?;
+        }
+    }
+
+    remove_bytecode(env)
+}
+
+/// Replaces the mentions of the path `old` in `text` with `new`, leaving alone longer paths
+/// that merely share its prefix. Returns `None` when nothing changed.
+fn replace_path(text: &str, old: &str, new: &str) -> Option<String> {
+    let is_path_char = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut changed = false;
+
+    while let Some(index) = rest.find(old) {
+        let before = rest[..index]
+            .chars()
+            .last()
+            .or_else(|| result.chars().last());
+        let after = rest[index + old.len()..].chars().next();
+
+        let starts = !before.is_some_and(|c| is_path_char(c) || matches!(c, '/' | '\\'));
+        let ends = !after.is_some_and(is_path_char);
+
+        result.push_str(&rest[..index]);
+        if starts && ends {
+            result.push_str(new);
+            changed = true;
+        } else {
+            result.push_str(old);
+        }
+        rest = &rest[index + old.len()..];
+    }
+
+    result.push_str(rest);
+
+    changed.then_some(result)
+}
+
+/// Rewrites the shebang of a launcher executable: the launcher, a `#!` line and the zip
+/// archive of the script, which Python finds from the end of the file.
+fn rewrite_launcher(contents: &[u8], old: &str, new: &str) -> Option<Vec<u8>> {
+    let (start, end) = contents
+        .windows(2)
+        .enumerate()
+        .filter(|(_, window)| *window == b"#!")
+        .map(|(start, _)| start)
+        .collect::<Vec<_>>()
+        .into_iter()
+        .rev()
+        .find_map(|start| {
+            let end = start + contents[start..].iter().position(|byte| *byte == b'\n')? + 1;
+            contents[end..]
+                .starts_with(b"PK\x03\x04")
+                .then_some((start, end))
+        })?;
+
+    let shebang = std::str::from_utf8(&contents[start..end]).ok()?;
+    let shebang = replace_path(shebang, old, new)?;
+
+    let mut rewritten = contents[..start].to_vec();
+    rewritten.extend_from_slice(shebang.as_bytes());
+    rewritten.extend_from_slice(&contents[end..]);
+
+    Some(rewritten)
+}
+
+/// Removes compiled bytecode below `dir`, which records the source paths it was compiled
+/// from. Python regenerates it on the next import.
+pub fn remove_bytecode(dir: &Path) -> eyre::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        if entry.file_name() == "__pycache__" {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            remove_bytecode(&entry.path())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Changes the prompt recorded in `pyvenv.cfg` and used by the activation scripts.
+pub async fn set_prompt(env: &Path, prompt: &str) -> eyre::Result<()> {
+    let path = env.join("pyvenv.cfg");
+    let contents = tokio::fs::read_to_string(&path).await?;
+
+    let mut found = false;
+    let mut lines = contents
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((key, _)) if key.trim() == "prompt" => {
+                found = true;
+                format!("prompt = {}", prompt)
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    if !found {
+        lines.push(format!("prompt = {}", prompt));
+    }
+
+    tokio::fs::write(&path, lines.join("\n") + "\n").await?;
+
+    crate::pyvenv::write_activation_scripts(env, prompt).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_whole_paths_only() {
+        let text = "#!/tmp/env/bin/python\nVIRTUAL_ENV=\"/tmp/env\"\n/tmp/env2/bin /x/tmp/env\n";
+
+        assert_eq!(
+            replace_path(text, "/tmp/env", "/opt/new").as_deref(),
+            Some("#!/opt/new/bin/python\nVIRTUAL_ENV=\"/opt/new\"\n/tmp/env2/bin /x/tmp/env\n")
+        );
+        assert_eq!(replace_path("/tmp/envs/a", "/tmp/env", "/opt/new"), None);
+    }
+
+    #[test]
+    fn rewrites_launcher_shebang() {
+        let mut launcher = b"MZ\x90\x00#!not a shebang\x00".to_vec();
+        launcher.extend_from_slice(b"#!\"C:\\envs\\old\\Scripts\\python.exe\"\r\n");
+        launcher.extend_from_slice(b"PK\x03\x04zipped");
+
+        let rewritten = rewrite_launcher(&launcher, "C:\\envs\\old", "C:\\envs\\new").unwrap();
+
+        let mut expected = b"MZ\x90\x00#!not a shebang\x00".to_vec();
+        expected.extend_from_slice(b"#!\"C:\\envs\\new\\Scripts\\python.exe\"\r\n");
+        expected.extend_from_slice(b"PK\x03\x04zipped");
+        assert_eq!(rewritten, expected);
+
+        assert_eq!(rewrite_launcher(&launcher, "C:\\other", "C:\\new"), None);
+    }
+}
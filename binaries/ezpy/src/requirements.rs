@@ -0,0 +1,699 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::pep508::{InterpreterInfo, MarkerEnvironment, MarkerTree, Requirement};
+
+/// Options pip accepts in requirements files that don't change what gets installed here.
+const IGNORED_OPTIONS: &[&str] = &[
+    "--pre",
+    "--prefer-binary",
+    "--only-binary",
+    "--no-binary",
+    "--trusted-host",
+    "--use-feature",
+    "--require-hashes",
+];
+
+/// A requirements file, along with the files it includes.
+#[derive(Debug, Clone, Default)]
+pub struct RequirementsFile {
+    pub requirements: Vec<Entry>,
+
+    /// Entries of the files included with `-c`, which limit versions without requiring.
+    pub constraints: Vec<Entry>,
+
+    pub index_url: Option<String>,
+    pub extra_index_urls: Vec<String>,
+    pub find_links: Vec<PathBuf>,
+    pub no_index: bool,
+
+    /// Every file read, starting with the top-level one.
+    pub files: Vec<PathBuf>,
+
+    pub errors: Vec<ParseError>,
+
+    /// Options left to pip, such as `--config-settings`, which ezpy itself ignores.
+    pub warnings: Vec<ParseError>,
+}
+
+/// One requirement line.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub spec: Spec,
+
+    /// Set for `-e`/`--editable` entries.
+    pub editable: bool,
+
+    /// The `--hash` options of the line, as `algorithm:digest`.
+    pub hashes: Vec<String>,
+
+    pub file: PathBuf,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub enum Spec {
+    /// A PEP 508 requirement, on a distribution from the index or a `name @ url` reference.
+    Named(Requirement),
+
+    /// A bare URL or a local path.
+    Direct {
+        url: String,
+        marker: Option<MarkerTree>,
+    },
+}
+
+impl Entry {
+    /// Whether the entry's marker holds in `env`.
+    pub fn applies(&self, env: &MarkerEnvironment) -> bool {
+        match &self.spec {
+            Spec::Named(requirement) => requirement.evaluate(env, &[]),
+            Spec::Direct { marker, .. } => marker
+                .as_ref()
+                .is_none_or(|marker| marker.evaluate(env, &[])),
+        }
+    }
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Spec::Named(requirement) => write!(f, "{}", requirement),
+            Spec::Direct { url, marker } => {
+                write!(f, "{}", url)?;
+                if let Some(marker) = marker {
+                    write!(f, " ; {}", marker)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A syntax error, located by file and line number.
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+impl RequirementsFile {
+    /// Reads `path` and the files it includes. Syntax errors are collected in `errors`
+    /// rather than failing, so they can all be reported at once.
+    pub fn read(path: &Path) -> eyre::Result<Self> {
+        let mut file = RequirementsFile::default();
+        file.include(path, false, None)?;
+
+        Ok(file)
+    }
+
+    /// Fails with every syntax error when there are any.
+    pub fn check(&self) -> eyre::Result<()> {
+        if self.errors.is_empty() {
+            return Ok(());
+        }
+
+        let errors = self
+            .errors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        eyre::bail!("Invalid requirements file:\n{}", errors)
+    }
+
+    /// The requirements whose marker holds in `env`.
+    pub fn applicable(&self, env: &MarkerEnvironment) -> Vec<&Entry> {
+        self.requirements
+            .iter()
+            .filter(|entry| entry.applies(env))
+            .collect()
+    }
+
+    fn include(
+        &mut self,
+        path: &Path,
+        constraint: bool,
+        from: Option<(&Path, usize)>,
+    ) -> eyre::Result<()> {
+        // Files included from several places, or recursively, are only read once.
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.files.contains(&canonical) {
+            return Ok(());
+        }
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => match from {
+                Some((file, line)) => {
+                    self.error(file, line, format!("can't read {}: {}", path.display(), e));
+                    return Ok(());
+                }
+                None => eyre::bail!("Failed to read {}: {}", path.display(), e),
+            },
+        };
+        self.files.push(canonical);
+
+        for (number, line) in logical_lines(&contents) {
+            if let Err(message) = self.parse_line(path, number, &line, constraint) {
+                self.error(path, number, message);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn error(&mut self, file: &Path, line: usize, message: String) {
+        self.errors.push(ParseError {
+            file: file.to_path_buf(),
+            line,
+            message,
+        });
+    }
+
+    fn warn(&mut self, file: &Path, line: usize, option: &str) {
+        self.warnings.push(ParseError {
+            file: file.to_path_buf(),
+            line,
+            message: format!("option '{}' is only applied by pip", option),
+        });
+    }
+
+    fn parse_line(
+        &mut self,
+        path: &Path,
+        number: usize,
+        line: &str,
+        constraint: bool,
+    ) -> Result<(), String> {
+        let line = expand_env_vars(line);
+        let base = path.parent().unwrap_or(Path::new("."));
+
+        if line.starts_with('-') && !line.starts_with("-e") && !line.starts_with("--editable") {
+            let (option, value) = split_option(&line);
+
+            match option {
+                "-r" | "--requirement" | "-c" | "--constraint" => {
+                    let value = value.ok_or_else(|| format!("{} expects a file", option))?;
+                    let nested = constraint || matches!(option, "-c" | "--constraint");
+                    self.include(&base.join(value), nested, Some((path, number)))
+                        .map_err(|e| e.to_string())?;
+                }
+                "-i" | "--index-url" => {
+                    self.index_url = Some(value.ok_or("--index-url expects a URL")?.to_string());
+                }
+                "--extra-index-url" => self
+                    .extra_index_urls
+                    .push(value.ok_or("--extra-index-url expects a URL")?.to_string()),
+                "-f" | "--find-links" => self
+                    .find_links
+                    .push(base.join(value.ok_or("--find-links expects a directory")?)),
+                "--no-index" => self.no_index = true,
+                option if IGNORED_OPTIONS.contains(&option) => {}
+                option => self.warn(path, number, option),
+            }
+
+            return Ok(());
+        }
+
+        let (editable, line) = match split_option(&line) {
+            ("-e" | "--editable", value) => {
+                (true, value.ok_or("--editable expects a path or URL")?)
+            }
+            _ => (false, line.as_str()),
+        };
+
+        let (spec, hashes, ignored) = split_options(line)?;
+        for option in ignored {
+            self.warn(path, number, option);
+        }
+        let spec = parse_spec(spec, base)?;
+
+        if editable && matches!(spec, Spec::Named(_)) {
+            return Err("editable requirements must be a local path or a VCS URL".to_string());
+        }
+
+        let entry = Entry {
+            spec,
+            editable,
+            hashes,
+            file: path.to_path_buf(),
+            line: number,
+        };
+
+        if constraint {
+            if entry.editable {
+                return Err("constraints can't be editable".to_string());
+            }
+            self.constraints.push(entry);
+        } else {
+            self.requirements.push(entry);
+        }
+
+        Ok(())
+    }
+}
+
+/// Joins backslash continuations and strips comments, yielding non-empty lines along with
+/// the number of the line they start on.
+fn logical_lines(contents: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (index, raw) in contents.lines().enumerate() {
+        let (start, mut text) = current.take().unwrap_or((index + 1, String::new()));
+
+        if let Some(continued) = raw.strip_suffix('\\') {
+            text.push_str(continued);
+            current = Some((start, text));
+            continue;
+        }
+        text.push_str(raw);
+
+        let text = strip_comment(&text).trim().to_string();
+        if !text.is_empty() {
+            lines.push((start, text));
+        }
+    }
+
+    if let Some((start, text)) = current {
+        let text = strip_comment(&text).trim().to_string();
+        if !text.is_empty() {
+            lines.push((start, text));
+        }
+    }
+
+    lines
+}
+
+/// Comments start with `#` at the beginning of a line or after whitespace, so URL fragments
+/// survive.
+fn strip_comment(line: &str) -> &str {
+    if line.trim_start().starts_with('#') {
+        return "";
+    }
+
+    line.char_indices()
+        .find(|(index, c)| *c == '#' && line[..*index].ends_with(char::is_whitespace))
+        .map(|(index, _)| &line[..index])
+        .unwrap_or(line)
+}
+
+/// Expands `${NAME}` references to environment variables, as pip does.
+fn expand_env_vars(line: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let name = &rest[start + 2..start + end];
+
+        expanded.push_str(&rest[..start]);
+        match std::env::var(name) {
+            Ok(value) => expanded.push_str(&value),
+            Err(_) => expanded.push_str(&rest[start..start + end + 1]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+
+    expanded
+}
+
+/// Splits `--option value`, `--option=value` and `-ovalue` forms.
+fn split_option(line: &str) -> (&str, Option<&str>) {
+    let (option, value) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+        Some(end) => (&line[..end], Some(line[end + 1..].trim())),
+        None => (line, None),
+    };
+
+    if !option.starts_with("--") && option.len() > 2 {
+        return (&option[..2], Some(line[2..].trim()));
+    }
+
+    (option, value.filter(|value| !value.is_empty()))
+}
+
+/// Separates the trailing per-requirement options from the requirement, returning the
+/// `--hash=algorithm:digest` values and the names of the other options, which are ignored.
+fn split_options(line: &str) -> Result<(&str, Vec<String>, Vec<&str>), String> {
+    let Some(start) = line.find(" --").map(|start| start + 1) else {
+        return Ok((line, Vec::new(), Vec::new()));
+    };
+
+    let mut hashes = Vec::new();
+    let mut ignored = Vec::new();
+    let mut words = line[start..].split_whitespace().peekable();
+    while let Some(word) = words.next() {
+        let (option, inline) = match word.split_once('=') {
+            Some((option, value)) => (option, Some(value)),
+            None => (word, None),
+        };
+        if !option.starts_with("--") {
+            return Err(format!(
+                "unexpected '{}' after the requirement options",
+                word
+            ));
+        }
+
+        let value = match inline {
+            Some(value) => Some(value),
+            None => words.next_if(|next| !next.starts_with("--")),
+        };
+
+        if option != "--hash" {
+            ignored.push(option);
+            continue;
+        }
+
+        let value = value.ok_or("--hash expects a digest")?;
+
+        match value.split_once(':') {
+            Some((algorithm, digest))
+                if matches!(algorithm, "sha256" | "sha384" | "sha512")
+                    && !digest.is_empty()
+                    && digest.chars().all(|c| c.is_ascii_hexdigit()) =>
+            {
+                hashes.push(value.to_string())
+            }
+            _ => return Err(format!("invalid hash '{}'", value)),
+        }
+    }
+
+    Ok((line[..start].trim(), hashes, ignored))
+}
+
+fn parse_spec(spec: &str, base: &Path) -> Result<Spec, String> {
+    let parse_requirement = |spec: &str| {
+        spec.parse::<Requirement>()
+            .map(Spec::Named)
+            .map_err(|e| format!("invalid requirement: {}", e))
+    };
+
+    // `name @ url` is a PEP 508 requirement, while the `@` of a bare URL, as in
+    // `git+https://host/repo@tag`, comes after its scheme or path.
+    if spec
+        .find('@')
+        .is_some_and(|at| !spec[..at].contains(['/', ':']))
+    {
+        return parse_requirement(spec);
+    }
+
+    let (body, marker) = match spec.split_once(';') {
+        // A URL may contain `;` as long as it isn't followed by whitespace.
+        Some((body, marker)) if !is_url(spec) || body.ends_with(' ') => {
+            let marker = marker
+                .trim()
+                .parse::<MarkerTree>()
+                .map_err(|e| format!("invalid marker: {}", e))?;
+            (body.trim(), Some(marker))
+        }
+        _ => (spec.trim(), None),
+    };
+
+    if is_url(body) {
+        return Ok(Spec::Direct {
+            url: body.to_string(),
+            marker,
+        });
+    }
+
+    if is_path(body) {
+        return Ok(Spec::Direct {
+            url: base.join(body).display().to_string(),
+            marker,
+        });
+    }
+
+    parse_requirement(spec)
+}
+
+fn is_url(spec: &str) -> bool {
+    spec.split_once("://").is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+fn is_path(spec: &str) -> bool {
+    spec.starts_with('.')
+        || spec.starts_with('/')
+        || spec.contains(std::path::MAIN_SEPARATOR)
+        || spec.ends_with(".whl")
+        || spec.ends_with(".tar.gz")
+        || spec.ends_with(".zip")
+}
+
+/// Parses `path` and reports syntax errors with their line numbers, along with which
+/// requirements apply to the interpreter of the environment.
+pub async fn check(
+    path: &Path,
+    global: Option<String>,
+    python: Option<String>,
+) -> eyre::Result<()> {
+    let file = RequirementsFile::read(path)?;
+
+    for error in &file.errors {
+        println!("{}", error);
+    }
+    for warning in &file.warnings {
+        println!("Warning: {}", warning);
+    }
+
+    let python = match crate::venv::resolve_env(global.clone()).await {
+        Ok(env) => env.join(crate::env_bin_path()),
+        Err(e) if global.is_some() => return Err(e),
+        Err(_) => indygreg::python_path(crate::pin::resolve_version(python).await?.version)?
+            .join(crate::python_bin_path()),
+    };
+    let info = InterpreterInfo::query(&python).await?;
+    let version = &info.python_full_version;
+    let markers = info.markers();
+
+    let applicable = file.applicable(&markers);
+    println!(
+        "{} requirement(s), {} for Python {}, and {} constraint(s) in {} file(s)",
+        file.requirements.len(),
+        applicable.len(),
+        version,
+        file.constraints.len(),
+        file.files.len()
+    );
+
+    for entry in &file.requirements {
+        if !entry.applies(&markers) {
+            println!(
+                "Skipping {} ({}:{}), its marker doesn't match",
+                entry.spec,
+                entry.file.display(),
+                entry.line
+            );
+        }
+    }
+
+    if !file.errors.is_empty() {
+        eyre::bail!("{} error(s) found in {}", file.errors.len(), path.display());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wheel::tests::temp_dir;
+
+    fn read(dir: &Path, files: &[(&str, &str)]) -> RequirementsFile {
+        for (name, contents) in files {
+            std::fs::write(dir.join(name), contents).unwrap();
+        }
+        RequirementsFile::read(&dir.join(files[0].0)).unwrap()
+    }
+
+    fn specs(entries: &[Entry]) -> Vec<String> {
+        entries.iter().map(|entry| entry.spec.to_string()).collect()
+    }
+
+    #[test]
+    fn joins_continuations_and_strips_comments() {
+        let dir = temp_dir("requirements-lines");
+        let file = read(
+            &dir,
+            &[(
+                "requirements.txt",
+                "# a comment\n\
+                 requests>=2 \\\n    ; python_version >= '3.8'  # trailing\n\
+                 \n\
+                 pkg @ https://example.com/pkg.whl#sha256=abc\n\
+                 other==1.0 # not a fragment\n",
+            )],
+        );
+
+        assert!(file.errors.is_empty(), "{:?}", file.errors);
+        assert_eq!(
+            specs(&file.requirements),
+            [
+                "requests>=2; python_version >= \"3.8\"",
+                "pkg @ https://example.com/pkg.whl#sha256=abc",
+                "other==1.0",
+            ]
+        );
+        let lines: Vec<_> = file.requirements.iter().map(|entry| entry.line).collect();
+        assert_eq!(lines, [2, 5, 6]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn nests_requirements_and_constraints() {
+        let dir = temp_dir("requirements-nesting");
+        let file = read(
+            &dir,
+            &[
+                (
+                    "requirements.txt",
+                    "-r dev.txt\n-r test.txt\n-c constraints.txt\nmain\n",
+                ),
+                ("dev.txt", "-r common.txt\ndev\n"),
+                ("test.txt", "-r common.txt\ntest\n"),
+                ("common.txt", "common\n"),
+                ("constraints.txt", "-r pinned.txt\nmain<2\n"),
+                ("pinned.txt", "common==1.0\n"),
+            ],
+        );
+
+        // common.txt is included twice, but only read once.
+        assert!(file.errors.is_empty(), "{:?}", file.errors);
+        assert_eq!(specs(&file.requirements), ["common", "dev", "test", "main"]);
+        assert_eq!(specs(&file.constraints), ["common==1.0", "main<2"]);
+        assert_eq!(file.files.len(), 6);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn parses_hashes_editables_and_direct_references() {
+        let dir = temp_dir("requirements-entries");
+        let digest = "ab".repeat(32);
+        let contents = format!(
+            "demo==1.0 --hash=sha256:{digest} \\\n    --hash sha256:{digest}\n\
+             -e ./local\n\
+             name[extra] @ https://example.com/name.whl ; sys_platform == 'linux'\n\
+             --config-settings key=value\n\
+             built==2.0 --config-settings=key=value --global-option build\n"
+        );
+        let file = read(&dir, &[("requirements.txt", &contents)]);
+
+        assert!(file.errors.is_empty(), "{:?}", file.errors);
+        assert_eq!(
+            file.requirements[0].hashes,
+            [format!("sha256:{}", digest), format!("sha256:{}", digest)]
+        );
+
+        assert!(file.requirements[1].editable);
+        assert_eq!(
+            file.requirements[1].spec.to_string(),
+            dir.join("./local").display().to_string()
+        );
+
+        match &file.requirements[2].spec {
+            Spec::Named(requirement) => {
+                assert_eq!(requirement.name, "name");
+                assert_eq!(requirement.extras, ["extra"]);
+                assert_eq!(
+                    requirement.url.as_deref(),
+                    Some("https://example.com/name.whl")
+                );
+                assert_eq!(
+                    requirement
+                        .marker
+                        .as_ref()
+                        .map(ToString::to_string)
+                        .as_deref(),
+                    Some("sys_platform == \"linux\"")
+                );
+            }
+            spec => panic!("unexpected {:?}", spec),
+        }
+
+        assert_eq!(
+            parse_spec("git+https://host/repo@v1;x=1", &dir)
+                .unwrap()
+                .to_string(),
+            "git+https://host/repo@v1;x=1"
+        );
+        assert!(matches!(
+            parse_spec("demo @ https://host/demo.whl;x=1", &dir),
+            Ok(Spec::Named(Requirement { url: Some(url), marker: None, .. }))
+                if url == "https://host/demo.whl;x=1"
+        ));
+
+        // Options meant for pip's build step are left to it.
+        assert_eq!(specs(&file.requirements[3..]), ["built==2.0"]);
+        let warnings: Vec<_> = file
+            .warnings
+            .iter()
+            .map(|warning| (warning.line, warning.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (5, "option '--config-settings' is only applied by pip"),
+                (6, "option '--config-settings' is only applied by pip"),
+                (6, "option '--global-option' is only applied by pip"),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn reports_errors_with_line_numbers() {
+        let dir = temp_dir("requirements-errors");
+        let file = read(
+            &dir,
+            &[
+                (
+                    "requirements.txt",
+                    "good\nbad===\n-r missing.txt\ndemo --hash=md5:abc\n-e demo\n-c extra.txt\n",
+                ),
+                ("extra.txt", "\n-e ./local\n"),
+            ],
+        );
+
+        let errors: Vec<_> = file
+            .errors
+            .iter()
+            .map(|error| (error.file.file_name().unwrap().to_owned(), error.line))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("requirements.txt".into(), 2),
+                ("requirements.txt".into(), 3),
+                ("requirements.txt".into(), 4),
+                ("requirements.txt".into(), 5),
+                ("extra.txt".into(), 2),
+            ]
+        );
+        assert_eq!(file.errors[2].message, "invalid hash 'md5:abc'");
+
+        let message = file.check().unwrap_err().to_string();
+        assert!(message.starts_with("Invalid requirements file:\n"));
+        assert!(message.contains("requirements.txt:4: invalid hash 'md5:abc'"));
+        assert_eq!(specs(&file.requirements), ["good"]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
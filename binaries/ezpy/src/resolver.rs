@@ -0,0 +1,654 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::index::DistFile;
+use crate::install::Sources;
+use crate::pep440::{SpecifierSet, Version};
+use crate::pep508::{MarkerEnvironment, Requirement};
+use crate::tags::Tags;
+use crate::wheel::{Metadata, Wheel, WheelFilename};
+
+/// How many times resolution restarts after a conflict before giving up.
+const MAX_ROUNDS: usize = 200;
+
+/// A distribution pinned by the resolver.
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub name: String,
+    pub version: Version,
+    pub extras: BTreeSet<String>,
+
+    /// Set when the package is only needed on other platforms, in which case its wheels
+    /// don't have to be compatible with this one.
+    pub foreign: bool,
+
+    /// Dependencies by normalized name, with the platform marker they're limited to.
+    pub dependencies: BTreeMap<String, Option<String>>,
+
+    /// Every wheel published for the pinned version.
+    pub wheels: Vec<DistFile>,
+
+    metadata: Metadata,
+}
+
+/// Pins a set of requirements to one version per distribution for a given interpreter.
+///
+/// Non-platform markers (Python version, extras) are evaluated for the interpreter, while
+/// dependencies limited to other platforms are kept along with their marker so the result
+/// holds everywhere. Conflicts are handled by learning the offending constraint, or ruling
+/// out the version that introduced it, and starting over.
+pub struct Resolver<'a> {
+    sources: &'a Sources,
+    tags: Tags,
+    markers: MarkerEnvironment,
+    python: Version,
+    files: HashMap<String, Vec<DistFile>>,
+    metadata: HashMap<(String, String), Metadata>,
+}
+
+struct Pending {
+    requirement: Requirement,
+    parent: Option<(String, Version)>,
+    foreign: bool,
+    marker: Option<String>,
+}
+
+/// A constraint learned from a conflict, along with the package version that introduced it.
+type Learned = (String, SpecifierSet, Option<(String, Version)>);
+
+enum Attempt {
+    Resolved(BTreeMap<String, Package>),
+    Learn(String, SpecifierSet, Option<(String, Version)>),
+    Exclude(String, Version),
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(sources: &'a Sources, python_version: &str) -> eyre::Result<Self> {
+        Ok(Resolver {
+            sources,
+            tags: Tags::for_interpreter(python_version),
+            markers: MarkerEnvironment::current(python_version),
+            python: python_version.parse()?,
+            files: HashMap::new(),
+            metadata: HashMap::new(),
+        })
+    }
+
+    /// Resolves `requirements` to packages keyed by normalized name.
+    pub async fn resolve(
+        &mut self,
+        requirements: &[Requirement],
+    ) -> eyre::Result<BTreeMap<String, Package>> {
+        let mut learned: Vec<Learned> = Vec::new();
+        let mut excluded: HashMap<String, Vec<Version>> = HashMap::new();
+
+        for _ in 0..MAX_ROUNDS {
+            match self.attempt(requirements, &learned, &excluded).await? {
+                Attempt::Resolved(packages) => return Ok(packages),
+                Attempt::Learn(name, specifiers, source) => {
+                    if crate::is_verbose() {
+                        eprintln!("ezpy: restarting resolution with {}{}", name, specifiers);
+                    }
+                    learned.push((name, specifiers, source));
+                }
+                Attempt::Exclude(name, version) => {
+                    if crate::is_verbose() {
+                        eprintln!("ezpy: ruling out {} {}", name, version);
+                    }
+                    excluded.entry(name).or_default().push(version);
+                }
+            }
+        }
+
+        eyre::bail!(
+            "Resolution didn't converge after {} attempts, the requirements are likely conflicting",
+            MAX_ROUNDS
+        )
+    }
+
+    async fn attempt(
+        &mut self,
+        requirements: &[Requirement],
+        learned: &[Learned],
+        excluded: &HashMap<String, Vec<Version>>,
+    ) -> eyre::Result<Attempt> {
+        let mut constraints: HashMap<String, Vec<SpecifierSet>> = HashMap::new();
+        for (name, specifiers, source) in learned {
+            let ruled_out = source.as_ref().is_some_and(|(parent, version)| {
+                excluded
+                    .get(parent)
+                    .is_some_and(|versions| versions.contains(version))
+            });
+            if !ruled_out {
+                constraints
+                    .entry(name.clone())
+                    .or_default()
+                    .push(specifiers.clone());
+            }
+        }
+
+        let mut packages: BTreeMap<String, Package> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+
+        for requirement in requirements {
+            if let Some(pending) = self.pending(requirement, None, false, &[]) {
+                queue.push_back(pending);
+            }
+        }
+
+        while let Some(pending) = queue.pop_front() {
+            let name = pending.requirement.normalized_name();
+            let specifiers = pending.requirement.specifiers.clone();
+            constraints
+                .entry(name.clone())
+                .or_default()
+                .push(specifiers.clone());
+
+            if let Some((parent, _)) = &pending.parent {
+                if let Some(parent) = packages.get_mut(parent) {
+                    let marker = match parent.dependencies.get(&name) {
+                        Some(existing) => merge_markers(existing, &pending.marker),
+                        None => pending.marker.clone(),
+                    };
+                    parent.dependencies.insert(name.clone(), marker);
+                }
+            }
+
+            if let Some(package) = packages.get_mut(&name) {
+                if !specifiers.contains(&package.version) {
+                    return Ok(Attempt::Learn(name, specifiers, pending.parent));
+                }
+
+                let mut changed = false;
+                if package.foreign && !pending.foreign {
+                    if !package.wheels.iter().any(|file| self.is_compatible(file)) {
+                        return Ok(Attempt::Exclude(name, package.version.clone()));
+                    }
+                    package.foreign = false;
+                    changed = true;
+                }
+
+                for extra in &pending.requirement.extras {
+                    changed |= package.extras.insert(extra.clone());
+                }
+
+                if changed {
+                    let package = package.clone();
+                    queue.extend(self.dependencies(&name, &package)?);
+                }
+
+                continue;
+            }
+
+            let constraints = constraints.get(&name).cloned().unwrap_or_default();
+            let excluded = excluded.get(&name).cloned().unwrap_or_default();
+
+            match self
+                .choose(
+                    &pending.requirement,
+                    &constraints,
+                    &excluded,
+                    pending.foreign,
+                )
+                .await?
+            {
+                Some(package) => {
+                    queue.extend(self.dependencies(&name, &package)?);
+                    packages.insert(name, package);
+                }
+                None => match pending.parent {
+                    Some((parent, version)) => return Ok(Attempt::Exclude(parent, version)),
+                    None => {
+                        let constraints = constraints
+                            .iter()
+                            .filter(|specifiers| !specifiers.is_empty())
+                            .map(ToString::to_string)
+                            .collect::<Vec<_>>();
+
+                        eyre::bail!(
+                            "No version of {} satisfies {} for Python {} on this platform",
+                            pending.requirement.name,
+                            if constraints.is_empty() {
+                                "the requirements".to_string()
+                            } else {
+                                format!("'{}'", constraints.join(", "))
+                            },
+                            self.python
+                        );
+                    }
+                },
+            }
+        }
+
+        Ok(Attempt::Resolved(packages))
+    }
+
+    /// Turns a requirement into pending work, or `None` when its marker can't hold for this
+    /// interpreter.
+    fn pending(
+        &self,
+        requirement: &Requirement,
+        parent: Option<(String, Version)>,
+        foreign: bool,
+        extras: &[String],
+    ) -> Option<Pending> {
+        let (native, marker) = match &requirement.marker {
+            None => (true, None),
+            Some(marker) => {
+                let residual = marker.restrict(&self.markers, extras)?;
+                (
+                    marker.evaluate(&self.markers, extras),
+                    residual.map(|marker| marker.to_string()),
+                )
+            }
+        };
+
+        let mut requirement = requirement.clone();
+        requirement.marker = None;
+
+        Some(Pending {
+            requirement,
+            parent,
+            foreign: foreign || !native,
+            marker,
+        })
+    }
+
+    fn dependencies(&self, name: &str, package: &Package) -> eyre::Result<Vec<Pending>> {
+        let extras = package.extras.iter().cloned().collect::<Vec<_>>();
+        let mut pending = Vec::new();
+
+        for dependency in &package.metadata.requires_dist {
+            let dependency: Requirement = dependency.parse()?;
+            let parent = Some((name.to_string(), package.version.clone()));
+
+            if let Some(dependency) = self.pending(&dependency, parent, package.foreign, &extras) {
+                pending.push(dependency);
+            }
+        }
+
+        Ok(pending)
+    }
+
+    fn is_compatible(&self, file: &DistFile) -> bool {
+        file.filename
+            .parse::<WheelFilename>()
+            .is_ok_and(|wheel| self.tags.is_compatible(&wheel))
+    }
+
+    /// Picks the newest version of `requirement` allowed by every constraint.
+    async fn choose(
+        &mut self,
+        requirement: &Requirement,
+        constraints: &[SpecifierSet],
+        excluded: &[Version],
+        foreign: bool,
+    ) -> eyre::Result<Option<Package>> {
+        if let Some(url) = &requirement.url {
+            eyre::bail!(
+                "Can't resolve {} @ {}, direct references can't be locked from the index",
+                requirement.name,
+                url
+            );
+        }
+
+        let name = requirement.normalized_name();
+
+        if !self.files.contains_key(&name) {
+            let files = self.sources.files(&requirement.name).await?;
+            self.files.insert(name.clone(), files);
+        }
+
+        let prereleases = constraints
+            .iter()
+            .any(|specifiers| specifiers.allows_prereleases());
+
+        let mut versions: BTreeMap<Version, Vec<DistFile>> = BTreeMap::new();
+        for file in &self.files[&name] {
+            let Ok(wheel) = file.filename.parse::<WheelFilename>() else {
+                continue;
+            };
+
+            if !file.is_installable(&self.python)
+                || excluded.contains(&wheel.version)
+                || !constraints
+                    .iter()
+                    .all(|specifiers| specifiers.contains(&wheel.version))
+            {
+                continue;
+            }
+
+            versions
+                .entry(wheel.version)
+                .or_default()
+                .push(file.clone());
+        }
+
+        // Pre-releases are only considered when asked for or when nothing else is left.
+        let usable =
+            |wheels: &Vec<DistFile>| foreign || wheels.iter().any(|file| self.is_compatible(file));
+        if !prereleases
+            && versions
+                .iter()
+                .any(|(version, wheels)| !version.is_prerelease() && usable(wheels))
+        {
+            versions.retain(|version, _| !version.is_prerelease());
+        }
+
+        for (version, wheels) in versions.into_iter().rev() {
+            let best = if foreign {
+                wheels.first()
+            } else {
+                wheels
+                    .iter()
+                    .filter_map(|file| {
+                        let wheel = file.filename.parse::<WheelFilename>().ok()?;
+                        Some((self.tags.priority(&wheel)?, file))
+                    })
+                    .min_by_key(|(priority, _)| *priority)
+                    .map(|(_, file)| file)
+            };
+
+            let Some(best) = best.cloned() else {
+                continue;
+            };
+
+            let metadata = self.metadata(&name, &best).await?;
+            let compatible = metadata.requires_python.as_ref().is_none_or(|requires| {
+                requires
+                    .parse::<SpecifierSet>()
+                    .map_or(true, |requires| requires.contains(&self.python))
+            });
+            if !compatible {
+                continue;
+            }
+
+            return Ok(Some(Package {
+                name: metadata.name.clone(),
+                version,
+                extras: requirement.extras.iter().cloned().collect(),
+                foreign,
+                dependencies: BTreeMap::new(),
+                wheels,
+                metadata,
+            }));
+        }
+
+        let sdists_only = self.files[&name]
+            .iter()
+            .any(|file| !file.filename.ends_with(".whl"));
+        if sdists_only && crate::is_verbose() {
+            eprintln!(
+                "ezpy: ignoring source distributions of {}, only wheels are supported",
+                requirement.name
+            );
+        }
+
+        Ok(None)
+    }
+
+    async fn metadata(&mut self, name: &str, file: &DistFile) -> eyre::Result<Metadata> {
+        let key = (name.to_string(), file.filename.clone());
+        if let Some(metadata) = self.metadata.get(&key) {
+            return Ok(metadata.clone());
+        }
+
+        // Indexes serving the metadata on its own spare downloading the whole wheel.
+        let metadata = match crate::index::core_metadata(file).await? {
+            Some(contents) => Metadata::parse(&contents),
+            None => Wheel::open(&self.sources.fetch(file).await?)?.metadata()?,
+        };
+        self.metadata.insert(key, metadata.clone());
+
+        Ok(metadata)
+    }
+}
+
+/// Combines the markers of two paths to the same dependency, `None` meaning always.
+fn merge_markers(a: &Option<String>, b: &Option<String>) -> Option<String> {
+    match (a, b) {
+        (Some(a), Some(b)) if a == b => Some(a.clone()),
+        (Some(a), Some(b)) => Some(format!("({}) or ({})", a, b)),
+        _ => None,
+    }
+}
+
+/// Lists the packages reachable from `roots` whose markers hold in `markers`.
+pub fn reachable(
+    roots: &[String],
+    dependencies: &HashMap<String, Vec<(String, Option<String>)>>,
+    markers: &MarkerEnvironment,
+) -> eyre::Result<HashSet<String>> {
+    let mut seen = HashSet::new();
+    let mut queue: VecDeque<String> = roots.iter().cloned().collect();
+
+    while let Some(name) = queue.pop_front() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+
+        for (dependency, marker) in dependencies.get(&name).into_iter().flatten() {
+            let applies = match marker {
+                Some(marker) => marker
+                    .parse::<crate::pep508::MarkerTree>()?
+                    .evaluate(markers, &[]),
+                None => true,
+            };
+
+            if applies {
+                queue.push_back(dependency.clone());
+            }
+        }
+    }
+
+    Ok(seen)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+
+    use sha2::Digest;
+
+    use super::*;
+    use crate::index::Index;
+    use crate::wheel::tests::{temp_dir, write_wheel};
+
+    /// Adds the wheel `filename` to the local index at `dir`.
+    pub fn publish(dir: &Path, filename: &str, requires_dist: &[&str]) -> PathBuf {
+        let name = filename.split('-').next().unwrap();
+        let project = dir.join(crate::pep508::normalize_name(name));
+        std::fs::create_dir_all(&project).unwrap();
+
+        write_wheel(&project, filename, requires_dist, &[])
+    }
+
+    pub fn local_index(dir: &Path) -> Sources {
+        Sources {
+            find_links: Vec::new(),
+            index: Some(Index::new(Some(&dir.to_string_lossy()), &[]).unwrap()),
+        }
+    }
+
+    async fn resolve(sources: &Sources, requirements: &[&str]) -> eyre::Result<Vec<String>> {
+        let requirements = requirements
+            .iter()
+            .map(|requirement| requirement.parse().unwrap())
+            .collect::<Vec<_>>();
+
+        let packages = Resolver::new(sources, "3.12.1")?
+            .resolve(&requirements)
+            .await?;
+
+        Ok(packages
+            .values()
+            .map(|package| format!("{}=={}", package.name, package.version))
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn resolves_newest_compatible_versions() {
+        let dir = temp_dir("resolve-newest");
+        publish(&dir, "app-1.0-py3-none-any.whl", &["lib>=1", "Helper"]);
+        publish(&dir, "lib-1.0-py3-none-any.whl", &[]);
+        publish(&dir, "lib-2.0-py3-none-any.whl", &[]);
+        publish(&dir, "lib-3.0b1-py3-none-any.whl", &[]);
+        publish(&dir, "lib-4.0-cp27-cp27m-win32.whl", &[]);
+        publish(&dir, "helper-1.0-py3-none-any.whl", &[]);
+        let sources = local_index(&dir);
+
+        assert_eq!(
+            resolve(&sources, &["app"]).await.unwrap(),
+            vec!["app==1.0", "helper==1.0", "lib==2.0"]
+        );
+        assert_eq!(
+            resolve(&sources, &["app", "lib<2"]).await.unwrap(),
+            vec!["app==1.0", "helper==1.0", "lib==1.0"]
+        );
+        assert_eq!(
+            resolve(&sources, &["lib>=3.0b1"]).await.unwrap(),
+            vec!["lib==3.0b1"]
+        );
+        assert_eq!(
+            resolve(&sources, &["lib>2"]).await.unwrap(),
+            vec!["lib==3.0b1"]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn backtracks_on_conflicts() {
+        let dir = temp_dir("resolve-backtrack");
+        publish(&dir, "a-1.0-py3-none-any.whl", &["c<2"]);
+        publish(&dir, "a-2.0-py3-none-any.whl", &["c>=2"]);
+        publish(&dir, "b-1.0-py3-none-any.whl", &["c<2"]);
+        publish(&dir, "c-1.0-py3-none-any.whl", &[]);
+        publish(&dir, "c-2.0-py3-none-any.whl", &[]);
+        let sources = local_index(&dir);
+
+        assert_eq!(
+            resolve(&sources, &["a", "b"]).await.unwrap(),
+            vec!["a==1.0", "b==1.0", "c==1.0"]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn reports_conflicts() {
+        let dir = temp_dir("resolve-conflict");
+        publish(&dir, "c-1.0-py3-none-any.whl", &[]);
+        publish(&dir, "c-2.0-py3-none-any.whl", &[]);
+        publish(&dir, "old-1.0-py3-none-any.whl", &["c<1"]);
+        let sources = local_index(&dir);
+
+        assert_eq!(
+            resolve(&sources, &["c<2", "c>=2"])
+                .await
+                .unwrap_err()
+                .to_string(),
+            "No version of c satisfies '>=2, <2' for Python 3.12.1 on this platform"
+        );
+        assert_eq!(
+            resolve(&sources, &["missing"])
+                .await
+                .unwrap_err()
+                .to_string(),
+            "No version of missing satisfies the requirements for Python 3.12.1 on this platform"
+        );
+        assert_eq!(
+            resolve(&sources, &["old"]).await.unwrap_err().to_string(),
+            "No version of old satisfies the requirements for Python 3.12.1 on this platform"
+        );
+        assert_eq!(
+            resolve(&sources, &["c @ https://example.com/c-3.0-py3-none-any.whl"])
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Can't resolve c @ https://example.com/c-3.0-py3-none-any.whl, direct references can't be locked from the index"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn keeps_platform_markers_and_drops_others() {
+        let dir = temp_dir("resolve-markers");
+        publish(
+            &dir,
+            "app-1.0-py3-none-any.whl",
+            &[
+                "winonly; sys_platform == 'other-os'",
+                "legacy; python_version < '3'",
+                "extra-dep; extra == 'cli'",
+            ],
+        );
+        publish(&dir, "winonly-1.0-cp312-cp312-other_os.whl", &[]);
+        publish(&dir, "legacy-1.0-py3-none-any.whl", &[]);
+        publish(&dir, "extra_dep-1.0-py3-none-any.whl", &[]);
+        let sources = local_index(&dir);
+
+        let requirements = ["app".parse().unwrap()];
+        let packages = Resolver::new(&sources, "3.12.1")
+            .unwrap()
+            .resolve(&requirements)
+            .await
+            .unwrap();
+
+        assert_eq!(packages.keys().collect::<Vec<_>>(), vec!["app", "winonly"]);
+        assert!(packages["winonly"].foreign);
+        assert!(!packages["app"].foreign);
+        assert_eq!(
+            packages["app"].dependencies,
+            BTreeMap::from([(
+                "winonly".to_string(),
+                Some("sys_platform == \"other-os\"".to_string())
+            )])
+        );
+
+        assert_eq!(
+            resolve(&sources, &["app[cli]"]).await.unwrap(),
+            vec!["app==1.0", "extra_dep==1.0", "winonly==1.0"]
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn reads_metadata_served_by_the_index() {
+        let dir = temp_dir("resolve-metadata");
+        let project = dir.join("app");
+        std::fs::create_dir_all(&project).unwrap();
+
+        // Only the metadata exists, resolving must not need the wheel itself.
+        let metadata = "Metadata-Version: 2.1\nName: app\nVersion: 1.0\nRequires-Dist: lib\n";
+        std::fs::write(project.join("app-1.0-py3-none-any.whl.metadata"), metadata).unwrap();
+        std::fs::write(
+            project.join("index.html"),
+            format!(
+                "<a href=\"app-1.0-py3-none-any.whl\" data-dist-info-metadata=\"sha256={:x}\">app-1.0-py3-none-any.whl</a>",
+                sha2::Sha256::digest(metadata)
+            ),
+        )
+        .unwrap();
+        publish(&dir, "lib-1.0-py3-none-any.whl", &[]);
+        let sources = local_index(&dir);
+
+        assert_eq!(
+            resolve(&sources, &["app"]).await.unwrap(),
+            vec!["app==1.0", "lib==1.0"]
+        );
+
+        std::fs::write(
+            project.join("index.html"),
+            "<a href=\"app-1.0-py3-none-any.whl\" data-core-metadata=\"sha256=00\">app-1.0-py3-none-any.whl</a>",
+        )
+        .unwrap();
+        let error = resolve(&sources, &["app"]).await.unwrap_err().to_string();
+        assert!(error.starts_with(
+            "Hash mismatch for app-1.0-py3-none-any.whl.metadata: expected sha256 00"
+        ));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
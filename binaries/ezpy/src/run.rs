@@ -0,0 +1,89 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Runs a command inside the nearest (or a global) virtual environment without activating it,
+/// then exits with the command's exit code.
+pub async fn run(command: Vec<String>, global: Option<String>) -> eyre::Result<()> {
+    let env = crate::venv::resolve_env(global).await?;
+
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| eyre::eyre!("No command provided to run"))?;
+
+    let code = run_in_env(&env, program, args).await?;
+
+    std::process::exit(code);
+}
+
+/// Spawns `program` with the environment variables of an activated `env`, forwarding
+/// stdin, stdout and stderr, and returns its exit code.
+pub async fn run_in_env(env: &Path, program: &str, args: &[String]) -> eyre::Result<i32> {
+    let mut cmd = tokio::process::Command::new(find_program(env, program));
+    cmd.args(args);
+    activate_command(cmd.as_std_mut(), env)?;
+
+    let status = cmd
+        .status()
+        .await
+        .map_err(|e| eyre::eyre!("Failed to run {}: {}", program, e))?;
+
+    Ok(exit_code(status))
+}
+
+/// Sets `VIRTUAL_ENV` and `PATH` on `cmd` the way an activation script would, and clears
+/// `PYTHONHOME`.
+pub fn activate_command(cmd: &mut std::process::Command, env: &Path) -> eyre::Result<()> {
+    cmd.env("VIRTUAL_ENV", env)
+        .env("PATH", env_path(env)?)
+        .env_remove("PYTHONHOME");
+
+    Ok(())
+}
+
+/// The `PATH` value with the environment's scripts directory prepended.
+pub fn env_path(env: &Path) -> eyre::Result<OsString> {
+    let mut paths = vec![env.join(crate::env_scripts_path())];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+
+    Ok(std::env::join_paths(paths)?)
+}
+
+/// Prefers executables from the environment's scripts directory for bare program names.
+pub fn find_program(env: &Path, program: &str) -> PathBuf {
+    if program.contains(['/', '\\']) {
+        return PathBuf::from(program);
+    }
+
+    let scripts = env.join(crate::env_scripts_path());
+    let candidates = if cfg!(windows) {
+        vec![
+            scripts.join(format!("{}.exe", program)),
+            scripts.join(format!("{}.cmd", program)),
+            scripts.join(program),
+        ]
+    } else {
+        vec![scripts.join(program)]
+    };
+
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .unwrap_or_else(|| PathBuf::from(program))
+}
+
+#[cfg(unix)]
+pub fn exit_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1)
+}
+
+#[cfg(not(unix))]
+pub fn exit_code(status: std::process::ExitStatus) -> i32 {
+    status.code().unwrap_or(1)
+}
@@ -0,0 +1,414 @@
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+use sha2::{Digest, Sha256};
+
+use crate::pep440::SpecifierSet;
+use crate::pep508::Requirement;
+
+/// Written into a script environment once its dependencies are installed, listing them.
+const READY_FILE: &str = "ezpy-requirements.txt";
+
+/// The `script` metadata block of a single-file script, as described by PEP 723.
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScriptMetadata {
+    pub requires_python: Option<String>,
+
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+}
+
+/// The position of the `script` block in the lines of a script: the `# /// script` line,
+/// the closing `# ///` line and the TOML between them.
+struct Block {
+    start: usize,
+    end: usize,
+    toml: String,
+}
+
+/// Finds the `script` block of `contents`. The block ends at the last `# ///` line before
+/// the first line that isn't part of it.
+fn find_block(contents: &str, path: &Path) -> eyre::Result<Option<Block>> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let mut block = None;
+
+    let mut index = 0;
+    while index < lines.len() {
+        let Some(kind) = lines[index].strip_prefix("# /// ") else {
+            index += 1;
+            continue;
+        };
+
+        let start = index;
+        let mut end = None;
+        index += 1;
+        while index < lines.len() && (lines[index] == "#" || lines[index].starts_with("# ")) {
+            if lines[index] == "# ///" {
+                end = Some(index);
+            }
+            index += 1;
+        }
+
+        if kind.trim() != "script" {
+            continue;
+        }
+
+        let Some(end) = end else {
+            eyre::bail!(
+                "{}:{}: unclosed `# /// script` block",
+                path.display(),
+                start + 1
+            );
+        };
+
+        if block.is_some() {
+            eyre::bail!(
+                "{}:{}: a script can only have one `# /// script` block",
+                path.display(),
+                start + 1
+            );
+        }
+
+        let toml = lines[start + 1..end]
+            .iter()
+            .map(|line| line.strip_prefix("# ").unwrap_or(&line[1..]))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        block = Some(Block { start, end, toml });
+        index = end + 1;
+    }
+
+    Ok(block)
+}
+
+/// Reads the metadata block of the script at `path`, if it has one.
+pub async fn read_metadata(path: &Path) -> eyre::Result<Option<ScriptMetadata>> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+
+    let Some(block) = find_block(&contents, path)? else {
+        return Ok(None);
+    };
+
+    let metadata = toml::from_str(&block.toml)
+        .map_err(|e| eyre::eyre!("Invalid `# /// script` block in {}: {}", path.display(), e))?;
+
+    Ok(Some(metadata))
+}
+
+/// Whether `ezpy run` should treat `program` as a single-file script: a Python file with a
+/// `# /// script` block. Other files run in the current environment like any program.
+pub async fn is_script(program: &str) -> eyre::Result<bool> {
+    let path = Path::new(program);
+    if !program.ends_with(".py") || !path.is_file() {
+        return Ok(false);
+    }
+
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+
+    Ok(find_block(&contents, path)?.is_some())
+}
+
+/// Runs the script at `path` with `args`, then exits with its exit code. The interpreter
+/// satisfies `requires-python` of the script's metadata, and is installed when missing; its
+/// dependencies come from an environment cached by interpreter and dependencies.
+pub async fn run_script(
+    path: &Path,
+    args: &[String],
+    python: Option<VersionString>,
+) -> eyre::Result<()> {
+    let metadata = read_metadata(path).await?.unwrap_or_default();
+    let version = script_version(&metadata, python).await?;
+
+    let mut dependencies = metadata
+        .dependencies
+        .iter()
+        .map(|dependency| {
+            dependency.parse::<Requirement>().map_err(|e| {
+                eyre::eyre!(
+                    "Invalid dependency '{}' in {}: {}",
+                    dependency,
+                    path.display(),
+                    e
+                )
+            })?;
+            Ok(dependency.trim().to_string())
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+    dependencies.sort();
+    dependencies.dedup();
+
+    let env = script_env(&version, &dependencies).await?;
+
+    let mut command = vec![path.to_string_lossy().to_string()];
+    command.extend(args.iter().cloned());
+
+    let python = env.join(crate::env_bin_path());
+    let code = crate::run::run_in_env(&env, &python.to_string_lossy(), &command).await?;
+
+    std::process::exit(code);
+}
+
+/// The Python version for a script: the `--python` flag when given, which must satisfy its
+/// `requires-python`, otherwise the newest installed version satisfying it, installing one if
+/// needed.
+async fn script_version(
+    metadata: &ScriptMetadata,
+    python: Option<VersionString>,
+) -> eyre::Result<VersionString> {
+    let Some(requires) = &metadata.requires_python else {
+        return Ok(crate::pin::resolve(python, true).await?.version);
+    };
+
+    let requires: SpecifierSet = requires
+        .parse()
+        .map_err(|e| eyre::eyre!("Invalid requires-python '{}': {}", requires, e))?;
+
+    if python.is_some() {
+        let version = crate::pin::resolve(python, true).await?.version;
+        if !requires.contains(&version.parse()?) {
+            eyre::bail!(
+                "Python {} (from --python) doesn't satisfy requires-python '{}' of the script",
+                version,
+                requires
+            );
+        }
+        return Ok(version);
+    }
+
+    match crate::pin::best_installed(&requires)? {
+        Some(version) => Ok(version),
+        None => crate::pin::install_best(&requires).await,
+    }
+}
+
+/// Returns the cached environment with `dependencies` on Python `version`, creating it when
+/// it doesn't exist yet.
+async fn script_env(version: &VersionString, dependencies: &[String]) -> eyre::Result<PathBuf> {
+    let mut hasher = Sha256::new();
+    hasher.update(version.as_bytes());
+    for dependency in dependencies {
+        hasher.update(b"\n");
+        hasher.update(dependency.as_bytes());
+    }
+    let key = format!("{:x}", hasher.finalize());
+
+    let env = crate::cache_home_ezpy()?.join("scripts").join(&key[..16]);
+    if env.join(READY_FILE).exists() {
+        if crate::is_verbose() {
+            eprintln!("ezpy: reusing script environment {}", env.display());
+        }
+        return Ok(env);
+    }
+
+    if crate::is_verbose() {
+        eprintln!("ezpy: creating script environment {}", env.display());
+    }
+
+    // A previous attempt may have been interrupted.
+    tokio::fs::remove_dir_all(&env).await.ok();
+
+    let result = async {
+        crate::venv::create_env(version, &env, "script", Default::default()).await?;
+
+        if !dependencies.is_empty() {
+            println!(
+                "Installing script dependencies: {}",
+                dependencies.join(", ")
+            );
+
+            let mut args = vec!["install".to_string(), "--quiet".to_string()];
+            args.extend(dependencies.iter().cloned());
+            crate::pip::run(&env, &args, "Failed to install the script dependencies").await?;
+        }
+
+        let listing = dependencies
+            .iter()
+            .map(|dependency| format!("{}\n", dependency))
+            .collect::<String>();
+        tokio::fs::write(env.join(READY_FILE), listing).await?;
+
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = result {
+        tokio::fs::remove_dir_all(&env).await.ok();
+        return Err(e);
+    }
+
+    Ok(env)
+}
+
+/// Records `requirements` in the `dependencies` of the script's metadata block, replacing
+/// requirements on the same distributions. A block is added when the script has none.
+pub async fn add_dependencies(path: &Path, requirements: Vec<String>) -> eyre::Result<()> {
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| eyre::eyre!("Failed to read {}: {}", path.display(), e))?;
+
+    let contents = with_dependencies(&contents, path, &requirements)?;
+    tokio::fs::write(path, contents).await?;
+
+    println!("Added {} to {}", requirements.join(", "), path.display());
+
+    Ok(())
+}
+
+/// The contents of a script with `requirements` recorded in its metadata block.
+fn with_dependencies(contents: &str, path: &Path, requirements: &[String]) -> eyre::Result<String> {
+    let mut lines = contents.lines().map(str::to_string).collect::<Vec<_>>();
+
+    let block = match find_block(contents, path)? {
+        Some(block) => block,
+        None => {
+            // Keep the shebang and encoding declaration on the first lines.
+            let start = lines
+                .iter()
+                .take(2)
+                .take_while(|line| line.starts_with("#!") || line.contains("coding"))
+                .count();
+
+            lines.splice(
+                start..start,
+                ["# /// script".to_string(), "# ///".to_string()],
+            );
+            if lines.get(start + 2).is_some_and(|line| !line.is_empty()) {
+                lines.insert(start + 2, String::new());
+            }
+
+            Block {
+                start,
+                end: start + 1,
+                toml: String::new(),
+            }
+        }
+    };
+
+    let mut document = block
+        .toml
+        .parse::<toml_edit::DocumentMut>()
+        .map_err(|e| eyre::eyre!("Invalid `# /// script` block in {}: {}", path.display(), e))?;
+
+    if !document.contains_key("dependencies") {
+        document.insert("dependencies", toml_edit::value(toml_edit::Array::new()));
+    }
+    let dependencies = document
+        .get_mut("dependencies")
+        .and_then(|item| item.as_array_mut())
+        .ok_or_else(|| eyre::eyre!("'dependencies' of {} isn't an array", path.display()))?;
+
+    for requirement in requirements {
+        let name = requirement.parse::<Requirement>()?.normalized_name();
+        crate::pyproject::set_requirement(dependencies, requirement, &name);
+    }
+
+    let toml = document
+        .to_string()
+        .lines()
+        .map(|line| match line {
+            "" => "#".to_string(),
+            line => format!("# {}", line),
+        })
+        .collect::<Vec<_>>();
+    lines.splice(block.start + 1..block.end, toml);
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(contents: &str) -> eyre::Result<Option<(usize, usize, String)>> {
+        Ok(find_block(contents, Path::new("demo.py"))?
+            .map(|block| (block.start, block.end, block.toml)))
+    }
+
+    fn add(contents: &str, requirements: &[&str]) -> String {
+        let requirements = requirements
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>();
+        with_dependencies(contents, Path::new("demo.py"), &requirements).unwrap()
+    }
+
+    #[test]
+    fn finds_script_block() {
+        let contents = "import sys\n# /// script\n# requires-python = \">=3.11\"\n#\n# dependencies = [\"rich\"]\n# ///\nprint(1)\n";
+
+        assert_eq!(
+            block(contents).unwrap(),
+            Some((
+                1,
+                5,
+                "requires-python = \">=3.11\"\n\ndependencies = [\"rich\"]".to_string()
+            ))
+        );
+        assert_eq!(block("print(1)\n").unwrap(), None);
+    }
+
+    #[test]
+    fn ignores_other_block_types() {
+        let contents =
+            "# /// pyproject\n# [tool.x]\n# ///\n\n# /// script\n# dependencies = []\n# ///\n";
+
+        assert_eq!(
+            block(contents).unwrap(),
+            Some((4, 6, "dependencies = []".to_string()))
+        );
+        assert_eq!(block("# /// other\n# x\n").unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_invalid_blocks() {
+        let unclosed = block("x = 1\n# /// script\n# dependencies = []\nprint(1)\n").unwrap_err();
+        assert_eq!(
+            unclosed.to_string(),
+            "demo.py:2: unclosed `# /// script` block"
+        );
+
+        let duplicate = block("# /// script\n# ///\n\n# /// script\n# ///\n").unwrap_err();
+        assert_eq!(
+            duplicate.to_string(),
+            "demo.py:4: a script can only have one `# /// script` block"
+        );
+    }
+
+    #[test]
+    fn adds_block_after_shebang() {
+        assert_eq!(
+            add("#!/usr/bin/env python3\nprint(1)\n", &["rich"]),
+            "#!/usr/bin/env python3\n# /// script\n# dependencies = [\"rich\"]\n# ///\n\nprint(1)\n"
+        );
+        assert_eq!(
+            add("print(1)\n", &["rich", "httpx>=0.27"]),
+            "# /// script\n# dependencies = [\"rich\", \"httpx>=0.27\"]\n# ///\n\nprint(1)\n"
+        );
+    }
+
+    #[test]
+    fn updates_existing_block() {
+        let contents = "# /// script\n# requires-python = \">=3.11\"\n# dependencies = [\n#     \"rich\",\n# ]\n# ///\nprint(1)\n";
+
+        assert_eq!(
+            add(contents, &["Rich>=13", "httpx"]),
+            "# /// script\n# requires-python = \">=3.11\"\n# dependencies = [\n#     \"Rich>=13\",\n#     \"httpx\",\n# ]\n# ///\nprint(1)\n"
+        );
+        assert_eq!(
+            add(
+                "# /// script\n# requires-python = \">=3.11\"\n# ///\n",
+                &["rich"]
+            ),
+            "# /// script\n# requires-python = \">=3.11\"\n# dependencies = [\"rich\"]\n# ///\n"
+        );
+    }
+}
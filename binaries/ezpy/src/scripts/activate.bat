@@ -0,0 +1,31 @@
+@echo off
+
+set "VIRTUAL_ENV=__VENV_DIR__"
+
+if defined _OLD_VIRTUAL_PROMPT (
+    set "PROMPT=%_OLD_VIRTUAL_PROMPT%"
+) else (
+    if not defined PROMPT (
+        set "PROMPT=$P$G"
+    )
+    if not defined VIRTUAL_ENV_DISABLE_PROMPT (
+        set "_OLD_VIRTUAL_PROMPT=%PROMPT%"
+    )
+)
+if not defined VIRTUAL_ENV_DISABLE_PROMPT (
+    set "PROMPT=(__VENV_PROMPT__) %PROMPT%"
+)
+
+REM Don't use () to avoid problems with them in %PATH%
+if defined _OLD_VIRTUAL_PYTHONHOME goto ENDIFVHOME
+    set "_OLD_VIRTUAL_PYTHONHOME=%PYTHONHOME%"
+:ENDIFVHOME
+
+set PYTHONHOME=
+
+if defined _OLD_VIRTUAL_PATH goto ENDIFVPATH1
+    set "_OLD_VIRTUAL_PATH=%PATH%"
+:ENDIFVPATH1
+
+set "PATH=%VIRTUAL_ENV%\__VENV_BIN_NAME__;%_OLD_VIRTUAL_PATH%"
+set "VIRTUAL_ENV_PROMPT=__VENV_PROMPT__"
@@ -0,0 +1,367 @@
+use std::path::{Path, PathBuf};
+
+/// Environment variable set inside shells spawned by `ezpy shell`.
+pub const SHELL_ENV_VAR: &str = "EZPY_SHELL_ENV";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    #[value(alias = "nu")]
+    Nushell,
+    #[value(alias = "pwsh")]
+    Powershell,
+}
+
+impl Shell {
+    /// Guesses the user's shell from the environment.
+    pub fn detect() -> Shell {
+        if std::env::var_os("NU_VERSION").is_some() {
+            return Shell::Nushell;
+        }
+
+        if let Some(shell) = std::env::var_os("SHELL") {
+            let name = Path::new(&shell)
+                .file_stem()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            match name.as_str() {
+                "bash" => return Shell::Bash,
+                "zsh" => return Shell::Zsh,
+                "fish" => return Shell::Fish,
+                "nu" => return Shell::Nushell,
+                "pwsh" | "powershell" => return Shell::Powershell,
+                _ => {}
+            }
+        }
+
+        if cfg!(windows) {
+            Shell::Powershell
+        } else {
+            Shell::Bash
+        }
+    }
+
+    fn program(&self) -> PathBuf {
+        let from_env = std::env::var_os("SHELL")
+            .map(PathBuf::from)
+            .filter(|path| Shell::detect() == *self && path.is_file());
+
+        from_env.unwrap_or_else(|| {
+            PathBuf::from(match self {
+                Shell::Bash => "bash",
+                Shell::Zsh => "zsh",
+                Shell::Fish => "fish",
+                Shell::Nushell => "nu",
+                Shell::Powershell if cfg!(windows) => "powershell",
+                Shell::Powershell => "pwsh",
+            })
+        })
+    }
+
+    /// Quotes `value` as a literal string for this shell.
+    pub fn quote(&self, value: &str) -> String {
+        match self {
+            Shell::Bash | Shell::Zsh => format!("'{}'", value.replace('\'', r"'\''")),
+            Shell::Fish => format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'")),
+            Shell::Nushell if !value.contains('\'') => format!("'{}'", value),
+            Shell::Nushell => format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\"")),
+            Shell::Powershell => format!("'{}'", value.replace('\'', "''")),
+        }
+    }
+}
+
+/// The name displayed in the prompt of an activated environment.
+pub fn env_prompt(env: &Path) -> String {
+    let dir = if env.file_name().is_some_and(|name| name == ".venv") {
+        env.parent().unwrap_or(env)
+    } else {
+        env
+    };
+
+    dir.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(".venv".to_string())
+}
+
+/// Shell code activating `env` in the current shell session.
+pub fn activate_code(shell: Shell, env: &Path) -> String {
+    let venv = shell.quote(&env.display().to_string());
+    let bin = shell.quote(&env.join(crate::env_scripts_path()).display().to_string());
+    let prompt = shell.quote(&env_prompt(env));
+
+    match shell {
+        Shell::Bash | Shell::Zsh => format!(
+            r#"export VIRTUAL_ENV={venv}
+export _EZPY_OLD_PATH="$PATH"
+export PATH={bin}:"$PATH"
+if [ -n "${{PYTHONHOME:-}}" ]; then
+    export _EZPY_OLD_PYTHONHOME="$PYTHONHOME"
+    unset PYTHONHOME
+fi
+export VIRTUAL_ENV_PROMPT={prompt}
+if [ -z "${{VIRTUAL_ENV_DISABLE_PROMPT:-}}" ]; then
+    _EZPY_OLD_PS1="${{PS1:-}}"
+    PS1="($VIRTUAL_ENV_PROMPT) ${{PS1:-}}"
+fi
+hash -r 2>/dev/null
+"#
+        ),
+        Shell::Fish => format!(
+            r#"set -gx VIRTUAL_ENV {venv}
+set -gx _EZPY_OLD_PATH $PATH
+set -gx PATH {bin} $PATH
+if set -q PYTHONHOME
+    set -gx _EZPY_OLD_PYTHONHOME $PYTHONHOME
+    set -e PYTHONHOME
+end
+set -gx VIRTUAL_ENV_PROMPT {prompt}
+if not set -q VIRTUAL_ENV_DISABLE_PROMPT; and not functions -q _ezpy_old_fish_prompt
+    functions -c fish_prompt _ezpy_old_fish_prompt
+    function fish_prompt
+        set -l old_status $status
+        printf '(%s) ' $VIRTUAL_ENV_PROMPT
+        echo "exit $old_status" | .
+        _ezpy_old_fish_prompt
+    end
+end
+"#
+        ),
+        Shell::Nushell => format!(
+            r#"$env.VIRTUAL_ENV = {venv}
+$env._EZPY_OLD_PATH = $env.PATH
+$env.PATH = ($env.PATH | prepend {bin})
+if 'PYTHONHOME' in $env {{
+    $env._EZPY_OLD_PYTHONHOME = $env.PYTHONHOME
+    hide-env PYTHONHOME
+}}
+$env.VIRTUAL_ENV_PROMPT = {prompt}
+if 'VIRTUAL_ENV_DISABLE_PROMPT' not-in $env {{
+    $env._EZPY_OLD_PROMPT_COMMAND = ($env.PROMPT_COMMAND? | default '')
+    $env.PROMPT_COMMAND = {{||
+        let old = $env._EZPY_OLD_PROMPT_COMMAND
+        let base = if ($old | describe) == 'closure' {{ do $old }} else {{ $old }}
+        ['(' $env.VIRTUAL_ENV_PROMPT ') ' $base] | str join
+    }}
+}}
+"#
+        ),
+        Shell::Powershell => format!(
+            r#"$env:VIRTUAL_ENV = {venv}
+$env:_EZPY_OLD_PATH = $env:PATH
+$env:PATH = {bin} + [System.IO.Path]::PathSeparator + $env:PATH
+if (Test-Path -Path Env:PYTHONHOME) {{
+    $env:_EZPY_OLD_PYTHONHOME = $env:PYTHONHOME
+    Remove-Item -Path Env:PYTHONHOME
+}}
+$env:VIRTUAL_ENV_PROMPT = {prompt}
+if (-not $env:VIRTUAL_ENV_DISABLE_PROMPT -and -not (Test-Path -Path Function:_ezpy_old_prompt)) {{
+    Copy-Item -Path Function:prompt -Destination Function:global:_ezpy_old_prompt
+    function global:prompt {{
+        Write-Host -NoNewline "($env:VIRTUAL_ENV_PROMPT) "
+        _ezpy_old_prompt
+    }}
+}}
+"#
+        ),
+    }
+}
+
+/// Shell code undoing [`activate_code`] in the current shell session.
+pub fn deactivate_code(shell: Shell) -> String {
+    match shell {
+        Shell::Bash | Shell::Zsh => r#"if [ -n "${_EZPY_OLD_PATH:-}" ]; then
+    export PATH="$_EZPY_OLD_PATH"
+    unset _EZPY_OLD_PATH
+fi
+if [ -n "${_EZPY_OLD_PYTHONHOME:-}" ]; then
+    export PYTHONHOME="$_EZPY_OLD_PYTHONHOME"
+    unset _EZPY_OLD_PYTHONHOME
+fi
+if [ -n "${_EZPY_OLD_PS1+x}" ]; then
+    PS1="$_EZPY_OLD_PS1"
+    unset _EZPY_OLD_PS1
+fi
+unset VIRTUAL_ENV VIRTUAL_ENV_PROMPT
+hash -r 2>/dev/null
+"#
+        .to_string(),
+        Shell::Fish => r#"if set -q _EZPY_OLD_PATH
+    set -gx PATH $_EZPY_OLD_PATH
+    set -e _EZPY_OLD_PATH
+end
+if set -q _EZPY_OLD_PYTHONHOME
+    set -gx PYTHONHOME $_EZPY_OLD_PYTHONHOME
+    set -e _EZPY_OLD_PYTHONHOME
+end
+if functions -q _ezpy_old_fish_prompt
+    functions -e fish_prompt
+    functions -c _ezpy_old_fish_prompt fish_prompt
+    functions -e _ezpy_old_fish_prompt
+end
+set -e VIRTUAL_ENV
+set -e VIRTUAL_ENV_PROMPT
+"#
+        .to_string(),
+        Shell::Nushell => r#"if '_EZPY_OLD_PATH' in $env {
+    $env.PATH = $env._EZPY_OLD_PATH
+    hide-env _EZPY_OLD_PATH
+}
+if '_EZPY_OLD_PYTHONHOME' in $env {
+    $env.PYTHONHOME = $env._EZPY_OLD_PYTHONHOME
+    hide-env _EZPY_OLD_PYTHONHOME
+}
+if '_EZPY_OLD_PROMPT_COMMAND' in $env {
+    $env.PROMPT_COMMAND = $env._EZPY_OLD_PROMPT_COMMAND
+    hide-env _EZPY_OLD_PROMPT_COMMAND
+}
+hide-env -i VIRTUAL_ENV VIRTUAL_ENV_PROMPT
+"#
+        .to_string(),
+        Shell::Powershell => r#"if (Test-Path -Path Env:_EZPY_OLD_PATH) {
+    $env:PATH = $env:_EZPY_OLD_PATH
+    Remove-Item -Path Env:_EZPY_OLD_PATH
+}
+if (Test-Path -Path Env:_EZPY_OLD_PYTHONHOME) {
+    $env:PYTHONHOME = $env:_EZPY_OLD_PYTHONHOME
+    Remove-Item -Path Env:_EZPY_OLD_PYTHONHOME
+}
+if (Test-Path -Path Function:_ezpy_old_prompt) {
+    Copy-Item -Path Function:_ezpy_old_prompt -Destination Function:global:prompt
+    Remove-Item -Path Function:_ezpy_old_prompt
+}
+Remove-Item -Path Env:VIRTUAL_ENV, Env:VIRTUAL_ENV_PROMPT -ErrorAction SilentlyContinue
+"#
+        .to_string(),
+    }
+}
+
+/// Spawns an interactive subshell with `env` activated and exits with the subshell's exit code.
+pub async fn spawn_shell(global: Option<String>, shell: Option<Shell>) -> eyre::Result<()> {
+    if let Some(active) = std::env::var_os(SHELL_ENV_VAR) {
+        eyre::bail!(
+            "Already inside an ezpy shell for {}, run `exit` to leave it first",
+            Path::new(&active).display()
+        );
+    }
+
+    let env = crate::venv::resolve_env(global).await?;
+    let shell = shell.unwrap_or_else(Shell::detect);
+    let init = activate_code(shell, &env);
+
+    // Startup files may reorder PATH, so the activation code runs after them.
+    let temp = std::env::temp_dir().join(format!("ezpy-shell-{}", std::process::id()));
+    tokio::fs::create_dir_all(&temp).await?;
+
+    let mut cmd = tokio::process::Command::new(shell.program());
+    match shell {
+        Shell::Bash => {
+            let rcfile = temp.join("bashrc");
+            tokio::fs::write(
+                &rcfile,
+                format!("[ -f ~/.bashrc ] && . ~/.bashrc\n{}", init),
+            )
+            .await?;
+
+            cmd.arg("--rcfile").arg(rcfile).arg("-i");
+        }
+        Shell::Zsh => {
+            let original = std::env::var("ZDOTDIR")
+                .ok()
+                .or_else(|| std::env::var("HOME").ok())
+                .unwrap_or_default();
+
+            tokio::fs::write(
+                temp.join(".zshenv"),
+                "[ -f \"$_EZPY_ZDOTDIR/.zshenv\" ] && . \"$_EZPY_ZDOTDIR/.zshenv\"\n",
+            )
+            .await?;
+            tokio::fs::write(
+                temp.join(".zshrc"),
+                format!(
+                    "ZDOTDIR=\"$_EZPY_ZDOTDIR\"\n[ -f \"$ZDOTDIR/.zshrc\" ] && . \"$ZDOTDIR/.zshrc\"\n{}",
+                    init
+                ),
+            )
+            .await?;
+
+            cmd.env("_EZPY_ZDOTDIR", original)
+                .env("ZDOTDIR", &temp)
+                .arg("-i");
+        }
+        Shell::Fish => {
+            cmd.arg("--init-command").arg(init);
+        }
+        Shell::Nushell => {
+            cmd.arg("--execute").arg(init);
+        }
+        Shell::Powershell => {
+            cmd.arg("-NoExit").arg("-Command").arg(init);
+        }
+    }
+
+    cmd.env(SHELL_ENV_VAR, &env);
+
+    println!(
+        "Spawning a {:?} shell with {} activated, run `exit` to leave it",
+        shell,
+        env.display()
+    );
+
+    let status = cmd.status().await;
+    tokio::fs::remove_dir_all(&temp).await.ok();
+
+    let status = status.map_err(|e| eyre::eyre!("Failed to spawn {:?}: {}", shell, e))?;
+
+    std::process::exit(crate::run::exit_code(status));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRICKY: &str = r#"/it's a "project"\dir"#;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(Shell::Bash.quote("plain"), "'plain'");
+        assert_eq!(Shell::Bash.quote(TRICKY), r#"'/it'\''s a "project"\dir'"#);
+        assert_eq!(Shell::Zsh.quote(TRICKY), Shell::Bash.quote(TRICKY));
+        assert_eq!(Shell::Fish.quote(TRICKY), r#"'/it\'s a "project"\\dir'"#);
+        assert_eq!(Shell::Nushell.quote(r#"a "b"\c"#), r#"'a "b"\c'"#);
+        assert_eq!(
+            Shell::Nushell.quote(TRICKY),
+            r#""/it's a \"project\"\\dir""#
+        );
+        assert_eq!(
+            Shell::Powershell.quote(TRICKY),
+            r#"'/it''s a "project"\dir'"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_activate_code_in_bash() {
+        let env = Path::new(TRICKY).join(".venv");
+        let script = format!(
+            "{}printf '%s\\n' \"$VIRTUAL_ENV\" \"${{PATH%%:*}}\" \"$VIRTUAL_ENV_PROMPT\"",
+            activate_code(Shell::Bash, &env)
+        );
+
+        let output = std::process::Command::new("bash")
+            .args(["--norc", "--noprofile", "-c", &script])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+
+        let expected = format!(
+            "{}\n{}/bin\n{}\n",
+            env.display(),
+            env.display(),
+            env_prompt(&env)
+        );
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+    }
+}
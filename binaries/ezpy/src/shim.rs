@@ -0,0 +1,154 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::install_home_ezpy;
+
+/// Names ezpy answers to when invoked through a shim.
+pub const SHIMS: &[&str] = &["python", "python3", "pip", "pip3"];
+
+pub fn shims_dir() -> eyre::Result<PathBuf> {
+    Ok(install_home_ezpy()?.join("shims"))
+}
+
+/// Returns the shim name when ezpy was invoked as `python`, `pip`, etc.
+pub fn invoked_as() -> Option<String> {
+    let arg0 = std::env::args_os().next()?;
+    let name = Path::new(&arg0).file_name()?.to_string_lossy().to_string();
+    let name = name.strip_suffix(".exe").unwrap_or(&name).to_string();
+
+    SHIMS.contains(&name.as_str()).then_some(name)
+}
+
+/// Resolves the interpreter for the current directory and replaces the current process with it.
+pub async fn run_shim(name: &str) -> eyre::Result<()> {
+    let args: Vec<OsString> = std::env::args_os().skip(1).collect();
+    let current = std::env::current_dir()?;
+
+    let mut cmd = match crate::venv::locate_env(&current)? {
+        Some(env) => {
+            let mut cmd = std::process::Command::new(env.join(crate::env_bin_path()));
+            crate::run::activate_command(&mut cmd, &env)?;
+            cmd
+        }
+        None => {
+            let version = crate::pin::resolve_version(None).await.map_err(|e| {
+                eyre::eyre!(
+                    "No virtual environment or pinned Python version found for `{}`: {}",
+                    name,
+                    e
+                )
+            })?;
+
+            std::process::Command::new(
+                indygreg::python_path(version.version)?.join(crate::python_bin_path()),
+            )
+        }
+    };
+
+    if name.starts_with("pip") {
+        cmd.arg("-m").arg("pip");
+    }
+    cmd.args(args);
+
+    exec(cmd)
+}
+
+#[cfg(unix)]
+fn exec(mut cmd: std::process::Command) -> eyre::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let error = cmd.exec();
+
+    Err(eyre::eyre!(
+        "Failed to run {}: {}",
+        cmd.get_program().to_string_lossy(),
+        error
+    ))
+}
+
+#[cfg(not(unix))]
+fn exec(mut cmd: std::process::Command) -> eyre::Result<()> {
+    let status = cmd.status().map_err(|e| {
+        eyre::eyre!(
+            "Failed to run {}: {}",
+            cmd.get_program().to_string_lossy(),
+            e
+        )
+    })?;
+
+    std::process::exit(crate::run::exit_code(status));
+}
+
+pub async fn install_shims() -> eyre::Result<()> {
+    let dir = shims_dir()?;
+    let exe = tokio::fs::canonicalize(std::env::current_exe()?).await?;
+
+    tokio::fs::create_dir_all(&dir).await?;
+
+    for name in SHIMS {
+        let shim = dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX));
+        tokio::fs::remove_file(&shim).await.ok();
+
+        link_shim(&exe, &shim).await?;
+    }
+
+    println!(
+        "Installed shims for {} in {}",
+        SHIMS.join(", "),
+        dir.display()
+    );
+
+    print_path_hint(
+        &dir,
+        "Add the shims directory to the front of your PATH to use them:",
+    );
+
+    Ok(())
+}
+
+/// Prints `message` and the command adding `dir` to the front of `PATH`, unless it's
+/// already there.
+pub fn print_path_hint(dir: &Path, message: &str) {
+    let on_path = std::env::var_os("PATH")
+        .map(|path| std::env::split_paths(&path).any(|entry| entry == dir))
+        .unwrap_or(false);
+
+    if !on_path {
+        println!("{}", message);
+        if cfg!(windows) {
+            println!("$env:PATH = \"{};$env:PATH\"", dir.display());
+        } else {
+            println!("export PATH=\"{}:$PATH\"", dir.display());
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn link_shim(exe: &Path, shim: &Path) -> eyre::Result<()> {
+    tokio::fs::symlink(exe, shim).await?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn link_shim(exe: &Path, shim: &Path) -> eyre::Result<()> {
+    if tokio::fs::hard_link(exe, shim).await.is_err() {
+        tokio::fs::copy(exe, shim).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn uninstall_shims() -> eyre::Result<()> {
+    let dir = shims_dir()?;
+    if !dir.exists() {
+        println!("No shims installed");
+        return Ok(());
+    }
+
+    tokio::fs::remove_dir_all(&dir).await?;
+
+    println!("Removed shims from {}", dir.display());
+
+    Ok(())
+}
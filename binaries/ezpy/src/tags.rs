@@ -0,0 +1,322 @@
+use std::sync::OnceLock;
+
+use crate::wheel::WheelFilename;
+
+/// The wheel tags supported by an interpreter, most preferred first, as described by the
+/// platform compatibility tags specification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tags(pub Vec<(String, String, String)>);
+
+impl Tags {
+    /// Computes the tags supported by CPython `version` on the current platform.
+    pub fn for_interpreter(version: &str) -> Self {
+        let mut parts = version.split('.');
+        let major: u64 = parts
+            .next()
+            .and_then(|major| major.parse().ok())
+            .unwrap_or(3);
+        let minor: u64 = parts
+            .next()
+            .and_then(|minor| minor.parse().ok())
+            .unwrap_or(0);
+
+        let platforms = platform_tags();
+        let cpython = format!("cp{}{}", major, minor);
+        let mut tags = Vec::new();
+
+        for platform in &platforms {
+            tags.push((cpython.clone(), cpython.clone(), platform.clone()));
+            tags.push((cpython.clone(), "abi3".to_string(), platform.clone()));
+            tags.push((cpython.clone(), "none".to_string(), platform.clone()));
+        }
+
+        // Stable ABI wheels built for older interpreters keep working.
+        for older in (2..minor).rev() {
+            for platform in &platforms {
+                tags.push((
+                    format!("cp{}{}", major, older),
+                    "abi3".to_string(),
+                    platform.clone(),
+                ));
+            }
+        }
+
+        for platform in &platforms {
+            for python in python_versions(major, minor) {
+                tags.push((python, "none".to_string(), platform.clone()));
+            }
+        }
+
+        tags.push((cpython, "none".to_string(), "any".to_string()));
+        for python in python_versions(major, minor) {
+            tags.push((python, "none".to_string(), "any".to_string()));
+        }
+
+        Tags(tags)
+    }
+
+    /// The priority of the best tag of `wheel` supported by the interpreter, lower being
+    /// preferred, or `None` when the wheel isn't compatible.
+    pub fn priority(&self, wheel: &WheelFilename) -> Option<usize> {
+        self.0.iter().position(|(python, abi, platform)| {
+            wheel.python.contains(python)
+                && wheel.abi.contains(abi)
+                && wheel.platform.contains(platform)
+        })
+    }
+
+    pub fn is_compatible(&self, wheel: &WheelFilename) -> bool {
+        self.priority(wheel).is_some()
+    }
+}
+
+/// Generic Python tags, from `py312` down to `py30` with `py3` right after the exact version.
+fn python_versions(major: u64, minor: u64) -> Vec<String> {
+    let mut versions = vec![format!("py{}{}", major, minor), format!("py{}", major)];
+    versions.extend(
+        (0..minor)
+            .rev()
+            .map(|minor| format!("py{}{}", major, minor)),
+    );
+
+    versions
+}
+
+fn platform_tags() -> Vec<String> {
+    let arch = std::env::consts::ARCH;
+
+    match std::env::consts::OS {
+        "linux" => linux_platforms(arch),
+        "macos" => macos_platforms(arch),
+        "windows" => vec![match arch {
+            "x86" => "win32",
+            "aarch64" => "win_arm64",
+            _ => "win_amd64",
+        }
+        .to_string()],
+        os => vec![format!("{}_{}", os, arch)],
+    }
+}
+
+fn linux_platforms(arch: &str) -> Vec<String> {
+    let mut platforms = Vec::new();
+
+    match libc_version() {
+        Libc::Musl(minor) => {
+            platforms.extend(
+                (0..=minor)
+                    .rev()
+                    .map(|minor| format!("musllinux_1_{}_{}", minor, arch)),
+            );
+        }
+        Libc::Glibc(minor) => {
+            // Legacy aliases are listed right after the glibc version they stand for.
+            for minor in (5..=minor).rev() {
+                platforms.push(format!("manylinux_2_{}_{}", minor, arch));
+                let legacy = match minor {
+                    17 => "manylinux2014",
+                    12 => "manylinux2010",
+                    5 => "manylinux1",
+                    _ => continue,
+                };
+                platforms.push(format!("{}_{}", legacy, arch));
+            }
+        }
+    }
+
+    platforms.push(format!("linux_{}", arch));
+
+    platforms
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Libc {
+    Glibc(u64),
+    Musl(u64),
+}
+
+/// Detects the C library from `ldd --version` once per process, assuming glibc 2.17 when it
+/// can't be run.
+fn libc_version() -> Libc {
+    static LIBC: OnceLock<Libc> = OnceLock::new();
+
+    *LIBC.get_or_init(|| {
+        let output = std::process::Command::new("ldd")
+            .arg("--version")
+            .output()
+            .map(|out| {
+                format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&out.stdout),
+                    String::from_utf8_lossy(&out.stderr)
+                )
+            })
+            .unwrap_or_default();
+
+        parse_libc(&output)
+    })
+}
+
+/// Reads the C library and its version from the output of `ldd --version`.
+fn parse_libc(output: &str) -> Libc {
+    let version = |line: &str| {
+        line.split_whitespace()
+            .filter_map(|word| {
+                let (major, minor) = word
+                    .trim_matches(|c: char| !c.is_ascii_digit())
+                    .split_once('.')?;
+                Some((
+                    major.parse::<u64>().ok()?,
+                    minor.split('.').next()?.parse::<u64>().ok()?,
+                ))
+            })
+            .next_back()
+    };
+
+    if output.to_ascii_lowercase().contains("musl") {
+        let minor = output
+            .lines()
+            .find(|line| line.starts_with("Version"))
+            .and_then(version)
+            .map(|(_, minor)| minor)
+            .unwrap_or(1);
+        return Libc::Musl(minor);
+    }
+
+    match output.lines().next().and_then(version) {
+        Some((2, minor)) => Libc::Glibc(minor),
+        _ => Libc::Glibc(17),
+    }
+}
+
+fn macos_platforms(arch: &str) -> Vec<String> {
+    let arch = if arch == "aarch64" { "arm64" } else { arch };
+
+    let (major, minor) = std::process::Command::new("sw_vers")
+        .arg("-productVersion")
+        .output()
+        .ok()
+        .and_then(|out| {
+            let version = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            let mut parts = version.split('.');
+            Some((
+                parts.next()?.parse::<u64>().ok()?,
+                parts
+                    .next()
+                    .and_then(|minor| minor.parse().ok())
+                    .unwrap_or(0),
+            ))
+        })
+        .unwrap_or((11, 0));
+
+    let mut versions = Vec::new();
+    for major in (11..=major).rev() {
+        versions.push((major, 0));
+    }
+    if major == 10 {
+        versions.extend((4..=minor).rev().map(|minor| (10, minor)));
+    } else if arch == "x86_64" {
+        versions.extend((4..=16).rev().map(|minor| (10, minor)));
+    }
+
+    let mut platforms = Vec::new();
+    for (major, minor) in versions {
+        platforms.push(format!("macosx_{}_{}_{}", major, minor, arch));
+        platforms.push(format!("macosx_{}_{}_universal2", major, minor));
+    }
+
+    platforms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(python: &str, abi: &str, platform: &str) -> (String, String, String) {
+        (python.to_string(), abi.to_string(), platform.to_string())
+    }
+
+    #[test]
+    fn orders_tags_from_most_specific() {
+        let tags = Tags::for_interpreter("3.12.1");
+        let platform = platform_tags().remove(0);
+        let position = |python: &str, abi: &str, platform: &str| {
+            tags.0
+                .iter()
+                .position(|candidate| *candidate == tag(python, abi, platform))
+                .unwrap_or_else(|| panic!("{}-{}-{} is missing", python, abi, platform))
+        };
+
+        assert_eq!(tags.0[0], tag("cp312", "cp312", &platform));
+        assert_eq!(tags.0.last(), Some(&tag("py30", "none", "any")));
+
+        let ordered = [
+            position("cp312", "cp312", &platform),
+            position("cp312", "abi3", &platform),
+            position("cp312", "none", &platform),
+            position("cp311", "abi3", &platform),
+            position("cp32", "abi3", &platform),
+            position("py312", "none", &platform),
+            position("cp312", "none", "any"),
+            position("py312", "none", "any"),
+            position("py3", "none", "any"),
+            position("py311", "none", "any"),
+        ];
+        assert!(ordered.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert!(!tags.0.contains(&tag("cp313", "abi3", &platform)));
+        assert!(!tags.0.contains(&tag("cp311", "cp311", &platform)));
+    }
+
+    #[test]
+    fn ranks_wheels_by_best_tag() {
+        let tags = Tags(vec![
+            tag("cp312", "cp312", "linux_x86_64"),
+            tag("cp312", "abi3", "linux_x86_64"),
+            tag("py3", "none", "any"),
+        ]);
+        let priority = |filename: &str| tags.priority(&filename.parse().unwrap());
+
+        assert_eq!(priority("demo-1.0-cp312-cp312-linux_x86_64.whl"), Some(0));
+        assert_eq!(priority("demo-1.0-cp312-abi3-linux_x86_64.whl"), Some(1));
+        assert_eq!(priority("demo-1.0-py2.py3-none-any.whl"), Some(2));
+        assert_eq!(priority("demo-1.0-cp311-cp311-linux_x86_64.whl"), None);
+        assert!(!tags.is_compatible(&"demo-1.0-py3-none-win_amd64.whl".parse().unwrap()));
+    }
+
+    #[test]
+    fn lists_manylinux_aliases() {
+        let Libc::Glibc(minor) = libc_version() else {
+            return;
+        };
+        if minor < 17 || std::env::consts::OS != "linux" {
+            return;
+        }
+
+        let arch = std::env::consts::ARCH;
+        let platforms = linux_platforms(arch);
+        let position = |platform: &str| {
+            platforms
+                .iter()
+                .position(|candidate| *candidate == format!("{}_{}", platform, arch))
+                .unwrap()
+        };
+
+        assert_eq!(position("manylinux2014"), position("manylinux_2_17") + 1);
+        assert_eq!(position("manylinux1"), position("manylinux_2_5") + 1);
+        assert_eq!(platforms.last(), Some(&format!("linux_{}", arch)));
+    }
+
+    #[test]
+    fn parses_ldd_output() {
+        assert_eq!(
+            parse_libc("ldd (Ubuntu GLIBC 2.35-0ubuntu3.8) 2.35\nCopyright (C) 2022\n"),
+            Libc::Glibc(35)
+        );
+        assert_eq!(
+            parse_libc("musl libc (x86_64)\nVersion 1.2.4\nDynamic Program Loader\n"),
+            Libc::Musl(2)
+        );
+        assert_eq!(parse_libc(""), Libc::Glibc(17));
+    }
+}
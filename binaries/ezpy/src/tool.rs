@@ -0,0 +1,329 @@
+use std::path::{Path, PathBuf};
+
+use indygreg::metadata::VersionString;
+
+use crate::inspect::print_table;
+use crate::install_home_ezpy;
+use crate::pep508::{normalize_name, Requirement};
+use crate::wheel::Target;
+
+/// Where the scripts of installed tools are linked: `EZPY_TOOL_BIN_DIR` when set, otherwise
+/// `~/.local/bin` (`bin` in the ezpy data directory on Windows).
+pub fn tool_bin_dir() -> eyre::Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("EZPY_TOOL_BIN_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+
+    if cfg!(windows) {
+        return Ok(install_home_ezpy()?.join("bin"));
+    }
+
+    Ok(simple_home_dir::home_dir()
+        .ok_or_else(|| eyre::eyre!("Failed to get home directory, your home directory is not set"))?
+        .join(".local")
+        .join("bin"))
+}
+
+/// The file next to `pyvenv.cfg` recording the requirement a tool was installed with, so
+/// upgrades keep its extras and version constraints.
+const TOOL_SPEC_FILE: &str = "ezpy-tool.txt";
+
+/// The directory holding the environment of each installed tool, apart from the global
+/// environments.
+fn tools_dir() -> eyre::Result<PathBuf> {
+    Ok(install_home_ezpy()?.join("tools"))
+}
+
+fn tool_env(name: &str) -> eyre::Result<PathBuf> {
+    let env = tools_dir()?.join(normalize_name(name));
+    if !env.exists() {
+        eyre::bail!("{} is not installed as a tool", name);
+    }
+
+    Ok(env)
+}
+
+/// Installs the package `spec` into a dedicated environment and links its scripts into the
+/// tool bin directory.
+pub async fn install_tool(spec: String, python: Option<VersionString>) -> eyre::Result<()> {
+    let requirement: Requirement = spec.parse()?;
+    let name = requirement.normalized_name();
+
+    let env = tools_dir()?.join(&name);
+    if env.exists() {
+        eyre::bail!(
+            "{} is already installed, upgrade it with `ezpy tool upgrade {}`",
+            name,
+            name
+        );
+    }
+
+    let version = crate::pin::resolve(python, false).await?.version;
+
+    let result = async {
+        crate::venv::create_env(&version, &env, &name, Default::default()).await?;
+
+        let install = ["install", &spec].map(String::from);
+        crate::pip::run(&env, &install, "Failed to install the tool").await?;
+        tokio::fs::write(env.join(TOOL_SPEC_FILE), format!("{}\n", spec)).await?;
+
+        link_scripts(&env, &name).await
+    }
+    .await;
+
+    let linked = match result {
+        Ok(linked) => linked,
+        Err(e) => {
+            tokio::fs::remove_dir_all(&env).await.ok();
+            return Err(e);
+        }
+    };
+
+    let target = Target::new(&env).await?;
+    let version = target.installed()?.remove(&name).unwrap_or("?".to_string());
+
+    println!(
+        "Installed {} {} with scripts: {}",
+        name,
+        version,
+        linked.join(", ")
+    );
+
+    let bin = tool_bin_dir()?;
+    crate::shim::print_path_hint(&bin, "Add the tool bin directory to your PATH to use them:");
+
+    Ok(())
+}
+
+/// Prints a table of the installed tools.
+pub async fn list_tools() -> eyre::Result<()> {
+    let mut rows = Vec::new();
+
+    for name in tool_names().await? {
+        let target = Target::new(&tool_env(&name)?).await?;
+        let version = target.installed()?.remove(&name).unwrap_or("?".to_string());
+        let scripts = target.scripts_of(&name).unwrap_or_default();
+
+        rows.push(vec![
+            name,
+            version,
+            target.version.clone(),
+            scripts.join(", "),
+        ]);
+    }
+
+    if rows.is_empty() {
+        println!("No tools installed");
+        return Ok(());
+    }
+
+    print_table(&["TOOL", "VERSION", "PYTHON", "SCRIPTS"], &rows);
+
+    Ok(())
+}
+
+/// Upgrades the tools `names`, or every tool when `all` is set, relinking their scripts.
+pub async fn upgrade_tools(names: Vec<String>, all: bool) -> eyre::Result<()> {
+    let names = if all {
+        tool_names().await?
+    } else if names.is_empty() {
+        eyre::bail!("Name the tools to upgrade, or pass --all");
+    } else {
+        names.iter().map(|name| normalize_name(name)).collect()
+    };
+
+    if names.is_empty() {
+        println!("No tools installed");
+        return Ok(());
+    }
+
+    for name in names {
+        let env = tool_env(&name)?;
+        let before = Target::new(&env).await?.scripts_of(&name)?;
+
+        // Tools installed before the spec was recorded are upgraded by name.
+        let spec = tokio::fs::read_to_string(env.join(TOOL_SPEC_FILE))
+            .await
+            .map(|spec| spec.trim().to_string())
+            .unwrap_or_else(|_| name.clone());
+
+        let upgrade = ["install", "--upgrade", &spec].map(String::from);
+        crate::pip::run(&env, &upgrade, "Failed to upgrade the tool").await?;
+
+        let linked = link_scripts(&env, &name).await?;
+        for script in before.iter().filter(|script| !linked.contains(script)) {
+            unlink_script(&env, script).await?;
+        }
+
+        println!("Upgraded {}", name);
+    }
+
+    Ok(())
+}
+
+/// Removes the tools `names` along with their environments and linked scripts.
+pub async fn uninstall_tools(names: Vec<String>) -> eyre::Result<()> {
+    for name in names {
+        let name = normalize_name(&name);
+        let env = tool_env(&name)?;
+
+        let scripts = Target::new(&env)
+            .await?
+            .scripts_of(&name)
+            .unwrap_or_default();
+        for script in &scripts {
+            unlink_script(&env, script).await?;
+        }
+
+        tokio::fs::remove_dir_all(&env).await?;
+
+        println!("Uninstalled {}", name);
+    }
+
+    Ok(())
+}
+
+/// Runs `command` from the installed tool of the same name, or from a temporary environment
+/// where `from` (the command name by default) is installed, then exits with its exit code.
+pub async fn run_tool(
+    command: Vec<String>,
+    from: Option<String>,
+    python: Option<VersionString>,
+) -> eyre::Result<()> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| eyre::eyre!("No command provided to run"))?;
+
+    if from.is_none() {
+        if let Ok(env) = tool_env(program) {
+            if crate::is_verbose() {
+                eprintln!("ezpy: running {} from {}", program, env.display());
+            }
+
+            let code = crate::run::run_in_env(&env, program, args).await?;
+            std::process::exit(code);
+        }
+    }
+
+    let spec = from.unwrap_or_else(|| program.clone());
+    let version = crate::pin::resolve_version(python).await?.version;
+
+    let unique = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_nanos();
+    let env =
+        crate::cache_home_ezpy()?
+            .join("tools")
+            .join(format!("{}-{}", std::process::id(), unique));
+
+    if crate::is_verbose() {
+        eprintln!("ezpy: installing {} in {}", spec, env.display());
+    }
+
+    let result = async {
+        crate::venv::create_env(&version, &env, program, Default::default()).await?;
+
+        let install = ["install", "--quiet", &spec].map(String::from);
+        crate::pip::run(&env, &install, "Failed to install the tool").await?;
+
+        crate::run::run_in_env(&env, program, args).await
+    }
+    .await;
+
+    tokio::fs::remove_dir_all(&env).await.ok();
+
+    std::process::exit(result?);
+}
+
+/// The normalized names of the installed tools.
+async fn tool_names() -> eyre::Result<Vec<String>> {
+    let dir = tools_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            names.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+
+    names.sort();
+
+    Ok(names)
+}
+
+/// Links the scripts of the package `name` installed in `env` into the tool bin directory,
+/// returning the ones linked. Files that belong to something else are left alone.
+async fn link_scripts(env: &Path, name: &str) -> eyre::Result<Vec<String>> {
+    let scripts = Target::new(env).await?.scripts_of(name)?;
+    if scripts.is_empty() {
+        eyre::bail!("{} doesn't provide any scripts to install as a tool", name);
+    }
+
+    let bin = tool_bin_dir()?;
+    tokio::fs::create_dir_all(&bin).await?;
+
+    let mut linked = Vec::new();
+    for script in scripts {
+        let link = link_path(&bin, &script);
+        if link.symlink_metadata().is_ok() && !links_into(&link, env) {
+            println!("Skipping {}: {} already exists", script, link.display());
+            continue;
+        }
+
+        tokio::fs::remove_file(&link).await.ok();
+        write_link(env, &script, &link).await?;
+        linked.push(script);
+    }
+
+    Ok(linked)
+}
+
+/// Removes the link to `script` from the tool bin directory when it points into `env`.
+async fn unlink_script(env: &Path, script: &str) -> eyre::Result<()> {
+    let link = link_path(&tool_bin_dir()?, script);
+    if links_into(&link, env) {
+        tokio::fs::remove_file(&link).await?;
+    }
+
+    Ok(())
+}
+
+fn link_path(bin: &Path, script: &str) -> PathBuf {
+    if cfg!(windows) {
+        bin.join(format!("{}.cmd", script))
+    } else {
+        bin.join(script)
+    }
+}
+
+#[cfg(unix)]
+fn links_into(link: &Path, env: &Path) -> bool {
+    std::fs::read_link(link).is_ok_and(|target| target.starts_with(env))
+}
+
+#[cfg(not(unix))]
+fn links_into(link: &Path, env: &Path) -> bool {
+    std::fs::read_to_string(link).is_ok_and(|contents| contents.contains(&*env.to_string_lossy()))
+}
+
+#[cfg(unix)]
+async fn write_link(env: &Path, script: &str, link: &Path) -> eyre::Result<()> {
+    let target = env.join(crate::env_scripts_path()).join(script);
+    tokio::fs::symlink(&target, link).await?;
+
+    Ok(())
+}
+
+/// Scripts of Windows environments locate the interpreter relative to themselves, so the
+/// tool bin directory gets a wrapper calling them instead of a copy.
+#[cfg(not(unix))]
+async fn write_link(env: &Path, script: &str, link: &Path) -> eyre::Result<()> {
+    let target = crate::run::find_program(env, script);
+    tokio::fs::write(link, format!("@\"{}\" %*\r\n", target.display())).await?;
+
+    Ok(())
+}
@@ -1,17 +1,90 @@
-use indygreg::{ensure_python_version, metadata::VersionString};
+use std::path::Path;
+
+use indygreg::metadata::VersionString;
+
+use crate::inspect::{format_age, format_size, print_table, EnvInfo};
+use crate::shell::Shell;
 
 use crate::install_home_ezpy;
 
-pub async fn create_local_env(version: Option<VersionString>) -> eyre::Result<()> {
-    if let Some(version) = version.clone() {
-        ensure_python_version(version)?
+/// Packages seeded into new environments rather than installed by the user.
+pub const SEEDED_PACKAGES: &[&str] = &["pip"];
+
+/// Options controlling how a virtual environment is created.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EnvOptions {
+    /// Spawn `python -m venv` instead of creating the environment natively.
+    pub use_stdlib_venv: bool,
+
+    /// Don't seed pip into the new environment.
+    pub without_pip: bool,
+
+    /// Download the requested Python version when it isn't installed yet.
+    pub install_missing: bool,
+}
+
+pub async fn create_local_env(
+    version: Option<VersionString>,
+    options: EnvOptions,
+) -> eyre::Result<()> {
+    let resolved = crate::pin::resolve(version, options.install_missing).await?;
+    crate::pin::check_requires_python(&resolved).await?;
+    let version = resolved.version;
+
+    let current = std::env::current_dir()?;
+    let dir = current.join(".venv");
+    if dir.exists() {
+        eyre::bail!("Virtual environment already exists at {}", dir.display());
     }
 
-    let version = match version {
-        Some(version) => version,
-        None => crate::pin::get_pinned_version().await?,
-    };
+    let prompt = current
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or(".venv".to_string());
+
+    create_env(&version, &dir, &prompt, options).await?;
+
+    println!(
+        "Created virtual environment with Python version {} at .venv",
+        version
+    );
+
+    Ok(())
+}
+
+pub async fn create_global_env(
+    version: Option<VersionString>,
+    name: String,
+    options: EnvOptions,
+) -> eyre::Result<()> {
+    let version = crate::pin::resolve(version, options.install_missing)
+        .await?
+        .version;
+
+    let dir = install_home_ezpy()?.join("env").join(&name);
+    if dir.exists() {
+        eyre::bail!("Virtual environment already exists at {}", dir.display());
+    }
+
+    create_env(&version, &dir, &name, options).await?;
+
+    println!(
+        "Created global virtual environment {} with Python version {} at {}",
+        name,
+        version,
+        dir.display()
+    );
+
+    Ok(())
+}
 
+/// Creates a virtual environment at `dir` using the installed Python `version`.
+pub async fn create_env(
+    version: &VersionString,
+    dir: &Path,
+    prompt: &str,
+    options: EnvOptions,
+) -> eyre::Result<()> {
     let bin = indygreg::python_path(version.clone())?.join(crate::python_bin_path());
     if !bin.exists() {
         eyre::bail!(
@@ -21,13 +94,37 @@ pub async fn create_local_env(version: Option<VersionString>) -> eyre::Result<()
         );
     }
 
-    let cmd = tokio::process::Command::new(bin)
-        .arg("-m")
-        .arg("venv")
-        .arg(".venv")
-        .spawn()?;
+    if options.use_stdlib_venv {
+        return create_stdlib_env(&bin, dir, options).await;
+    }
+
+    let result = async {
+        crate::pyvenv::create(&bin, version, dir, prompt).await?;
+
+        if !options.without_pip {
+            crate::pyvenv::seed_pip(version, dir).await?;
+        }
+
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        tokio::fs::remove_dir_all(dir).await.ok();
+    }
+
+    result
+}
+
+async fn create_stdlib_env(bin: &Path, dir: &Path, options: EnvOptions) -> eyre::Result<()> {
+    let mut cmd = tokio::process::Command::new(bin);
+    cmd.arg("-m").arg("venv");
+
+    if options.without_pip {
+        cmd.arg("--without-pip");
+    }
 
-    let out = cmd.wait_with_output().await?;
+    let out = cmd.arg(dir).spawn()?.wait_with_output().await?;
 
     if !out.status.success() {
         eyre::bail!(
@@ -36,59 +133,80 @@ pub async fn create_local_env(version: Option<VersionString>) -> eyre::Result<()
         );
     }
 
-    println!(
-        "Created virtual environment with Python version {} at .venv",
-        version
-    );
-
     Ok(())
 }
 
-pub async fn create_global_env(version: Option<VersionString>, name: String) -> eyre::Result<()> {
-    if let Some(version) = version.clone() {
-        ensure_python_version(version)?
+/// Fails unless `name` is a single directory name, so the environment stays in the env directory.
+fn check_env_name(name: &str) -> eyre::Result<()> {
+    if name.is_empty() || matches!(name, "." | "..") || name.contains(['/', '\\']) {
+        eyre::bail!("Invalid environment name '{}'", name);
     }
 
-    let version = match version {
-        Some(version) => version,
-        None => crate::pin::get_pinned_version().await?,
-    };
+    Ok(())
+}
 
-    let bin = indygreg::python_path(version.clone())?.join(crate::python_bin_path());
-    if !bin.exists() {
+pub async fn clone_global_env(src: String, dst: String) -> eyre::Result<()> {
+    check_env_name(&dst)?;
+    let source = get_global_env(src.clone())?;
+    let destination = install_home_ezpy()?.join("env").join(&dst);
+    if destination.exists() {
         eyre::bail!(
-            "Python version {} is not installed, please install it with `ezpy install python {}`",
-            version,
-            version
+            "Virtual environment already exists at {}",
+            destination.display()
         );
     }
 
-    let dir = install_home_ezpy()?.join("env").join(&name);
-    if dir.exists() {
-        eyre::bail!("Virtual environment already exists at {}", dir.display());
+    let (from, to) = (source.clone(), destination.clone());
+    let copied = tokio::task::spawn_blocking(move || {
+        crate::relocate::copy_dir(&from, &to)?;
+        crate::relocate::rewrite_paths(&to, &from, &to)
+    })
+    .await?;
+
+    if let Err(e) = copied {
+        tokio::fs::remove_dir_all(&destination).await.ok();
+        return Err(e);
     }
 
-    let cmd = tokio::process::Command::new(bin)
-        .arg("-m")
-        .arg("venv")
-        .arg(dir.clone())
-        .spawn()?;
+    crate::relocate::set_prompt(&destination, &dst).await?;
 
-    let out = cmd.wait_with_output().await?;
+    println!("Cloned global virtual environment {} to {}", src, dst);
 
-    if !out.status.success() {
+    Ok(())
+}
+
+pub async fn rename_global_env(old: String, new: String) -> eyre::Result<()> {
+    check_env_name(&new)?;
+    let source = get_global_env(old.clone())?;
+    let destination = install_home_ezpy()?.join("env").join(&new);
+    if destination.exists() {
         eyre::bail!(
-            "Failed to create virtual environment: {}",
-            String::from_utf8_lossy(&out.stderr)
+            "Virtual environment already exists at {}",
+            destination.display()
         );
     }
 
-    println!(
-        "Created global virtual environment {} with Python version {} at {}",
-        name,
-        version,
-        dir.display()
-    );
+    tokio::fs::rename(&source, &destination).await?;
+
+    let (from, to) = (source.clone(), destination.clone());
+    let mut renamed =
+        tokio::task::spawn_blocking(move || crate::relocate::rewrite_paths(&to, &from, &to))
+            .await?;
+    if renamed.is_ok() {
+        renamed = crate::relocate::set_prompt(&destination, &new).await;
+    }
+
+    // Put the environment back as it was, rewriting whichever paths were already changed.
+    if let Err(e) = renamed {
+        let (from, to) = (destination.clone(), source.clone());
+        tokio::task::spawn_blocking(move || crate::relocate::rewrite_paths(&from, &from, &to))
+            .await?
+            .ok();
+        tokio::fs::rename(&destination, &source).await.ok();
+        return Err(e);
+    }
+
+    println!("Renamed global virtual environment {} to {}", old, new);
 
     Ok(())
 }
@@ -106,27 +224,83 @@ pub async fn delete_global_env(name: String) -> eyre::Result<()> {
     Ok(())
 }
 
-pub async fn list_global_envs() -> eyre::Result<()> {
+pub async fn list_envs(json: bool) -> eyre::Result<()> {
+    let mut envs = Vec::new();
+
+    if let Some(local) = find_nearest_env(&std::env::current_dir()?).unwrap_or(None) {
+        let global_dir = install_home_ezpy()?.join("env");
+        if !local.starts_with(&global_dir) {
+            let name = crate::shell::env_prompt(&local);
+            envs.push(EnvInfo::collect(name, "local", local).await);
+        }
+    }
+
     let env_dir = install_home_ezpy()?.join("env");
+    if env_dir.exists() {
+        let mut names = Vec::new();
+
+        let mut entries = tokio::fs::read_dir(env_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                names.push((
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path(),
+                ));
+            }
+        }
 
-    if !env_dir.exists() {
-        println!("No global virtual environments found");
+        names.sort();
+        for (name, path) in names {
+            envs.push(EnvInfo::collect(name, "global", path).await);
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&envs)?);
         return Ok(());
     }
 
-    let mut entries = tokio::fs::read_dir(env_dir).await?;
-    while let Some(entry) = entries.next_entry().await? {
-        println!("{:?}", entry.file_name());
+    if envs.is_empty() {
+        println!("No virtual environments found");
+        return Ok(());
     }
 
+    let rows = envs
+        .iter()
+        .map(|env| {
+            vec![
+                env.name.clone(),
+                env.kind.clone(),
+                env.python_version.clone().unwrap_or("?".to_string()),
+                env.base_interpreter
+                    .as_ref()
+                    .map(|base| base.display().to_string())
+                    .unwrap_or("?".to_string()),
+                if env.base_exists { "yes" } else { "missing" }.to_string(),
+                format_size(env.size),
+                env.packages.to_string(),
+                env.created.map(format_age).unwrap_or("?".to_string()),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    print_table(
+        &[
+            "NAME", "KIND", "PYTHON", "BASE", "BASE OK", "SIZE", "PACKAGES", "CREATED",
+        ],
+        &rows,
+    );
+
     Ok(())
 }
 
-pub async fn activate_env(global: Option<String>) -> eyre::Result<()> {
-    let env = match global {
-        Some(name) => get_global_env(name)?,
-        None => get_nearest_env().await?,
-    };
+pub async fn activate_env(global: Option<String>, eval: Option<Shell>) -> eyre::Result<()> {
+    let env = resolve_env(global).await?;
+
+    if let Some(shell) = eval {
+        print!("{}", crate::shell::activate_code(shell, &env));
+        return Ok(());
+    }
 
     let bin = env.join(crate::python_bin_path());
     if !bin.exists() {
@@ -145,28 +319,57 @@ pub async fn activate_env(global: Option<String>) -> eyre::Result<()> {
     } else {
         println!(". {}", bin.parent().unwrap().join("activate").display());
     }
+    println!("Or spawn an activated shell with `ezpy shell`, or use `eval \"$(ezpy env activate --eval bash)\"`");
 
     Ok(())
 }
 
-pub async fn deactivate_env() -> eyre::Result<()> {
+pub async fn deactivate_env(eval: Option<Shell>) -> eyre::Result<()> {
+    if let Some(shell) = eval {
+        print!("{}", crate::shell::deactivate_code(shell));
+        return Ok(());
+    }
+
     println!("To deactivate this environment, run:");
     println!("deactivate");
+    println!("If it was activated with `ezpy shell`, run `exit` instead");
 
     Ok(())
 }
 
 pub async fn show_env_path(global: Option<String>) -> eyre::Result<()> {
-    let env = match global {
-        Some(name) => get_global_env(name)?,
-        None => get_nearest_env().await?,
-    };
+    let env = resolve_env(global).await?;
 
     println!("{}", env.display());
 
     Ok(())
 }
 
+/// Returns the global environment `name` if given, the nearest local environment otherwise.
+pub async fn resolve_env(global: Option<String>) -> eyre::Result<std::path::PathBuf> {
+    match global {
+        Some(name) => get_global_env(name),
+        None => get_nearest_env().await,
+    }
+}
+
+/// Fails when `env` doesn't use the Python version requested with `--python`, since commands
+/// working in an existing environment can't switch its interpreter.
+pub async fn check_env_python(env: &Path, python: &str) -> eyre::Result<()> {
+    let config = crate::pyvenv::PyvenvConfig::read(env).await?;
+
+    if !crate::pin::matches_request(&config.version, python) {
+        eyre::bail!(
+            "{} uses Python {}, not Python {} from --python",
+            env.display(),
+            config.version,
+            python
+        );
+    }
+
+    Ok(())
+}
+
 pub fn get_global_env(name: String) -> eyre::Result<std::path::PathBuf> {
     let dir = install_home_ezpy()?.join("env").join(&name);
     if !dir.exists() {
@@ -180,24 +383,134 @@ pub fn get_global_env(name: String) -> eyre::Result<std::path::PathBuf> {
 }
 
 pub async fn get_nearest_env() -> eyre::Result<std::path::PathBuf> {
-    let mut current = std::env::current_dir()?;
+    locate_env(&std::env::current_dir()?)?
+        .ok_or_else(|| eyre::eyre!("No virtual environment found"))
+}
 
-    let mut count = 0;
-    loop {
-        if count > 3 {
-            break;
+/// Returns the active environment from `VIRTUAL_ENV` if there is one, otherwise the nearest
+/// environment found from `start`.
+pub fn locate_env(start: &Path) -> eyre::Result<Option<std::path::PathBuf>> {
+    if let Some(active) = std::env::var_os("VIRTUAL_ENV").filter(|env| !env.is_empty()) {
+        let active = std::path::PathBuf::from(active);
+        if is_valid_env(&active) {
+            verbose(format!(
+                "using {} from the VIRTUAL_ENV environment variable",
+                active.display()
+            ));
+            return Ok(Some(active));
         }
 
-        count += 1;
+        verbose(format!(
+            "ignoring VIRTUAL_ENV={}, it has no pyvenv.cfg",
+            active.display()
+        ));
+    }
 
-        if current.join(".venv").exists() {
-            return Ok(current.join(".venv"));
+    find_nearest_env(start)
+}
+
+/// Looks for a local environment, or a global environment named in `.ezpy.toml`, in `start`
+/// and its parents, stopping at the root of the repository.
+pub fn find_nearest_env(start: &Path) -> eyre::Result<Option<std::path::PathBuf>> {
+    let names = env_names(start)?;
+
+    for dir in start.ancestors() {
+        let config = dir.join(crate::config::PROJECT_CONFIG_FILE);
+        if config.is_file() {
+            if let Some(name) = crate::config::ProjectConfig::read(&config)?.env {
+                verbose(format!(
+                    "using global environment {} named in {}",
+                    name,
+                    config.display()
+                ));
+                return Ok(Some(get_global_env(name)?));
+            }
+        }
+
+        for name in &names {
+            let env = dir.join(name);
+            if is_valid_env(&env) {
+                verbose(format!(
+                    "using {} found from {}",
+                    env.display(),
+                    start.display()
+                ));
+                return Ok(Some(env));
+            }
+
+            if env.is_dir() {
+                verbose(format!("skipping {}, it has no pyvenv.cfg", env.display()));
+            }
         }
 
-        if !current.pop() {
+        if VCS_MARKERS.iter().any(|marker| dir.join(marker).exists()) {
+            verbose(format!("stopping at repository root {}", dir.display()));
             break;
         }
     }
 
-    return Err(eyre::eyre!("No virtual environment found"));
+    verbose(format!(
+        "no environment named {} found from {}",
+        names.join(", "),
+        start.display()
+    ));
+
+    Ok(None)
+}
+
+const VCS_MARKERS: &[&str] = &[".git", ".hg", ".svn", ".jj"];
+
+/// Environment variable overriding the directory names recognized as local environments.
+pub const ENV_NAMES_VAR: &str = "EZPY_ENV_NAMES";
+
+/// The directory names recognized as local environments, from `EZPY_ENV_NAMES`, the nearest
+/// `.ezpy.toml`, or `.venv` by default.
+pub fn env_names(start: &Path) -> eyre::Result<Vec<String>> {
+    if let Ok(names) = std::env::var(ENV_NAMES_VAR) {
+        let names = names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        if !names.is_empty() {
+            return Ok(names);
+        }
+    }
+
+    if let Some(config) = crate::find_upwards(start, crate::config::PROJECT_CONFIG_FILE) {
+        if let Some(names) = crate::config::ProjectConfig::read(&config)?.env_names {
+            return Ok(names);
+        }
+    }
+
+    Ok(vec![".venv".to_string()])
+}
+
+/// A directory is a virtual environment only if it contains a `pyvenv.cfg`.
+pub fn is_valid_env(env: &Path) -> bool {
+    env.join("pyvenv.cfg").is_file()
+}
+
+fn verbose(message: String) {
+    if crate::is_verbose() {
+        eprintln!("ezpy: {}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_env_names_leaving_the_env_directory() {
+        for name in ["", ".", "..", "../../x", "a/b", "a\\b"] {
+            assert_eq!(
+                check_env_name(name).unwrap_err().to_string(),
+                format!("Invalid environment name '{}'", name)
+            );
+        }
+        assert!(check_env_name("my-env.2").is_ok());
+    }
 }
@@ -0,0 +1,1204 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+
+use base64::Engine;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+use crate::pep440::Version;
+use crate::pep508::normalize_name;
+use crate::pyvenv::PyvenvConfig;
+
+/// Name written to the `INSTALLER` file of every distribution installed by ezpy.
+pub const INSTALLER: &str = "ezpy";
+
+/// The components of a wheel file name, `{name}-{version}(-{build})?-{python}-{abi}-{platform}.whl`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WheelFilename {
+    pub name: String,
+    pub version: Version,
+    pub build: Option<String>,
+    pub python: Vec<String>,
+    pub abi: Vec<String>,
+    pub platform: Vec<String>,
+}
+
+impl FromStr for WheelFilename {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let stem = s
+            .strip_suffix(".whl")
+            .ok_or_else(|| eyre::eyre!("Invalid wheel file name '{}'", s))?;
+
+        let parts = stem.split('-').collect::<Vec<_>>();
+        let (name, version, build, tags) = match parts.as_slice() {
+            [name, version, python, abi, platform] => {
+                (name, version, None, [python, abi, platform])
+            }
+            [name, version, build, python, abi, platform] => (
+                name,
+                version,
+                Some(build.to_string()),
+                [python, abi, platform],
+            ),
+            _ => eyre::bail!("Invalid wheel file name '{}'", s),
+        };
+
+        let split = |tag: &str| tag.split('.').map(str::to_string).collect::<Vec<_>>();
+
+        Ok(WheelFilename {
+            name: name.to_string(),
+            version: version.parse()?,
+            build,
+            python: split(tags[0]),
+            abi: split(tags[1]),
+            platform: split(tags[2]),
+        })
+    }
+}
+
+/// The core metadata of a distribution, read from its `METADATA` file.
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub name: String,
+    pub version: String,
+    pub requires_dist: Vec<String>,
+    pub requires_python: Option<String>,
+    pub provides_extra: Vec<String>,
+}
+
+impl Metadata {
+    /// Parses the email style headers of a `METADATA` file, ignoring the description body.
+    pub fn parse(contents: &str) -> Self {
+        let mut metadata = Metadata::default();
+        let (headers, _) = parse_headers(contents);
+
+        for (key, value) in headers {
+            match key.to_ascii_lowercase().as_str() {
+                "name" => metadata.name = value,
+                "version" => metadata.version = value,
+                "requires-dist" => metadata.requires_dist.push(value),
+                "requires-python" => metadata.requires_python = Some(value),
+                "provides-extra" => metadata.provides_extra.push(value),
+                _ => {}
+            }
+        }
+
+        metadata
+    }
+}
+
+/// Splits the email style headers of a `METADATA` or `PKG-INFO` file from its body,
+/// joining continuation lines.
+pub fn parse_headers(contents: &str) -> (Vec<(String, String)>, Option<String>) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut lines = contents.lines();
+
+    for line in lines.by_ref() {
+        if line.is_empty() {
+            break;
+        }
+
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let body = lines.collect::<Vec<_>>().join("\n");
+    let body = (!body.trim().is_empty()).then_some(body);
+
+    (headers, body)
+}
+
+/// Where a distribution's files go inside a virtual environment.
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub env: PathBuf,
+    pub site_packages: PathBuf,
+    pub scripts: PathBuf,
+
+    /// The environment's interpreter, used in script shebangs.
+    pub python: PathBuf,
+
+    /// The full version of the environment's interpreter.
+    pub version: String,
+}
+
+impl Target {
+    pub async fn new(env: &Path) -> eyre::Result<Self> {
+        let config = PyvenvConfig::read(env).await?;
+
+        Ok(Target {
+            env: env.to_path_buf(),
+            site_packages: config.site_packages(env),
+            scripts: env.join(crate::env_scripts_path()),
+            python: env.join(crate::env_bin_path()),
+            version: config.version,
+        })
+    }
+
+    /// Lists the normalized name and version of every installed distribution.
+    pub fn installed(&self) -> eyre::Result<HashMap<String, String>> {
+        let mut installed = HashMap::new();
+
+        if !self.site_packages.exists() {
+            return Ok(installed);
+        }
+
+        for entry in std::fs::read_dir(&self.site_packages)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some((name, version)) = name
+                .strip_suffix(".dist-info")
+                .and_then(|stem| stem.split_once('-'))
+            {
+                installed.insert(normalize_name(name), version.to_string());
+            }
+        }
+
+        Ok(installed)
+    }
+
+    /// The names of the console and GUI scripts declared by the installed distribution `name`.
+    pub fn scripts_of(&self, name: &str) -> eyre::Result<Vec<String>> {
+        let Some(dist_info) = self.find_dist_info(name)? else {
+            eyre::bail!("{} is not installed in {}", name, self.env.display());
+        };
+
+        let entry_points = match std::fs::read_to_string(dist_info.join("entry_points.txt")) {
+            Ok(contents) => contents,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        Ok(parse_entry_points(&entry_points)
+            .into_iter()
+            .map(|(name, _, _, _)| name)
+            .collect())
+    }
+
+    /// The `METADATA` of the installed distribution `name`, when it's installed.
+    pub fn metadata_of(&self, name: &str) -> eyre::Result<Option<Metadata>> {
+        let Some(dist_info) = self.find_dist_info(name)? else {
+            return Ok(None);
+        };
+
+        let contents = std::fs::read_to_string(dist_info.join("METADATA"))?;
+
+        Ok(Some(Metadata::parse(&contents)))
+    }
+
+    fn find_dist_info(&self, name: &str) -> eyre::Result<Option<PathBuf>> {
+        let name = normalize_name(name);
+
+        if !self.site_packages.exists() {
+            return Ok(None);
+        }
+
+        for entry in std::fs::read_dir(&self.site_packages)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some((dist, _)) = file_name
+                .strip_suffix(".dist-info")
+                .and_then(|stem| stem.split_once('-'))
+            else {
+                continue;
+            };
+
+            if normalize_name(dist) == name {
+                return Ok(Some(entry.path()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The path written to `RECORD` for a file installed at `path`, relative to site-packages.
+    fn record_path(&self, path: &Path) -> String {
+        let relative = match path.strip_prefix(&self.site_packages) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => {
+                let depth = self
+                    .site_packages
+                    .strip_prefix(&self.env)
+                    .map(|relative| relative.components().count())
+                    .unwrap_or(0);
+
+                let mut relative = PathBuf::new();
+                for _ in 0..depth {
+                    relative.push("..");
+                }
+                relative.join(path.strip_prefix(&self.env).unwrap_or(path))
+            }
+        };
+
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// An opened wheel archive.
+pub struct Wheel {
+    pub filename: WheelFilename,
+    archive: zip::ZipArchive<File>,
+    dist_info: String,
+}
+
+impl Wheel {
+    pub fn open(path: &Path) -> eyre::Result<Self> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let filename: WheelFilename = name.parse()?;
+
+        let file = File::open(path)
+            .map_err(|e| eyre::eyre!("Failed to open {}: {}", path.display(), e))?;
+        let archive = zip::ZipArchive::new(file)
+            .map_err(|e| eyre::eyre!("{} is not a valid wheel: {}", path.display(), e))?;
+
+        let dist_info = archive
+            .file_names()
+            .filter_map(|name| name.split_once('/').map(|(dir, _)| dir))
+            .find(|dir| {
+                dir.strip_suffix(".dist-info")
+                    .and_then(|stem| stem.split_once('-'))
+                    .is_some_and(|(dist, _)| normalize_name(dist) == normalize_name(&filename.name))
+            })
+            .map(str::to_string)
+            .ok_or_else(|| {
+                eyre::eyre!("{} doesn't contain a .dist-info directory", path.display())
+            })?;
+
+        Ok(Wheel {
+            filename,
+            archive,
+            dist_info,
+        })
+    }
+
+    fn read(&mut self, name: &str) -> eyre::Result<Option<String>> {
+        let path = format!("{}/{}", self.dist_info, name);
+
+        let mut file = match self.archive.by_name(&path) {
+            Ok(file) => file,
+            Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+
+        Ok(Some(contents))
+    }
+
+    pub fn metadata(&mut self) -> eyre::Result<Metadata> {
+        Ok(Metadata::parse(&self.metadata_contents()?))
+    }
+
+    /// The raw `METADATA` file of the wheel.
+    pub fn metadata_contents(&mut self) -> eyre::Result<String> {
+        self.read("METADATA")?.ok_or_else(|| {
+            eyre::eyre!(
+                "{} is missing from the wheel",
+                format!("{}/METADATA", self.dist_info)
+            )
+        })
+    }
+
+    /// Installs the wheel into `target`, replacing any installed version of the distribution.
+    pub fn install(&mut self, target: &Target) -> eyre::Result<Metadata> {
+        self.install_from(target, None)
+    }
+
+    /// Installs the wheel like [`Wheel::install`], recording where it came from in
+    /// `direct_url.json` as described by PEP 610.
+    pub fn install_from(
+        &mut self,
+        target: &Target,
+        direct_url: Option<&str>,
+    ) -> eyre::Result<Metadata> {
+        let metadata = self.metadata()?;
+
+        if let Some(wheel) = self.read("WHEEL")? {
+            let major = wheel
+                .lines()
+                .find_map(|line| line.strip_prefix("Wheel-Version:"))
+                .and_then(|version| version.trim().split('.').next()?.parse::<u64>().ok());
+            if major.is_some_and(|major| major > 1) {
+                eyre::bail!(
+                    "{} uses an unsupported wheel format version",
+                    self.filename.name
+                );
+            }
+        }
+
+        let expected = self
+            .read("RECORD")?
+            .map(|record| {
+                parse_record(&record)
+                    .into_iter()
+                    .filter(|(_, hash, _)| !hash.is_empty())
+                    .map(|(path, hash, _)| (path, hash))
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+
+        let data_prefix = format!("{}.data/", self.dist_info.trim_end_matches(".dist-info"));
+        let record_name = format!("{}/RECORD", self.dist_info);
+
+        // Every path and hash is checked before touching the installed version, so a broken
+        // wheel leaves it intact.
+        let mut files = Vec::new();
+        for index in 0..self.archive.len() {
+            let mut file = self.archive.by_index(index)?;
+            if file.is_dir() {
+                continue;
+            }
+
+            let name = file.name().to_string();
+            let relative = file
+                .enclosed_name()
+                .ok_or_else(|| eyre::eyre!("Refusing to install unsafe path '{}'", name))?;
+
+            if name == record_name || name.ends_with("/RECORD.jws") || name.ends_with("/RECORD.p7s")
+            {
+                continue;
+            }
+
+            let (destination, script) = match name.strip_prefix(&data_prefix) {
+                Some(rest) => {
+                    let (scheme, path) = rest
+                        .split_once('/')
+                        .ok_or_else(|| eyre::eyre!("Invalid data file '{}'", name))?;
+
+                    // `enclosed_name` only checks the whole name, not the part below the scheme.
+                    if !Path::new(path)
+                        .components()
+                        .all(|component| matches!(component, Component::Normal(_)))
+                    {
+                        eyre::bail!("Refusing to install unsafe path '{}'", name);
+                    }
+
+                    let root = match scheme {
+                        "purelib" | "platlib" => target.site_packages.clone(),
+                        "scripts" => target.scripts.clone(),
+                        "headers" => headers_dir(target, &metadata.name),
+                        "data" => target.env.clone(),
+                        _ => eyre::bail!("Unknown wheel data directory '{}'", scheme),
+                    };
+
+                    (root.join(path), scheme == "scripts")
+                }
+                None => (target.site_packages.join(relative), false),
+            };
+
+            if let Some(expected) = expected.get(&name) {
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                verify_record_hash(&name, expected, &contents, &self.filename.name)?;
+            }
+
+            let executable = script || file.unix_mode().is_some_and(|mode| mode & 0o111 != 0);
+            files.push((index, destination, script, executable));
+        }
+
+        uninstall(target, &metadata.name)?;
+
+        let mut record: Vec<(String, String, String)> = Vec::new();
+
+        for (index, destination, script, executable) in files {
+            let mut contents = Vec::new();
+            self.archive.by_index(index)?.read_to_end(&mut contents)?;
+
+            if script && contents.starts_with(b"#!python") {
+                let end = contents
+                    .iter()
+                    .position(|byte| *byte == b'\n')
+                    .unwrap_or(contents.len());
+                let mut rewritten = shebang(&target.python).into_bytes();
+                rewritten.extend_from_slice(&contents[end..]);
+                contents = rewritten;
+            }
+
+            write_file(&destination, &contents, executable)?;
+            record.push(record_entry(target, &destination, &contents));
+        }
+
+        if let Some(entry_points) = self.read("entry_points.txt")? {
+            for (name, module, attr, gui) in parse_entry_points(&entry_points) {
+                for (path, contents) in entry_point_scripts(target, &name, &module, &attr, gui) {
+                    write_file(&path, contents.as_bytes(), true)?;
+                    record.push(record_entry(target, &path, contents.as_bytes()));
+                }
+            }
+        }
+
+        let dist_info = target.site_packages.join(&self.dist_info);
+
+        let installer = dist_info.join("INSTALLER");
+        let contents = format!("{}\n", INSTALLER);
+        write_file(&installer, contents.as_bytes(), false)?;
+        record.push(record_entry(target, &installer, contents.as_bytes()));
+
+        if let Some(direct_url) = direct_url {
+            let path = dist_info.join("direct_url.json");
+            write_file(&path, direct_url.as_bytes(), false)?;
+            record.push(record_entry(target, &path, direct_url.as_bytes()));
+        }
+
+        record.push((
+            format!("{}/RECORD", self.dist_info),
+            String::new(),
+            String::new(),
+        ));
+
+        let contents = record
+            .iter()
+            .map(|(path, hash, size)| format!("{},{},{}\n", quote_record(path), hash, size))
+            .collect::<String>();
+        std::fs::write(dist_info.join("RECORD"), contents)?;
+
+        Ok(metadata)
+    }
+}
+
+/// Removes the installed distribution `name` using its `RECORD`, returning its version when it
+/// was installed.
+pub fn uninstall(target: &Target, name: &str) -> eyre::Result<Option<String>> {
+    let Some(dist_info) = target.find_dist_info(name)? else {
+        return Ok(None);
+    };
+
+    let version = dist_info.file_name().and_then(|name| {
+        name.to_string_lossy()
+            .strip_suffix(".dist-info")?
+            .split_once('-')
+            .map(|(_, version)| version.to_string())
+    });
+
+    let record = std::fs::read_to_string(dist_info.join("RECORD")).map_err(|_| {
+        eyre::eyre!(
+            "Can't uninstall {}, {} has no RECORD file",
+            name,
+            dist_info.display()
+        )
+    })?;
+
+    let mut parents = HashSet::new();
+    for (path, _, _) in parse_record(&record) {
+        let path = normalize_path(&target.site_packages.join(path));
+        if !path.starts_with(&target.env) {
+            continue;
+        }
+
+        if path.extension().is_some_and(|extension| extension == "py") {
+            remove_bytecode(&path);
+            if let Some(parent) = path.parent() {
+                parents.insert(parent.join("__pycache__"));
+            }
+        }
+
+        match std::fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        if let Some(parent) = path.parent() {
+            parents.insert(parent.to_path_buf());
+        }
+    }
+
+    std::fs::remove_dir_all(&dist_info).ok();
+
+    // Remove the directories left empty, deepest first.
+    let mut parents = parents.into_iter().collect::<Vec<_>>();
+    parents.sort_by_key(|parent| std::cmp::Reverse(parent.components().count()));
+    for parent in parents {
+        let mut dir = parent.as_path();
+        while dir != target.site_packages && dir != target.scripts && dir.starts_with(&target.env) {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+            match dir.parent() {
+                Some(parent) => dir = parent,
+                None => break,
+            }
+        }
+    }
+
+    Ok(version)
+}
+
+fn remove_bytecode(source: &Path) {
+    let (Some(parent), Some(stem)) = (source.parent(), source.file_stem()) else {
+        return;
+    };
+
+    let cache = parent.join("__pycache__");
+    let Ok(entries) = std::fs::read_dir(&cache) else {
+        return;
+    };
+
+    let prefix = format!("{}.", stem.to_string_lossy());
+    for entry in entries.flatten() {
+        if entry.file_name().to_string_lossy().starts_with(&prefix) {
+            std::fs::remove_file(entry.path()).ok();
+        }
+    }
+}
+
+/// Resolves `.` and `..` components without touching the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+fn headers_dir(target: &Target, name: &str) -> PathBuf {
+    if cfg!(windows) {
+        target.env.join("Include").join(name)
+    } else {
+        target
+            .env
+            .join("include")
+            .join("site")
+            .join(format!(
+                "python{}",
+                crate::pyvenv::short_version(&target.version)
+            ))
+            .join(name)
+    }
+}
+
+fn write_file(path: &Path, contents: &[u8], executable: bool) -> eyre::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(path, contents)?;
+
+    #[cfg(unix)]
+    if executable {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = executable;
+
+    Ok(())
+}
+
+/// The `sha256=` digest used in `RECORD` files.
+pub fn digest(contents: &[u8]) -> String {
+    record_digest("sha256", contents).unwrap()
+}
+
+/// The `algorithm=` digest of `contents` in the `RECORD` format, for the algorithms ezpy can
+/// compute.
+fn record_digest(algorithm: &str, contents: &[u8]) -> Option<String> {
+    let hash = match algorithm {
+        "sha256" => Sha256::digest(contents).to_vec(),
+        "sha384" => Sha384::digest(contents).to_vec(),
+        "sha512" => Sha512::digest(contents).to_vec(),
+        _ => return None,
+    };
+
+    Some(format!(
+        "{}={}",
+        algorithm,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(hash)
+    ))
+}
+
+/// Checks `contents` of the archive member `name` against its `RECORD` hash, which the wheel
+/// specification allows to use any algorithm but md5 and sha1.
+fn verify_record_hash(
+    name: &str,
+    expected: &str,
+    contents: &[u8],
+    wheel: &str,
+) -> eyre::Result<()> {
+    let algorithm = expected
+        .split_once('=')
+        .map_or(expected, |(algorithm, _)| algorithm);
+    if matches!(algorithm, "md5" | "sha1") {
+        eyre::bail!(
+            "{} uses the insecure {} hash for '{}' in its RECORD",
+            wheel,
+            algorithm,
+            name
+        );
+    }
+
+    let Some(actual) = record_digest(algorithm, contents) else {
+        if crate::is_verbose() {
+            eprintln!(
+                "ezpy: can't verify '{}', its {} hash is unsupported",
+                name, algorithm
+            );
+        }
+        return Ok(());
+    };
+
+    if actual != expected {
+        eyre::bail!(
+            "Hash mismatch for '{}' in {}, the wheel may be corrupted",
+            name,
+            wheel
+        );
+    }
+
+    Ok(())
+}
+
+fn record_entry(target: &Target, path: &Path, contents: &[u8]) -> (String, String, String) {
+    (
+        target.record_path(path),
+        digest(contents),
+        contents.len().to_string(),
+    )
+}
+
+fn quote_record(path: &str) -> String {
+    if path.contains([',', '"']) {
+        format!("\"{}\"", path.replace('"', "\"\""))
+    } else {
+        path.to_string()
+    }
+}
+
+/// Parses the `path,hash,size` rows of a `RECORD` file.
+pub fn parse_record(contents: &str) -> Vec<(String, String, String)> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut fields = Vec::new();
+            let mut field = String::new();
+            let mut quoted = false;
+            let mut chars = line.chars().peekable();
+
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' if quoted && chars.peek() == Some(&'"') => {
+                        field.push('"');
+                        chars.next();
+                    }
+                    '"' => quoted = !quoted,
+                    ',' if !quoted => fields.push(std::mem::take(&mut field)),
+                    c => field.push(c),
+                }
+            }
+            fields.push(field);
+
+            let mut fields = fields.into_iter();
+            (
+                fields.next().unwrap_or_default(),
+                fields.next().unwrap_or_default(),
+                fields.next().unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+/// Parses the `console_scripts` and `gui_scripts` sections of `entry_points.txt` into
+/// `(name, module, attribute, gui)` tuples.
+fn parse_entry_points(contents: &str) -> Vec<(String, String, String, bool)> {
+    let mut entry_points = Vec::new();
+    let mut section = String::new();
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+
+        if let Some(name) = line
+            .strip_prefix('[')
+            .and_then(|line| line.strip_suffix(']'))
+        {
+            section = name.trim().to_string();
+            continue;
+        }
+
+        if section != "console_scripts" && section != "gui_scripts" {
+            continue;
+        }
+
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        // Drop the extras some entry points still declare, e.g. `module:main [cli]`.
+        let value = value.split('[').next().unwrap_or_default().trim();
+        let Some((module, attr)) = value.split_once(':') else {
+            continue;
+        };
+
+        entry_points.push((
+            name.trim().to_string(),
+            module.trim().to_string(),
+            attr.trim().to_string(),
+            section == "gui_scripts",
+        ));
+    }
+
+    entry_points
+}
+
+fn shebang(python: &Path) -> String {
+    let python = python.display().to_string();
+
+    // Shebangs can't contain spaces and are limited in length on most systems.
+    if python.contains(' ') || python.len() > 127 {
+        format!("#!/bin/sh\n'''exec' '{}' \"$0\" \"$@\"\n' '''", python)
+    } else {
+        format!("#!{}", python)
+    }
+}
+
+fn entry_point_scripts(
+    target: &Target,
+    name: &str,
+    module: &str,
+    attr: &str,
+    gui: bool,
+) -> Vec<(PathBuf, String)> {
+    let import = attr.split('.').next().unwrap_or(attr);
+    let body = format!(
+        "# -*- coding: utf-8 -*-\nimport re\nimport sys\nfrom {module} import {import}\nif __name__ == \"__main__\":\n    sys.argv[0] = re.sub(r\"(-script\\.pyw|\\.exe)?$\", \"\", sys.argv[0])\n    sys.exit({attr}())\n"
+    );
+
+    if cfg!(windows) {
+        let (suffix, python) = if gui {
+            ("-script.pyw", "pythonw.exe")
+        } else {
+            ("-script.py", "python.exe")
+        };
+
+        vec![
+            (target.scripts.join(format!("{}{}", name, suffix)), body),
+            (
+                target.scripts.join(format!("{}.cmd", name)),
+                format!("@\"%~dp0{}\" \"%~dp0{}{}\" %*\r\n", python, name, suffix),
+            ),
+        ]
+    } else {
+        vec![(
+            target.scripts.join(name),
+            format!("{}\n{}", shebang(&target.python), body),
+        )]
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// A fresh directory under the system temporary directory, unique to the test `name`.
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ezpy-test-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a zip archive with `files` at `path`.
+    pub fn write_zip(path: &Path, files: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, contents) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Builds the wheel `filename` in `dir` from `files`, adding its `METADATA` (with
+    /// `requires_dist`), `WHEEL` and `RECORD` files.
+    pub fn write_wheel(
+        dir: &Path,
+        filename: &str,
+        requires_dist: &[&str],
+        files: &[(&str, &str)],
+    ) -> PathBuf {
+        let wheel: WheelFilename = filename.parse().unwrap();
+        let dist_info = format!("{}-{}.dist-info", wheel.name, wheel.version);
+
+        let mut metadata = format!(
+            "Metadata-Version: 2.1\nName: {}\nVersion: {}\n",
+            wheel.name, wheel.version
+        );
+        for requirement in requires_dist {
+            metadata.push_str(&format!("Requires-Dist: {}\n", requirement));
+        }
+
+        let mut files = files
+            .iter()
+            .map(|(name, contents)| (name.to_string(), contents.to_string()))
+            .collect::<Vec<_>>();
+        files.push((format!("{}/METADATA", dist_info), metadata));
+        files.push((
+            format!("{}/WHEEL", dist_info),
+            "Wheel-Version: 1.0\nRoot-Is-Purelib: true\nTag: py3-none-any\n".to_string(),
+        ));
+
+        let mut record = files
+            .iter()
+            .map(|(name, contents)| {
+                format!(
+                    "{},{},{}\n",
+                    name,
+                    digest(contents.as_bytes()),
+                    contents.len()
+                )
+            })
+            .collect::<String>();
+        record.push_str(&format!("{}/RECORD,,\n", dist_info));
+        files.push((format!("{}/RECORD", dist_info), record));
+
+        let path = dir.join(filename);
+        let files = files
+            .iter()
+            .map(|(name, contents)| (name.as_str(), contents.as_str()))
+            .collect::<Vec<_>>();
+        write_zip(&path, &files);
+
+        path
+    }
+
+    /// A target for an environment at `env` on Python 3.12, without an interpreter.
+    pub fn test_target(env: &Path) -> Target {
+        let scripts = env.join(crate::env_scripts_path());
+
+        Target {
+            env: env.to_path_buf(),
+            site_packages: env.join(crate::site_packages_path("3.12.1")),
+            python: scripts.join("python"),
+            scripts,
+            version: "3.12.1".to_string(),
+        }
+    }
+
+    fn demo_wheel(dir: &Path) -> PathBuf {
+        write_wheel(
+            dir,
+            "demo-1.0-py3-none-any.whl",
+            &[],
+            &[
+                ("demo/__init__.py", "def main():\n    pass\n"),
+                ("demo/gui.py", "def run():\n    pass\n"),
+                ("demo, data.txt", "comma\n"),
+                (
+                    "demo-1.0.dist-info/entry_points.txt",
+                    "[console_scripts]\ndemo = demo:main\n\n[gui_scripts]\ndemo-gui = demo.gui:run [gui]\n",
+                ),
+                ("demo-1.0.data/scripts/tool", "#!python\nprint('tool')\n"),
+                ("demo-1.0.data/purelib/extra.py", "EXTRA = 1\n"),
+            ],
+        )
+    }
+
+    #[test]
+    fn parses_wheel_filenames() {
+        let wheel: WheelFilename = "demo_pkg-1.0-1-cp312.cp313-abi3-manylinux_2_17_x86_64.whl"
+            .parse()
+            .unwrap();
+
+        assert_eq!(wheel.name, "demo_pkg");
+        assert_eq!(wheel.build.as_deref(), Some("1"));
+        assert_eq!(wheel.python, vec!["cp312", "cp313"]);
+        assert_eq!(wheel.abi, vec!["abi3"]);
+        assert!("demo-1.0.whl".parse::<WheelFilename>().is_err());
+        assert!("demo-1.0-py3-none-any.zip"
+            .parse::<WheelFilename>()
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn installs_files_scripts_and_record() {
+        let dir = temp_dir("wheel-install");
+        let target = test_target(&dir.join("env"));
+
+        let metadata = Wheel::open(&demo_wheel(&dir))
+            .unwrap()
+            .install(&target)
+            .unwrap();
+        assert_eq!(
+            (metadata.name.as_str(), metadata.version.as_str()),
+            ("demo", "1.0")
+        );
+
+        let site_packages = &target.site_packages;
+        assert!(site_packages.join("demo").join("__init__.py").is_file());
+        assert!(site_packages.join("extra.py").is_file());
+        assert!(!site_packages.join("demo-1.0.data").exists());
+
+        let tool = std::fs::read_to_string(target.scripts.join("tool")).unwrap();
+        assert_eq!(
+            tool,
+            format!("#!{}\nprint('tool')\n", target.python.display())
+        );
+
+        let console = std::fs::read_to_string(target.scripts.join("demo")).unwrap();
+        assert!(console.starts_with(&format!("#!{}\n", target.python.display())));
+        assert!(console.contains("from demo import main\n"));
+        assert!(console.contains("sys.exit(main())"));
+
+        let gui = std::fs::read_to_string(target.scripts.join("demo-gui")).unwrap();
+        assert!(gui.contains("from demo.gui import run\n"));
+
+        let dist_info = site_packages.join("demo-1.0.dist-info");
+        assert_eq!(
+            std::fs::read_to_string(dist_info.join("INSTALLER")).unwrap(),
+            "ezpy\n"
+        );
+
+        let record = parse_record(&std::fs::read_to_string(dist_info.join("RECORD")).unwrap());
+        let paths = record
+            .iter()
+            .map(|(path, _, _)| path.as_str())
+            .collect::<HashSet<_>>();
+        for path in [
+            "demo/__init__.py",
+            "demo, data.txt",
+            "extra.py",
+            "../../../bin/tool",
+            "../../../bin/demo",
+            "../../../bin/demo-gui",
+            "demo-1.0.dist-info/INSTALLER",
+            "demo-1.0.dist-info/RECORD",
+        ] {
+            assert!(paths.contains(path), "{} is missing from RECORD", path);
+        }
+
+        // The rows of rewritten scripts hash the installed contents.
+        let (_, hash, size) = record
+            .iter()
+            .find(|(path, _, _)| path == "../../../bin/tool")
+            .unwrap();
+        assert_eq!(*hash, digest(tool.as_bytes()));
+        assert_eq!(*size, tool.len().to_string());
+        assert!(record
+            .iter()
+            .any(|(path, hash, _)| path == "demo-1.0.dist-info/RECORD" && hash.is_empty()));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn uninstalls_recorded_files() {
+        let dir = temp_dir("wheel-uninstall");
+        let target = test_target(&dir.join("env"));
+
+        Wheel::open(&demo_wheel(&dir))
+            .unwrap()
+            .install(&target)
+            .unwrap();
+        std::fs::create_dir_all(target.site_packages.join("demo").join("__pycache__")).unwrap();
+        std::fs::write(
+            target
+                .site_packages
+                .join("demo")
+                .join("__pycache__")
+                .join("__init__.cpython-312.pyc"),
+            "",
+        )
+        .unwrap();
+        std::fs::write(target.site_packages.join("other.py"), "").unwrap();
+
+        assert_eq!(uninstall(&target, "Demo").unwrap().as_deref(), Some("1.0"));
+
+        assert!(!target.site_packages.join("demo").exists());
+        assert!(!target.site_packages.join("extra.py").exists());
+        assert!(!target.site_packages.join("demo-1.0.dist-info").exists());
+        assert!(target.site_packages.join("other.py").exists());
+        assert!(target.scripts.exists());
+        assert_eq!(std::fs::read_dir(&target.scripts).unwrap().count(), 0);
+
+        assert_eq!(uninstall(&target, "demo").unwrap(), None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn replaces_installed_version() {
+        let dir = temp_dir("wheel-upgrade");
+        let target = test_target(&dir.join("env"));
+
+        Wheel::open(&demo_wheel(&dir))
+            .unwrap()
+            .install(&target)
+            .unwrap();
+        let upgrade = write_wheel(
+            &dir,
+            "demo-2.0-py3-none-any.whl",
+            &[],
+            &[("demo/__init__.py", "VERSION = 2\n")],
+        );
+        Wheel::open(&upgrade).unwrap().install(&target).unwrap();
+
+        assert_eq!(
+            target.installed().unwrap(),
+            HashMap::from([("demo".to_string(), "2.0".to_string())])
+        );
+        assert!(!target.site_packages.join("demo").join("gui.py").exists());
+        assert_eq!(target.scripts_of("demo").unwrap(), Vec::<String>::new());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_hash_mismatch() {
+        let dir = temp_dir("wheel-hash");
+        let target = test_target(&dir.join("env"));
+
+        let old = write_wheel(
+            &dir,
+            "demo-0.9-py3-none-any.whl",
+            &[],
+            &[("demo/__init__.py", "old\n")],
+        );
+        Wheel::open(&old).unwrap().install(&target).unwrap();
+
+        let path = dir.join("demo-1.0-py3-none-any.whl");
+        write_zip(
+            &path,
+            &[
+                ("demo/__init__.py", "tampered\n"),
+                (
+                    "demo-1.0.dist-info/METADATA",
+                    "Metadata-Version: 2.1\nName: demo\nVersion: 1.0\n",
+                ),
+                (
+                    "demo-1.0.dist-info/RECORD",
+                    &format!("demo/__init__.py,{},9\n", digest(b"original\n")),
+                ),
+            ],
+        );
+
+        let error = Wheel::open(&path).unwrap().install(&target).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Hash mismatch for 'demo/__init__.py' in demo, the wheel may be corrupted"
+        );
+
+        // The installed version is left untouched.
+        assert_eq!(
+            std::fs::read_to_string(target.site_packages.join("demo/__init__.py")).unwrap(),
+            "old\n"
+        );
+        assert!(target
+            .site_packages
+            .join("demo-0.9.dist-info/RECORD")
+            .is_file());
+        assert!(!target.site_packages.join("demo-1.0.dist-info").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn verifies_record_hash_algorithms() {
+        let dir = temp_dir("wheel-hash-algorithms");
+        let target = test_target(&dir.join("env"));
+        let metadata = "Metadata-Version: 2.1\nName: demo\nVersion: 1.0\n";
+
+        let path = dir.join("demo-1.0-py3-none-any.whl");
+        write_zip(
+            &path,
+            &[
+                ("demo/a.py", "a\n"),
+                ("demo/b.py", "b\n"),
+                ("demo-1.0.dist-info/METADATA", metadata),
+                (
+                    "demo-1.0.dist-info/RECORD",
+                    &format!(
+                        "demo/a.py,{},2\ndemo/b.py,{},2\n",
+                        record_digest("sha384", b"a\n").unwrap(),
+                        record_digest("sha512", b"b\n").unwrap()
+                    ),
+                ),
+            ],
+        );
+        Wheel::open(&path).unwrap().install(&target).unwrap();
+        assert!(target.site_packages.join("demo/b.py").is_file());
+
+        write_zip(
+            &path,
+            &[
+                ("demo/a.py", "a\n"),
+                ("demo-1.0.dist-info/METADATA", metadata),
+                ("demo-1.0.dist-info/RECORD", "demo/a.py,md5=abc,2\n"),
+            ],
+        );
+        let error = Wheel::open(&path).unwrap().install(&target).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "demo uses the insecure md5 hash for 'demo/a.py' in its RECORD"
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn rejects_data_files_outside_the_environment() {
+        let dir = temp_dir("wheel-data-escape");
+        let target = test_target(&dir.join("env"));
+        let outside = dir.join("outside");
+
+        for name in [
+            format!("demo-1.0.data/data/{}", outside.display()),
+            "demo-1.0.data/data/../../outside".to_string(),
+        ] {
+            let path = write_wheel(&dir, "demo-1.0-py3-none-any.whl", &[], &[(&name, "x\n")]);
+            let error = Wheel::open(&path).unwrap().install(&target).unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("Refusing to install unsafe path '{}'", name)
+            );
+        }
+        assert!(!outside.exists());
+        assert!(!target.site_packages.exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn parses_quoted_record_rows() {
+        assert_eq!(
+            parse_record("\"a,\"\"b\"\".py\",sha256=x,3\nc.py,,\n"),
+            vec![
+                (
+                    "a,\"b\".py".to_string(),
+                    "sha256=x".to_string(),
+                    "3".to_string()
+                ),
+                ("c.py".to_string(), String::new(), String::new()),
+            ]
+        );
+        assert_eq!(quote_record("a,\"b\".py"), "\"a,\"\"b\"\".py\"");
+    }
+}
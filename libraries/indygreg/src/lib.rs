@@ -23,3 +23,21 @@ pub fn ensure_python_version(version: VersionString) -> eyre::Result<()> {
         ))
     }
 }
+
+/// Lists every Python version installed in the indygreg directory.
+pub fn installed_versions() -> eyre::Result<Vec<VersionString>> {
+    let dir = install::install_home_indygreg()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(version) = name.strip_prefix("python-") {
+            versions.push(version.to_string());
+        }
+    }
+
+    Ok(versions)
+}